@@ -2,12 +2,12 @@
 
 # Deklariere das Array
 PROVIDER_CONTAINERS=(
-  "car_off_provider"
-  "car_on_provider"
-  "carkey_lock_provider"
-  "carkey_unlock_provider"
+  "vehicle_body_provider"
   "wheelchair_distance_decreasing_provider"
   "wheelchair_distance_increasing_provider"
+  "wheelchair_assistant_state_provider"
+  "wheelchair_scenario_provider"
+  "wheelchair_kinematics_provider"
 )
 
 APPLICATION_CONTAINERS=(
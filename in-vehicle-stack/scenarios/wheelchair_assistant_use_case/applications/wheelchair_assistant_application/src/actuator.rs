@@ -0,0 +1,300 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! Actuators that move the door, the seat and the steering wheel between their drive and assist
+//! positions.
+
+use std::fmt;
+
+use log::{debug, info, warn};
+use tokio::sync::{watch, Mutex};
+use tokio::time::{sleep, Duration};
+
+const STEP_PERCENT: u8 = 10;
+
+/// The end positions an actuator can be moved to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ActuatorTarget {
+    /// The position for driving, e.g. the door is closed.
+    Drive,
+    /// The position for getting the wheelchair in, e.g. the door is open.
+    Assist,
+}
+
+impl ActuatorTarget {
+    /// The target's position.
+    pub fn position(self) -> ActuatorPosition {
+        match self {
+            ActuatorTarget::Drive => ActuatorPosition::DRIVE,
+            ActuatorTarget::Assist => ActuatorPosition::ASSIST,
+        }
+    }
+}
+
+/// The position of an actuator between its drive (0 %) and assist (100 %) end positions.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ActuatorPosition {
+    pub percent: u8,
+}
+
+impl ActuatorPosition {
+    pub const DRIVE: ActuatorPosition = ActuatorPosition { percent: 0 };
+    pub const ASSIST: ActuatorPosition = ActuatorPosition { percent: 100 };
+}
+
+impl fmt::Display for ActuatorPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} %", self.percent)
+    }
+}
+
+/// Errors for moving an actuator.
+#[derive(Debug, Eq, PartialEq)]
+pub enum ActuatorError {
+    /// The actuator was blocked before it reached the target.
+    Obstructed {
+        actuator: String,
+        target: ActuatorTarget,
+        position: ActuatorPosition,
+    },
+}
+
+impl fmt::Display for ActuatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActuatorError::Obstructed {
+                actuator,
+                target,
+                position,
+            } => write!(
+                f,
+                "The {actuator} was obstructed at {position} while moving to {target:?}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ActuatorError {}
+
+/// An actuator that moves between its drive and assist positions and reports where it is.
+#[tonic::async_trait]
+pub trait Actuator: Send + Sync {
+    /// The actuator's name, used for logging.
+    fn name(&self) -> &str;
+
+    /// Receive the actuator's position while it moves.
+    fn position(&self) -> watch::Receiver<ActuatorPosition>;
+
+    /// Move the actuator and wait until it has reached the target. Commands are executed one
+    /// after the other.
+    ///
+    /// # Arguments
+    /// * `target` - The position to move to.
+    async fn move_to(&self, target: ActuatorTarget) -> Result<(), ActuatorError>;
+}
+
+/// Configuration of a simulated actuator.
+#[derive(Clone, Debug)]
+pub struct SimulatedActuatorConfig {
+    /// The time to move from one end position to the other.
+    pub travel_time: Duration,
+    /// The position at which the actuator is blocked on its way to the assist position, it is
+    /// never blocked if `None`.
+    pub obstruction_at: Option<ActuatorPosition>,
+}
+
+/// An actuator that simulates its travel.
+pub struct SimulatedActuator {
+    name: String,
+    config: SimulatedActuatorConfig,
+    position: watch::Sender<ActuatorPosition>,
+    command_lock: Mutex<()>,
+}
+
+impl SimulatedActuator {
+    /// Create an actuator in the drive position.
+    ///
+    /// # Arguments
+    /// * `name` - The actuator's name.
+    /// * `config` - The actuator's configuration.
+    pub fn new(name: &str, config: SimulatedActuatorConfig) -> Self {
+        let (position, _) = watch::channel(ActuatorPosition::DRIVE);
+
+        SimulatedActuator {
+            name: name.to_string(),
+            config,
+            position,
+            command_lock: Mutex::new(()),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Actuator for SimulatedActuator {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn position(&self) -> watch::Receiver<ActuatorPosition> {
+        self.position.subscribe()
+    }
+
+    async fn move_to(&self, target: ActuatorTarget) -> Result<(), ActuatorError> {
+        let _command_guard = self.command_lock.lock().await;
+
+        let target_percent = target.position().percent;
+        let step_time = self.config.travel_time * u32::from(STEP_PERCENT) / 100;
+        let mut percent = self.position.borrow().percent;
+
+        debug!("Moving the {} from {percent} % to {target:?}", self.name);
+
+        while percent != target_percent {
+            let next_percent = if target_percent > percent {
+                percent.saturating_add(STEP_PERCENT).min(target_percent)
+            } else {
+                percent.saturating_sub(STEP_PERCENT).max(target_percent)
+            };
+
+            // An obstruction only blocks the way towards the assist position.
+            if let Some(obstruction) = self.config.obstruction_at {
+                if next_percent > percent && (percent..=next_percent).contains(&obstruction.percent)
+                {
+                    self.position.send_replace(obstruction);
+                    warn!("The {} is obstructed at {obstruction}", self.name);
+
+                    return Err(ActuatorError::Obstructed {
+                        actuator: self.name.clone(),
+                        target,
+                        position: obstruction,
+                    });
+                }
+            }
+
+            sleep(step_time).await;
+            percent = next_percent;
+            self.position.send_replace(ActuatorPosition { percent });
+        }
+
+        info!("The {} has reached {target:?}", self.name);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use tokio::time::Instant;
+
+    const TRAVEL_TIME: Duration = Duration::from_millis(1000);
+
+    /// Create an actuator that travels in `TRAVEL_TIME`.
+    ///
+    /// # Arguments
+    /// * `obstruction_percent` - The position of the obstruction, if any.
+    fn actuator(obstruction_percent: Option<u8>) -> Arc<SimulatedActuator> {
+        Arc::new(SimulatedActuator::new(
+            "door",
+            SimulatedActuatorConfig {
+                travel_time: TRAVEL_TIME,
+                obstruction_at: obstruction_percent.map(|percent| ActuatorPosition { percent }),
+            },
+        ))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_actuator_travels_between_the_end_positions() {
+        let actuator = actuator(None);
+        let position = actuator.position();
+        let start = Instant::now();
+
+        assert_eq!(actuator.move_to(ActuatorTarget::Assist).await, Ok(()));
+        assert_eq!(*position.borrow(), ActuatorPosition::ASSIST);
+        assert_eq!(start.elapsed(), TRAVEL_TIME);
+
+        assert_eq!(actuator.move_to(ActuatorTarget::Drive).await, Ok(()));
+        assert_eq!(*position.borrow(), ActuatorPosition::DRIVE);
+        assert_eq!(start.elapsed(), 2 * TRAVEL_TIME);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_position_is_reported_while_the_actuator_moves() {
+        let actuator = actuator(None);
+        let position = actuator.position();
+
+        let moving = Arc::clone(&actuator);
+        let command = tokio::spawn(async move { moving.move_to(ActuatorTarget::Assist).await });
+
+        sleep(TRAVEL_TIME * 55 / 100).await;
+        assert_eq!(*position.borrow(), ActuatorPosition { percent: 50 });
+
+        assert_eq!(command.await.unwrap(), Ok(()));
+        assert_eq!(*position.borrow(), ActuatorPosition::ASSIST);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn moving_to_the_current_position_returns_right_away() {
+        let actuator = actuator(None);
+        let start = Instant::now();
+
+        assert_eq!(actuator.move_to(ActuatorTarget::Drive).await, Ok(()));
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn an_obstruction_stops_the_way_to_the_assist_position() {
+        let actuator = actuator(Some(35));
+        let position = actuator.position();
+
+        assert_eq!(
+            actuator.move_to(ActuatorTarget::Assist).await,
+            Err(ActuatorError::Obstructed {
+                actuator: "door".to_string(),
+                target: ActuatorTarget::Assist,
+                position: ActuatorPosition { percent: 35 },
+            })
+        );
+        assert_eq!(*position.borrow(), ActuatorPosition { percent: 35 });
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn an_obstructed_actuator_returns_to_the_drive_position() {
+        let actuator = actuator(Some(35));
+        let position = actuator.position();
+
+        assert!(actuator.move_to(ActuatorTarget::Assist).await.is_err());
+
+        // The obstruction does not block the way back.
+        let start = Instant::now();
+        assert_eq!(actuator.move_to(ActuatorTarget::Drive).await, Ok(()));
+        assert_eq!(*position.borrow(), ActuatorPosition::DRIVE);
+        assert_eq!(start.elapsed(), TRAVEL_TIME * 4 / 10);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn commands_are_executed_one_after_the_other() {
+        let actuator = actuator(None);
+        let position = actuator.position();
+        let start = Instant::now();
+
+        let moving = Arc::clone(&actuator);
+        let assist = tokio::spawn(async move { moving.move_to(ActuatorTarget::Assist).await });
+
+        // The actuator is reversed halfway, but only after it has reached the assist position.
+        sleep(TRAVEL_TIME / 2).await;
+        let moving = Arc::clone(&actuator);
+        let drive = tokio::spawn(async move { moving.move_to(ActuatorTarget::Drive).await });
+
+        assert_eq!(assist.await.unwrap(), Ok(()));
+        assert_eq!(*position.borrow(), ActuatorPosition::ASSIST);
+        assert_eq!(start.elapsed(), TRAVEL_TIME);
+
+        assert_eq!(drive.await.unwrap(), Ok(()));
+        assert_eq!(*position.borrow(), ActuatorPosition::DRIVE);
+        assert_eq!(start.elapsed(), 2 * TRAVEL_TIME);
+    }
+}
@@ -2,60 +2,227 @@
 // Licensed under the MIT license.
 // SPDX-License-Identifier: MIT
 
+mod actuator;
+
 use std::env;
+use std::sync::Arc;
 
+use wheelchair_digital_twin_model::assistant_state::AssistantState;
 use wheelchair_digital_twin_model::{car_v1, Metadata};
-use wheelchair_digital_twin_providers_common::constants::chariott::{
-    INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_KIND,
-    INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_REFERENCE, INVEHICLE_DIGITAL_TWIN_SERVICE_NAME,
-    INVEHICLE_DIGITAL_TWIN_SERVICE_NAMESPACE, INVEHICLE_DIGITAL_TWIN_SERVICE_VERSION,
-};
 use wheelchair_digital_twin_providers_common::constants::{
     constraint_type, digital_twin_operation, digital_twin_protocol,
 };
+use wheelchair_digital_twin_providers_common::health::HealthMonitor;
+use wheelchair_digital_twin_providers_common::managed_subscribe_provider::{
+    run_managed_subscribe_provider, ManagedSubscribeEntity, RegisteredProvider,
+};
+use wheelchair_digital_twin_providers_common::mqtt_consumer::consume;
+use wheelchair_digital_twin_providers_common::retry::RetryPolicy;
+use wheelchair_digital_twin_providers_common::settings::{
+    load_settings, validate_authority, validate_non_zero, validate_uri, RetrySettings,
+    SettingsError, ValidateSettings,
+};
+use wheelchair_digital_twin_providers_common::shutdown::Shutdown;
 use wheelchair_digital_twin_providers_common::utils::{
-    discover_digital_twin_provider_using_ibeji, discover_service_using_chariott, get_uri,
+    discover_digital_twin_provider_using_ibeji, get_uri,
 };
+
 use env_logger::{Builder, Target};
 use interfaces::module::managed_subscribe::v1::managed_subscribe_client::ManagedSubscribeClient;
 use interfaces::module::managed_subscribe::v1::{
     Constraint, SubscriptionInfoRequest, SubscriptionInfoResponse,
 };
-use interfaces::invehicle_digital_twin::v1::invehicle_digital_twin_client::InvehicleDigitalTwinClient;
-use interfaces::invehicle_digital_twin::v1::{EndpointInfo, EntityAccessInfo, RegisterRequest};
-use log::{debug, info, warn, LevelFilter};
-use paho_mqtt as mqtt;
-use tokio::signal;
-use tokio::task::JoinHandle;
-use tokio::time::{sleep, Duration};
-use tonic::{Request, Status};
-use tokio::sync::watch;
-use uuid::Uuid;
+use log::{info, warn, LevelFilter};
 use serde_derive::{Deserialize, Serialize};
-use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::watch;
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::Duration;
+use tonic::{Request, Status};
+
+use crate::actuator::{
+    Actuator, ActuatorPosition, ActuatorTarget, SimulatedActuator, SimulatedActuatorConfig,
+};
 
 const FREQUENCY_MS_FLAG: &str = "freq_ms=";
 const MQTT_CLIENT_ID: &str = "wheelchair-assistant-consumer";
 
-// TODO: These could be added in configuration
-const CHARIOTT_SERVICE_DISCOVERY_URI: &str = "http://0.0.0.0:50000";
-
+const DEFAULT_CHARIOTT_SERVICE_DISCOVERY_URI: &str = "http://0.0.0.0:50000";
+const DEFAULT_SEAT_PROVIDER_AUTHORITY: &str = "0.0.0.0:4070";
+const DEFAULT_DOOR_PROVIDER_AUTHORITY: &str = "0.0.0.0:4080";
+const DEFAULT_STEERING_PROVIDER_AUTHORITY: &str = "0.0.0.0:4090";
 const DEFAULT_FREQUENCY_MS: u64 = 10000; // 10 seconds
+const DEFAULT_MIN_INTERVAL_MS: u64 = 100;
+const DEFAULT_ACTUATOR_TRAVEL_TIME_MS: u64 = 2000;
+
+/// Settings of the wheelchair assistant application.
+#[derive(Debug, Serialize, Deserialize)]
+struct Settings {
+    /// Chariott's Service Discovery URI.
+    chariott_uri: String,
+    /// How discovery, registration and the requests to the In-Vehicle Digital Twin are retried.
+    #[serde(default)]
+    retry: RetrySettings,
+    /// The authority of the seat position endpoint.
+    seat_provider_authority: String,
+    /// The authority of the door endpoint.
+    door_provider_authority: String,
+    /// The authority of the steering wheel position endpoint.
+    steering_provider_authority: String,
+    /// The interval after which an unchanged assistant state is published again, the freq_ms flag
+    /// overrides it.
+    frequency_ms: u64,
+    /// The default publish interval of the door, seat and steering wheel properties.
+    min_interval_ms: u64,
+    /// The time the simulated door, seat and steering wheel take to move between positions.
+    actuator_travel_time_ms: u64,
+    /// The position in percent at which the simulated door is blocked on its way to the assist
+    /// position, it is never blocked if not set.
+    door_obstruction_percent: Option<u8>,
+    /// The position in percent at which the simulated seat is blocked.
+    seat_obstruction_percent: Option<u8>,
+    /// The position in percent at which the simulated steering wheel is blocked.
+    steering_obstruction_percent: Option<u8>,
+}
 
-// Constants used for retry logic
-const MAX_RETRIES: i32 = 10; // for demo purposes we will retry a maximum of 10 times
-                             // By default we will wait 5 seconds between retry attempts
-const DURATION_BETWEEN_ATTEMPTS: Duration = Duration::from_secs(5);
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            chariott_uri: DEFAULT_CHARIOTT_SERVICE_DISCOVERY_URI.to_string(),
+            retry: RetrySettings::default(),
+            seat_provider_authority: DEFAULT_SEAT_PROVIDER_AUTHORITY.to_string(),
+            door_provider_authority: DEFAULT_DOOR_PROVIDER_AUTHORITY.to_string(),
+            steering_provider_authority: DEFAULT_STEERING_PROVIDER_AUTHORITY.to_string(),
+            frequency_ms: DEFAULT_FREQUENCY_MS,
+            min_interval_ms: DEFAULT_MIN_INTERVAL_MS,
+            actuator_travel_time_ms: DEFAULT_ACTUATOR_TRAVEL_TIME_MS,
+            door_obstruction_percent: None,
+            seat_obstruction_percent: None,
+            steering_obstruction_percent: None,
+        }
+    }
+}
+
+impl ValidateSettings for Settings {
+    fn validate(&self) -> Result<(), SettingsError> {
+        validate_uri("chariott_uri", &self.chariott_uri)?;
+        self.retry.validate()?;
+        validate_authority("seat_provider_authority", &self.seat_provider_authority)?;
+        validate_authority("door_provider_authority", &self.door_provider_authority)?;
+        validate_authority(
+            "steering_provider_authority",
+            &self.steering_provider_authority,
+        )?;
+        validate_non_zero("frequency_ms", self.frequency_ms)?;
+        validate_non_zero("min_interval_ms", self.min_interval_ms)?;
+        validate_obstruction("door_obstruction_percent", self.door_obstruction_percent)?;
+        validate_obstruction("seat_obstruction_percent", self.seat_obstruction_percent)?;
+        validate_obstruction(
+            "steering_obstruction_percent",
+            self.steering_obstruction_percent,
+        )
+    }
+}
 
-static door_open: AtomicBool = AtomicBool::new(false);
-static steering_wheel_up: AtomicBool = AtomicBool::new(false);
-static driver_seat_back: AtomicBool = AtomicBool::new(false);
+/// Check that an obstruction is between the drive and the assist position.
+///
+/// # Arguments
+/// * `field` - The setting's name.
+/// * `obstruction_percent` - The position of the obstruction, if any.
+fn validate_obstruction(field: &str, obstruction_percent: Option<u8>) -> Result<(), SettingsError> {
+    match obstruction_percent {
+        Some(percent)
+            if percent == ActuatorPosition::DRIVE.percent
+                || percent > ActuatorPosition::ASSIST.percent =>
+        {
+            Err(SettingsError::Invalid {
+                field: field.to_string(),
+                message: format!(
+                    "must be greater than {} and not greater than {}",
+                    ActuatorPosition::DRIVE.percent,
+                    ActuatorPosition::ASSIST.percent
+                ),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Publish whether an actuator has reached its assist position.
+///
+/// # Arguments
+/// * `actuator` - The actuator.
+/// * `sender` - Sender for the actuator's property.
+fn start_actuator_feedback(actuator: &dyn Actuator, sender: watch::Sender<bool>) {
+    let mut position = actuator.position();
+    tokio::spawn(async move {
+        loop {
+            let in_assist_position = *position.borrow() == ActuatorPosition::ASSIST;
+            sender.send_if_modified(|current| {
+                let modified = *current != in_assist_position;
+                *current = in_assist_position;
+                modified
+            });
+
+            if position.changed().await.is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Move the actuators whenever the requested position changes and wait for all of them to confirm
+/// before reporting that the car is ready.
+///
+/// # Arguments
+/// * `actuators` - The actuators.
+/// * `assist_requested` - Receiver for whether the actuators should be in the assist position.
+fn start_actuator_control(
+    actuators: Vec<Arc<dyn Actuator>>,
+    mut assist_requested: watch::Receiver<bool>,
+) {
+    tokio::spawn(async move {
+        while assist_requested.changed().await.is_ok() {
+            let target = if *assist_requested.borrow() {
+                ActuatorTarget::Assist
+            } else {
+                ActuatorTarget::Drive
+            };
+            info!("Moving the actuators to {target:?}");
+
+            let mut moves = JoinSet::new();
+            for actuator in &actuators {
+                let actuator = Arc::clone(actuator);
+                moves.spawn(async move { actuator.move_to(target).await });
+            }
+
+            let mut confirmed = true;
+            while let Some(result) = moves.join_next().await {
+                match result {
+                    Ok(Ok(())) => {}
+                    Ok(Err(err)) => {
+                        warn!("{err}");
+                        confirmed = false;
+                    }
+                    Err(err) => {
+                        warn!("An actuator command failed due to '{err}'");
+                        confirmed = false;
+                    }
+                }
+            }
+
+            match (target, confirmed) {
+                (ActuatorTarget::Drive, true) => info!("The car is ready to drive."),
+                (ActuatorTarget::Assist, true) => info!("The car is ready for the wheelchair."),
+                (_, false) => warn!("Not all actuators reached {target:?}, the car is not ready."),
+            }
+        }
+    });
+}
 
 #[derive(Debug, Serialize, Deserialize)]
-#[serde(tag="type")]
 struct WheelchairAssistantStateProperty {
     #[serde(rename = "WheelchairAssistantState")]
-    car_wheelchair_assistant_state: car_v1::car::car_wheelchair_assistant_state::TYPE,
+    car_wheelchair_assistant_state: car_v1::car::wheelchair_assistant_state::TYPE,
     #[serde(rename = "$metadata")]
     metadata: Metadata,
 }
@@ -67,7 +234,7 @@ struct WheelchairAssistantStateProperty {
 /// * `constraints` - Constraints for the managed topic.
 async fn get_car_adjust_subscription_info(
     managed_subscribe_uri: &str,
-    constraints: Vec<Constraint>
+    constraints: Vec<Constraint>,
 ) -> Result<SubscriptionInfoResponse, Status> {
     // Create gRPC client.
     let mut client = ManagedSubscribeClient::connect(managed_subscribe_uri.to_string())
@@ -75,7 +242,7 @@ async fn get_car_adjust_subscription_info(
         .map_err(|err| Status::from_error(err.into()))?;
 
     let request = Request::new(SubscriptionInfoRequest {
-        entity_id: car_v1::car::car_wheelchair_assistant_state::ID.to_string(),              
+        entity_id: car_v1::car::wheelchair_assistant_state::ID.to_string(),
         constraints,
     });
 
@@ -89,271 +256,114 @@ async fn get_car_adjust_subscription_info(
 /// # Arguments
 /// * `broker_uri` - The broker URI.
 /// * `topic` - The topic.
-async fn receive_car_adjust_updates(
+/// * `assist_requested` - Sender for whether the actuators should be in the assist position.
+/// * `shutdown` - Stops receiving updates.
+/// * `health` - Probes the connection to the broker.
+fn receive_car_adjust_updates(
     broker_uri: &str,
     topic: &str,
+    assist_requested: watch::Sender<bool>,
+    shutdown: &Shutdown,
+    health: &HealthMonitor,
 ) -> Result<JoinHandle<()>, String> {
-    // Create a unique id for the client.
-    let client_id = format!("{MQTT_CLIENT_ID}-{}", Uuid::new_v4());
-
-    let create_opts = mqtt::CreateOptionsBuilder::new()
-        .server_uri(broker_uri)
-        .client_id(client_id)
-        .finalize();
-
-    let client = mqtt::Client::new(create_opts)
-        .map_err(|err| format!("Failed to create the client due to '{err:?}'"))?;
-
-    let receiver = client.start_consuming();
-
-    // Setup task to handle clean shutdown.
-    let ctrlc_cli = client.clone();
-    tokio::spawn(async move {
-        _ = signal::ctrl_c().await;
-
-        // Tells the client to shutdown consuming thread.
-        ctrlc_cli.stop_consuming();
-    });
-
-    // Last Will and Testament
-    let lwt = mqtt::MessageBuilder::new()
-        .topic("test")
-        .payload("Receiver lost connection")
-        .finalize();
-
-    let conn_opts = mqtt::ConnectOptionsBuilder::new_v5()
-        .keep_alive_interval(Duration::from_secs(30))
-        .clean_session(false)
-        .will_message(lwt)
-        .finalize();
-
-    let _connect_response = client
-        .connect(conn_opts)
-        .map_err(|err| format!("Failed to connect due to '{err:?}"));
-
-    let mut _subscribe_response = client
-        .subscribe(topic, mqtt::types::QOS_1)
-        .map_err(|err| format!("Failed to subscribe to topic {topic} due to '{err:?}'"));
-
-    // Copy topic for separate thread.
-    let topic_string = topic.to_string();
-
-    let sub_handle = tokio::spawn(async move {
-        for msg in receiver.iter() {
-            if let Some(msg) = msg {
-                // Here we log the message received. This could be expanded to parsing the message,
-                // Obtaining the wheelchair assistant state and making decisions based on the weight
-                // For example, adjusting body functions or powertrain of the towing vehicle.
-                let payload_str = msg.payload_str();
-                let msg_des: WheelchairAssistantStateProperty = serde_json::from_str(&payload_str).unwrap();
-                let new_state = msg_des.car_wheelchair_assistant_state;
-                info!("{}", new_state);
-                info!("{}", msg);
-
-                if new_state == 3 { //HOLD
-                    info!("Adjusting the car!");
-                    door_open.store(true, Ordering::Relaxed);
-                    steering_wheel_up.store(true, Ordering::Relaxed);
-                    driver_seat_back.store(true, Ordering::Relaxed);
-                } else {
-                    info!("No need to rearrange");
-                }
-
-            } else if !client.is_connected() {
-                if client.reconnect().is_ok() {
-                    _subscribe_response = client
-                        .subscribe(topic_string.as_str(), mqtt::types::QOS_1)
-                        .map_err(|err| {
-                            format!("Failed to subscribe to topic {topic_string} due to '{err:?}'")
-                        });
-                } else {
-                    break;
-                }
+    consume(
+        broker_uri,
+        MQTT_CLIENT_ID,
+        topic,
+        shutdown,
+        health,
+        move |property: WheelchairAssistantStateProperty| {
+            let new_state = property.car_wheelchair_assistant_state;
+            info!("{}", new_state);
+
+            // The door is opened and the seat and steering wheel are moved out of the way while
+            // the wheelchair is held, and returned once it is not.
+            let assist = new_state == AssistantState::Hold;
+            if assist {
+                info!("Adjusting the car!");
+            } else {
+                info!("No need to rearrange");
             }
-        }
-
-        if client.is_connected() {
-            debug!("Disconnecting");
-            client.unsubscribe(topic_string.as_str()).unwrap();
-            client.disconnect(None).unwrap();
-        }
-    });
 
-    Ok(sub_handle)
+            assist_requested.send_if_modified(|current| {
+                let modified = *current != assist;
+                *current = assist;
+                modified
+            });
+        },
+    )
 }
 
-/// Register the car seat adjustment property's endpoint.
+/// Receive the wheelchair assistant state from its provider and move the actuators accordingly
+/// until the shutdown.
 ///
 /// # Arguments
-/// * `invehicle_digital_twin_uri` - The In-Vehicle Digital Twin URI.
-/// * `provider_uri` - The provider's URI.
-async fn provider_register_seat_adjustment(
-    invehicle_digital_twin_uri: &str,
-    provider_uri: &str,
-) -> Result<(), Status> {
-    let endpoint_info = EndpointInfo {
-        protocol: digital_twin_protocol::GRPC.to_string(),
-        operations: vec![digital_twin_operation::MANAGEDSUBSCRIBE.to_string()],
-        uri: provider_uri.to_string(),
-        context: "GetSubscriptionInfo".to_string(),
-    };
-
-    let entity_access_info = EntityAccessInfo {
-        name: car_v1::car::is_car_seat_in_assist_position::NAME.to_string(),           
-        id: car_v1::car::is_car_seat_in_assist_position::ID.to_string(),                 
-        description: car_v1::car::is_car_seat_in_assist_position::DESCRIPTION.to_string(),       
-        endpoint_info_list: vec![endpoint_info],
-    };
+/// * `provider` - The registered actuator providers.
+/// * `frequency_ms` - The longest time between two publishes of the state.
+/// * `assist_requested` - Sender for whether the actuators should be in the assist position.
+async fn receive_wheelchair_assistant_state(
+    provider: RegisteredProvider,
+    frequency_ms: &str,
+    assist_requested: watch::Sender<bool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let RegisteredProvider {
+        invehicle_digital_twin_uri,
+        shutdown,
+        health,
+        retry_policy,
+    } = provider;
 
-    let mut client = InvehicleDigitalTwinClient::connect(invehicle_digital_twin_uri.to_string())
-        .await
-        .map_err(|e| Status::internal(e.to_string()))?;
-    let request = tonic::Request::new(RegisterRequest {
-        entity_access_info_list: vec![entity_access_info],
+    // Retrieve the provider URI.
+    let discovery = retry_policy.retry("find the provider for WheelchairAssistantState", || {
+        discover_digital_twin_provider_using_ibeji(
+            &invehicle_digital_twin_uri,
+            car_v1::car::wheelchair_assistant_state::ID,
+            digital_twin_protocol::GRPC,
+            &[digital_twin_operation::MANAGEDSUBSCRIBE.to_string()],
+        )
     });
-    let _response = client.register(request).await?;
-
-    Ok(())
-}
-
-/// Register the car door adjustment property's endpoint.
-///
-/// # Arguments
-/// * `invehicle_digital_twin_uri` - The In-Vehicle Digital Twin URI.
-/// * `provider_uri` - The provider's URI.
-async fn provider_register_door_adjustment(
-    invehicle_digital_twin_uri: &str,
-    provider_uri: &str,
-) -> Result<(), Status> {
-    let endpoint_info = EndpointInfo {
-        protocol: digital_twin_protocol::GRPC.to_string(),
-        operations: vec![digital_twin_operation::MANAGEDSUBSCRIBE.to_string()],
-        uri: provider_uri.to_string(),
-        context: "GetSubscriptionInfo".to_string(),
+    let Some(endpoint_info) = shutdown.run_until(discovery).await.transpose()? else {
+        return Ok(());
     };
+    let managed_subscribe_uri = endpoint_info.uri;
+    info!("The Managed Subscribe URI for the WheelchairAssistantState property's provider is {managed_subscribe_uri}");
+
+    // Create constraints for the managed subscribe call, changes are published as they happen and
+    // an unchanged value is published again after the frequency.
+    let constraints = vec![
+        Constraint {
+            r#type: constraint_type::ON_CHANGE.to_string(),
+            value: true.to_string(),
+        },
+        Constraint {
+            r#type: constraint_type::MAX_INTERVAL_MS.to_string(),
+            value: frequency_ms.to_string(),
+        },
+    ];
 
-    let entity_access_info = EntityAccessInfo {
-        name: car_v1::car::is_car_door_open::NAME.to_string(),           
-        id: car_v1::car::is_car_door_open::ID.to_string(),                 
-        description: car_v1::car::is_car_door_open::DESCRIPTION.to_string(),       
-        endpoint_info_list: vec![endpoint_info],
-    };
-
-    let mut client = InvehicleDigitalTwinClient::connect(invehicle_digital_twin_uri.to_string())
-        .await
-        .map_err(|e| Status::internal(e.to_string()))?;
-    let request = tonic::Request::new(RegisterRequest {
-        entity_access_info_list: vec![entity_access_info],
+    // Get the subscription information for a managed topic with constraints.
+    let subscription = retry_policy.retry("subscribe to WheelchairAssistantState", || {
+        get_car_adjust_subscription_info(&managed_subscribe_uri, constraints.clone())
     });
-    let _response = client.register(request).await?;
-
-    Ok(())
-}
-
-/// Register the car steering wheel adjustment property's endpoint.
-///
-/// # Arguments
-/// * `invehicle_digital_twin_uri` - The In-Vehicle Digital Twin URI.
-/// * `provider_uri` - The provider's URI.
-async fn provider_register_steering_wheel_adjustment(
-    invehicle_digital_twin_uri: &str,
-    provider_uri: &str,
-) -> Result<(), Status> {
-    let endpoint_info = EndpointInfo {
-        protocol: digital_twin_protocol::GRPC.to_string(),
-        operations: vec![digital_twin_operation::MANAGEDSUBSCRIBE.to_string()],
-        uri: provider_uri.to_string(),
-        context: "GetSubscriptionInfo".to_string(),
-    };
-
-    let entity_access_info = EntityAccessInfo {
-        name: car_v1::car::is_car_steeringwheel_in_assist_position::NAME.to_string(),           
-        id: car_v1::car::is_car_steeringwheel_in_assist_position::ID.to_string(),                 
-        description: car_v1::car::is_car_steeringwheel_in_assist_position::DESCRIPTION.to_string(),       
-        endpoint_info_list: vec![endpoint_info],
+    let Some(subscription_info) = shutdown.run_until(subscription).await.transpose()? else {
+        return Ok(());
     };
 
-    let mut client = InvehicleDigitalTwinClient::connect(invehicle_digital_twin_uri.to_string())
-        .await
-        .map_err(|e| Status::internal(e.to_string()))?;
-    let request = tonic::Request::new(RegisterRequest {
-        entity_access_info_list: vec![entity_access_info],
-    });
-    let _response = client.register(request).await?;
-
-    Ok(())
-}
-
-/// Start the seat adjustment data stream.
-fn provider_start_seat_adjustment_data_stream() {
-    debug!("Starting the Provider's seat adjustment data stream.");
-    
-    let (sender, reciever) = watch::channel(driver_seat_back.load(Ordering::Relaxed));
-    tokio::spawn(async move {
-
-        loop {
-            debug!(
-                "Recording new value for {} of {}",
-                car_v1::car::is_car_seat_in_assist_position::ID,
-                driver_seat_back.load(Ordering::Relaxed)
-            );
-
-            if let Err(err) = sender.send(driver_seat_back.load(Ordering::Relaxed)) {
-                warn!("Failed to get new value due to '{err:?}'");
-                break;
-            }
-
-            debug!("Completed the publish request");
-        }
-    });
-}
-
-/// Start the door adjustment data stream.
-fn provider_start_door_adjustment_data_stream() {
-    debug!("Starting the Provider's door adjustment data stream.");
-    
-    let (sender, reciever) = watch::channel(door_open.load(Ordering::Relaxed));
-    tokio::spawn(async move {
-
-        loop {
-            debug!(
-                "Recording new value for {} of {}",
-                car_v1::car::is_car_door_open::ID,
-                door_open.load(Ordering::Relaxed)
-            );
-
-            if let Err(err) = sender.send(door_open.load(Ordering::Relaxed)) {
-                warn!("Failed to get new value due to '{err:?}'");
-                break;
-            }
-
-            debug!("Completed the publish request");
-        }
-    });
-}
+    // Deconstruct subscription information.
+    let broker_uri = get_uri(&subscription_info.uri)?;
+    let topic = subscription_info.context;
+    info!(
+        "The broker URI for the car_wheelchair_assistant_state property's provider is {broker_uri}"
+    );
 
-/// Start the steering wheel adjustment data stream.
-fn provider_start_steering_wheel_adjustment_data_stream() {
-    debug!("Starting the Provider's steering wheel adjustment data stream.");
-    
-    let (sender, reciever) = watch::channel(steering_wheel_up.load(Ordering::Relaxed));
-    tokio::spawn(async move {
+    // Subscribe to topic.
+    let sub_handle =
+        receive_car_adjust_updates(&broker_uri, &topic, assist_requested, &shutdown, &health)?;
 
-        loop {
-            debug!(
-                "Recording new value for {} of {}",
-                car_v1::car::is_car_steeringwheel_in_assist_position::ID,
-                steering_wheel_up.load(Ordering::Relaxed)
-            );
-
-            if let Err(err) = sender.send(steering_wheel_up.load(Ordering::Relaxed)) {
-                warn!("Failed to get new value due to '{err:?}'");
-                break;
-            }
+    // Wait for subscriber task to cleanly shutdown, it stops on control-c or SIGTERM.
+    _ = sub_handle.await;
 
-            debug!("Completed the publish request");
-        }
-    });
+    Ok(())
 }
 
 #[tokio::main]
@@ -366,21 +376,35 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     info!("The Wheelchair Assistant Application has started.");
 
-    const PROVIDER_AUTHORITY_SEAT: &str = "0.0.0.0:4070";
-    const PROVIDER_AUTHORITY_DOOR: &str = "0.0.0.0:4080";
-    const PROVIDER_AUTHORITY_STEER: &str = "0.0.0.0:4090";
-
-    // Get the In-vehicle Digital Twin Uri from the service discovery system
-    // This could be enhanced to add retries for robustness
-    let invehicle_digital_twin_uri = discover_service_using_chariott(
-        CHARIOTT_SERVICE_DISCOVERY_URI,
-        INVEHICLE_DIGITAL_TWIN_SERVICE_NAMESPACE,
-        INVEHICLE_DIGITAL_TWIN_SERVICE_NAME,
-        INVEHICLE_DIGITAL_TWIN_SERVICE_VERSION,
-        INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_KIND,
-        INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_REFERENCE,
-    )
-    .await?;
+    let settings = load_settings("wheelchair_assistant_application", &Settings::default())?;
+
+    // Start the actuators, their properties follow the positions that the actuators confirm.
+    let actuator_config = |obstruction_percent: Option<u8>| SimulatedActuatorConfig {
+        travel_time: Duration::from_millis(settings.actuator_travel_time_ms),
+        obstruction_at: obstruction_percent.map(|percent| ActuatorPosition { percent }),
+    };
+    let door: Arc<dyn Actuator> = Arc::new(SimulatedActuator::new(
+        "door",
+        actuator_config(settings.door_obstruction_percent),
+    ));
+    let seat: Arc<dyn Actuator> = Arc::new(SimulatedActuator::new(
+        "seat",
+        actuator_config(settings.seat_obstruction_percent),
+    ));
+    let steering_wheel: Arc<dyn Actuator> = Arc::new(SimulatedActuator::new(
+        "steering wheel",
+        actuator_config(settings.steering_obstruction_percent),
+    ));
+
+    let (is_door_open, door_stream) = watch::channel(false);
+    let (is_seat_in_assist_position, seat_stream) = watch::channel(false);
+    let (is_steeringwheel_in_assist_position, steering_stream) = watch::channel(false);
+    start_actuator_feedback(door.as_ref(), is_door_open);
+    start_actuator_feedback(seat.as_ref(), is_seat_in_assist_position);
+    start_actuator_feedback(steering_wheel.as_ref(), is_steeringwheel_in_assist_position);
+
+    let (assist_requested, assist_requested_receiver) = watch::channel(false);
+    start_actuator_control(vec![door, seat, steering_wheel], assist_requested_receiver);
 
     // Get subscription constraints.
     let frequency_ms = env::args()
@@ -391,90 +415,46 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             None
         })
-        .unwrap_or_else(|| DEFAULT_FREQUENCY_MS.to_string());
-
-    // Retrieve the provider URI.
-    let mut provider_endpoint_info = None;
-    let mut retries: i32 = 0;
-    while provider_endpoint_info.is_none() {
-        provider_endpoint_info = match discover_digital_twin_provider_using_ibeji(
-            &invehicle_digital_twin_uri,
-            car_v1::car::car_wheelchair_assistant_state::ID,
-            digital_twin_protocol::GRPC,
-            &[digital_twin_operation::MANAGEDSUBSCRIBE.to_string()],
-        )
-        .await
-        {
-            Ok(response) => Some(response),
-            Err(status) => {
-                info!(
-                    "A provider was not found in the digital twin service for id '{}' with: '{:?}'",
-                    car_v1::car::car_wheelchair_assistant_state::ID,
-                    status
-                );
-                None
-            }
-        };
-
-        if provider_endpoint_info.is_none() && retries < MAX_RETRIES {
-            info!("Retrying FindById to retrieve the properties provider endpoint in {DURATION_BETWEEN_ATTEMPTS:?}.");
-            sleep(DURATION_BETWEEN_ATTEMPTS).await;
-            retries += 1;
-        } else {
-            break;
-        }
-    }
-
-    let managed_subscribe_uri = provider_endpoint_info.ok_or("Maximum amount of retries was reached while trying to retrieve the digital twin provider.")?.uri;
-    info!("The Managed Subscribe URI for the IsCarUnlocked property's provider is {managed_subscribe_uri}");
-
-    // Create constraint for the managed subscribe call.
-    let frequency_constraint = Constraint {
-        r#type: constraint_type::FREQUENCY_MS.to_string(),
-        value: frequency_ms.to_string(),
-    };
-
-    // Get the subscription information for a managed topic with constraints.
-    let subscription_info =
-        get_car_adjust_subscription_info(&managed_subscribe_uri, vec![frequency_constraint])
-            .await?;
-
-    // Deconstruct subscription information.
-    let broker_uri = get_uri(&subscription_info.uri)?;
-    let topic = subscription_info.context;
-    info!("The broker URI for the car_wheelchair_assistant_state property's provider is {broker_uri}");
-
-    // Subscribe to topic.
-    let sub_handle = receive_car_adjust_updates(&broker_uri, &topic)
-        .await
-        .map_err(|err| Status::internal(format!("{err:?}")))?;
-    
-
-    provider_start_seat_adjustment_data_stream();
-    signal::ctrl_c().await?;
-
-    let provider_uri_seat = format!("http://{PROVIDER_AUTHORITY_SEAT}");
-    provider_register_seat_adjustment(&invehicle_digital_twin_uri, &provider_uri_seat).await?;
-    debug!("The Provider Seat has registered with Ibeji.");
-
-    provider_start_door_adjustment_data_stream();
-    signal::ctrl_c().await?;
-
-    let provider_uri_door = format!("http://{PROVIDER_AUTHORITY_DOOR}");
-    provider_register_door_adjustment(&invehicle_digital_twin_uri, &provider_uri_door).await?;
-    debug!("The Provider Door has registered with Ibeji.");
-
-    provider_start_steering_wheel_adjustment_data_stream();
-    signal::ctrl_c().await?;
-
-    let provider_uri_steer = format!("http://{PROVIDER_AUTHORITY_STEER}");
-    provider_register_steering_wheel_adjustment(&invehicle_digital_twin_uri, &provider_uri_steer).await?;
-    debug!("The Provider SteeringWheel has registered with Ibeji.");
+        .unwrap_or_else(|| settings.frequency_ms.to_string());
+
+    // Serve the actuator properties.
+    let entities = vec![
+        ManagedSubscribeEntity::new(
+            &settings.seat_provider_authority,
+            car_v1::car::is_seat_in_assist_position::ID,
+            car_v1::car::is_seat_in_assist_position::NAME,
+            car_v1::car::is_seat_in_assist_position::DESCRIPTION,
+            seat_stream,
+            settings.min_interval_ms,
+        ),
+        ManagedSubscribeEntity::new(
+            &settings.door_provider_authority,
+            car_v1::car::is_door_open::ID,
+            car_v1::car::is_door_open::NAME,
+            car_v1::car::is_door_open::DESCRIPTION,
+            door_stream,
+            settings.min_interval_ms,
+        ),
+        ManagedSubscribeEntity::new(
+            &settings.steering_provider_authority,
+            car_v1::car::is_steeringwheel_in_assist_position::ID,
+            car_v1::car::is_steeringwheel_in_assist_position::NAME,
+            car_v1::car::is_steeringwheel_in_assist_position::DESCRIPTION,
+            steering_stream,
+            settings.min_interval_ms,
+        ),
+    ];
+
+    run_managed_subscribe_provider(
+        "wheelchair_assistant_application",
+        &settings.chariott_uri,
+        RetryPolicy::from(&settings.retry),
+        entities,
+        |provider| receive_wheelchair_assistant_state(provider, &frequency_ms, assist_requested),
+    )
+    .await?;
 
     info!("The Consumer has completed. Shutting down...");
 
-    // Wait for subscriber task to cleanly shutdown.
-    _ = sub_handle.await;
-
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,281 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! Decides whether the wheelchair is near the car from noisy distance readings.
+//!
+//! The readings are optionally smoothed by a running median. The wheelchair becomes near when the
+//! smoothed distance drops to `near_enter_cm` and far again when it rises above `near_exit_cm`, so
+//! readings between the two thresholds keep the current decision. A new decision only takes effect
+//! once the readings have supported it for `min_dwell_ms`.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use serde_derive::{Deserialize, Serialize};
+use wheelchair_digital_twin_providers_common::settings::{SettingsError, ValidateSettings};
+
+/// Settings of the near/far classifier.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProximitySettings {
+    /// The distance at or below which a far wheelchair becomes near.
+    pub near_enter_cm: i32,
+    /// The distance above which a near wheelchair becomes far.
+    pub near_exit_cm: i32,
+    /// The time that the readings must support a new decision before it takes effect.
+    pub min_dwell_ms: u64,
+    /// The number of readings of the running median, 1 disables the filtering.
+    pub median_window: usize,
+}
+
+impl Default for ProximitySettings {
+    fn default() -> Self {
+        ProximitySettings {
+            near_enter_cm: 200,
+            near_exit_cm: 250,
+            min_dwell_ms: 500,
+            median_window: 5,
+        }
+    }
+}
+
+impl ValidateSettings for ProximitySettings {
+    fn validate(&self) -> Result<(), SettingsError> {
+        if self.near_enter_cm < 0 {
+            return Err(SettingsError::Invalid {
+                field: "proximity.near_enter_cm".to_string(),
+                message: "must not be negative".to_string(),
+            });
+        }
+
+        if self.near_exit_cm < self.near_enter_cm {
+            return Err(SettingsError::Invalid {
+                field: "proximity.near_exit_cm".to_string(),
+                message: format!(
+                    "must not be less than near_enter_cm ({})",
+                    self.near_enter_cm
+                ),
+            });
+        }
+
+        if self.median_window == 0 {
+            return Err(SettingsError::Invalid {
+                field: "proximity.median_window".to_string(),
+                message: "must be greater than 0".to_string(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Classifies distance readings as near or far.
+#[derive(Clone, Debug)]
+pub struct ProximityClassifier {
+    settings: ProximitySettings,
+    readings: VecDeque<i32>,
+    is_near: bool,
+    /// Since when the readings have supported the opposite decision.
+    pending_since: Option<Instant>,
+}
+
+impl ProximityClassifier {
+    /// Create a classifier that starts with the wheelchair being far.
+    ///
+    /// # Arguments
+    /// * `settings` - The classifier's settings.
+    pub fn new(settings: ProximitySettings) -> Self {
+        let readings = VecDeque::with_capacity(settings.median_window);
+
+        ProximityClassifier {
+            settings,
+            readings,
+            is_near: false,
+            pending_since: None,
+        }
+    }
+
+    /// Whether the wheelchair is near.
+    pub fn is_near(&self) -> bool {
+        self.is_near
+    }
+
+    /// The median of the recent readings, the lower one of the middle two for an even count.
+    fn filtered_distance(&self) -> Option<i32> {
+        let mut sorted: Vec<i32> = self.readings.iter().copied().collect();
+        sorted.sort_unstable();
+
+        sorted.get(sorted.len().saturating_sub(1) / 2).copied()
+    }
+
+    /// Classify a reading and return whether the wheelchair is near.
+    ///
+    /// # Arguments
+    /// * `distance_cm` - The distance reading.
+    /// * `at` - When the reading has been received.
+    pub fn update(&mut self, distance_cm: i32, at: Instant) -> bool {
+        if self.readings.len() >= self.settings.median_window {
+            self.readings.pop_front();
+        }
+        self.readings.push_back(distance_cm);
+
+        let Some(distance_cm) = self.filtered_distance() else {
+            return self.is_near;
+        };
+
+        let supports_near = if self.is_near {
+            distance_cm <= self.settings.near_exit_cm
+        } else {
+            distance_cm <= self.settings.near_enter_cm
+        };
+
+        if supports_near == self.is_near {
+            self.pending_since = None;
+            return self.is_near;
+        }
+
+        let since = *self.pending_since.get_or_insert(at);
+        let min_dwell = Duration::from_millis(self.settings.min_dwell_ms);
+        if at.saturating_duration_since(since) >= min_dwell {
+            self.is_near = supports_near;
+            self.pending_since = None;
+        }
+
+        self.is_near
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_millis(100);
+
+    fn settings() -> ProximitySettings {
+        ProximitySettings {
+            near_enter_cm: 200,
+            near_exit_cm: 250,
+            min_dwell_ms: 500,
+            median_window: 5,
+        }
+    }
+
+    /// Feed readings one `STEP` apart and return the decision after each of them.
+    fn classify(
+        classifier: &mut ProximityClassifier,
+        start: Instant,
+        readings: &[i32],
+    ) -> Vec<bool> {
+        readings
+            .iter()
+            .enumerate()
+            .map(|(index, distance_cm)| {
+                classifier.update(*distance_cm, start + STEP * index as u32)
+            })
+            .collect()
+    }
+
+    /// Count how often the decision changes.
+    fn changes(decisions: &[bool]) -> usize {
+        decisions
+            .windows(2)
+            .filter(|pair| pair[0] != pair[1])
+            .count()
+    }
+
+    #[test]
+    fn noise_between_the_thresholds_does_not_flap() {
+        let mut classifier = ProximityClassifier::new(settings());
+        let start = Instant::now();
+
+        let approach = [190; 10];
+        let decisions = classify(&mut classifier, start, &approach);
+        assert!(classifier.is_near());
+        assert_eq!(changes(&decisions), 1);
+
+        // Noise around the enter threshold stays below the exit threshold.
+        let noisy = [205, 195, 230, 198, 245, 201, 240, 199, 249, 210, 220, 196];
+        let later = start + STEP * approach.len() as u32;
+        let decisions = classify(&mut classifier, later, &noisy);
+        assert!(decisions.iter().all(|is_near| *is_near));
+    }
+
+    #[test]
+    fn noise_around_the_enter_threshold_does_not_flap_while_far() {
+        let mut classifier = ProximityClassifier::new(settings());
+
+        // The median never reaches the enter threshold for long enough to dwell.
+        let noisy = [210, 199, 205, 201, 198, 203, 202, 197, 204, 201, 199, 206];
+        let decisions = classify(&mut classifier, Instant::now(), &noisy);
+        assert!(decisions.iter().all(|is_near| !*is_near));
+    }
+
+    #[test]
+    fn changes_only_after_the_dwell_time() {
+        let mut classifier = ProximityClassifier::new(ProximitySettings {
+            median_window: 1,
+            ..settings()
+        });
+        let start = Instant::now();
+
+        assert!(!classifier.update(150, start));
+        assert!(!classifier.update(150, start + Duration::from_millis(499)));
+        assert!(classifier.update(150, start + Duration::from_millis(500)));
+
+        let leaving = start + Duration::from_millis(1000);
+        assert!(classifier.update(300, leaving));
+        assert!(classifier.update(300, leaving + Duration::from_millis(499)));
+        assert!(!classifier.update(300, leaving + Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn an_interrupted_dwell_starts_again() {
+        let mut classifier = ProximityClassifier::new(ProximitySettings {
+            median_window: 1,
+            ..settings()
+        });
+        let start = Instant::now();
+
+        assert!(!classifier.update(150, start));
+        assert!(!classifier.update(300, start + Duration::from_millis(400)));
+        assert!(!classifier.update(150, start + Duration::from_millis(600)));
+        assert!(!classifier.update(150, start + Duration::from_millis(1000)));
+        assert!(classifier.update(150, start + Duration::from_millis(1100)));
+    }
+
+    #[test]
+    fn the_median_rejects_single_spikes() {
+        let mut classifier = ProximityClassifier::new(ProximitySettings {
+            min_dwell_ms: 0,
+            ..settings()
+        });
+        let start = Instant::now();
+
+        // A far wheelchair with single readings of a near one.
+        let far = [400, 410, 20, 405, 395, 0, 400, 402, 15, 398];
+        let decisions = classify(&mut classifier, start, &far);
+        assert!(decisions.iter().all(|is_near| !*is_near));
+
+        let approach = [100; 5];
+        let later = start + STEP * far.len() as u32;
+        classify(&mut classifier, later, &approach);
+        assert!(classifier.is_near());
+
+        // A near wheelchair with single readings of a far one.
+        let near = [100, 900, 105, 95, i32::MAX, 100, 98, 1000, 102];
+        let later = later + STEP * approach.len() as u32;
+        let decisions = classify(&mut classifier, later, &near);
+        assert!(decisions.iter().all(|is_near| *is_near));
+    }
+
+    #[test]
+    fn without_the_median_a_single_spike_changes_the_decision() {
+        let mut classifier = ProximityClassifier::new(ProximitySettings {
+            min_dwell_ms: 0,
+            median_window: 1,
+            ..settings()
+        });
+
+        assert!(classifier.update(20, Instant::now()));
+    }
+}
@@ -2,20 +2,30 @@
 // Licensed under the MIT license.
 // SPDX-License-Identifier: MIT
 
+//! Classifies the wheelchair distance as near or far and publishes the result as the
+//! WheelchairDistanceState property.
+
 use std::env;
 use std::str;
+use std::time::Instant;
 
 use wheelchair_digital_twin_model::{car_v1, Metadata};
-use wheelchair_digital_twin_providers_common::constants::chariott::{
-    INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_KIND,
-    INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_REFERENCE, INVEHICLE_DIGITAL_TWIN_SERVICE_NAME,
-    INVEHICLE_DIGITAL_TWIN_SERVICE_NAMESPACE, INVEHICLE_DIGITAL_TWIN_SERVICE_VERSION,
-};
 use wheelchair_digital_twin_providers_common::constants::{
     constraint_type, digital_twin_operation, digital_twin_protocol,
 };
+use wheelchair_digital_twin_providers_common::health::HealthMonitor;
+use wheelchair_digital_twin_providers_common::managed_subscribe_provider::{
+    run_managed_subscribe_provider, ManagedSubscribeEntity, RegisteredProvider,
+};
+use wheelchair_digital_twin_providers_common::mqtt_consumer::consume;
+use wheelchair_digital_twin_providers_common::retry::RetryPolicy;
+use wheelchair_digital_twin_providers_common::settings::{
+    load_settings, validate_authority, validate_non_zero, validate_uri, RetrySettings,
+    SettingsError, ValidateSettings,
+};
+use wheelchair_digital_twin_providers_common::shutdown::Shutdown;
 use wheelchair_digital_twin_providers_common::utils::{
-    discover_digital_twin_provider_using_ibeji, discover_service_using_chariott, get_uri,
+    discover_digital_twin_provider_using_ibeji, get_uri,
 };
 
 use env_logger::{Builder, Target};
@@ -23,40 +33,71 @@ use interfaces::module::managed_subscribe::v1::managed_subscribe_client::Managed
 use interfaces::module::managed_subscribe::v1::{
     Constraint, SubscriptionInfoRequest, SubscriptionInfoResponse,
 };
-use interfaces::invehicle_digital_twin::v1::invehicle_digital_twin_client::InvehicleDigitalTwinClient;
-use interfaces::invehicle_digital_twin::v1::{EndpointInfo, EntityAccessInfo, RegisterRequest};
-use log::{debug, info, warn, LevelFilter};
-use paho_mqtt as mqtt;
-use tokio::signal;
+use log::{info, LevelFilter};
+use serde_derive::{Deserialize, Serialize};
+use tokio::sync::watch;
 use tokio::task::JoinHandle;
-use tokio::time::{sleep, Duration};
 use tonic::{Request, Status};
-use tokio::sync::watch;
-use uuid::Uuid;
-use serde_derive::{Deserialize, Serialize};
-use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::classifier::{ProximityClassifier, ProximitySettings};
+
+mod classifier;
 
 const FREQUENCY_MS_FLAG: &str = "freq_ms=";
 const MQTT_CLIENT_ID: &str = "wheelchair-distance-consumer";
 
-// TODO: These could be added in configuration
-const CHARIOTT_SERVICE_DISCOVERY_URI: &str = "http://0.0.0.0:50000";
-
+const DEFAULT_CHARIOTT_SERVICE_DISCOVERY_URI: &str = "http://0.0.0.0:50000";
+const DEFAULT_PROVIDER_AUTHORITY: &str = "0.0.0.0:4030";
 const DEFAULT_FREQUENCY_MS: u64 = 10000; // 10 seconds
+const DEFAULT_MIN_INTERVAL_MS: u64 = 100;
 
-// Constants used for retry logic
-const MAX_RETRIES: i32 = 10; // for demo purposes we will retry a maximum of 10 times
-                             // By default we will wait 5 seconds between retry attempts
-const DURATION_BETWEEN_ATTEMPTS: Duration = Duration::from_secs(5);
+/// Settings of the wheelchair distance application.
+#[derive(Debug, Serialize, Deserialize)]
+struct Settings {
+    /// Chariott's Service Discovery URI.
+    chariott_uri: String,
+    /// How discovery, registration and the requests to the In-Vehicle Digital Twin are retried.
+    #[serde(default)]
+    retry: RetrySettings,
+    /// The authority of the wheelchair distance state endpoint.
+    provider_authority: String,
+    /// The interval after which an unchanged wheelchair distance is published again, the freq_ms
+    /// flag overrides it.
+    frequency_ms: u64,
+    /// The default publish interval of the wheelchair distance state.
+    min_interval_ms: u64,
+    /// How the wheelchair distance is classified as near or far.
+    proximity: ProximitySettings,
+}
 
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            chariott_uri: DEFAULT_CHARIOTT_SERVICE_DISCOVERY_URI.to_string(),
+            retry: RetrySettings::default(),
+            provider_authority: DEFAULT_PROVIDER_AUTHORITY.to_string(),
+            frequency_ms: DEFAULT_FREQUENCY_MS,
+            min_interval_ms: DEFAULT_MIN_INTERVAL_MS,
+            proximity: ProximitySettings::default(),
+        }
+    }
+}
 
-static STATE_DISTANCE: AtomicBool = AtomicBool::new(false);
+impl ValidateSettings for Settings {
+    fn validate(&self) -> Result<(), SettingsError> {
+        validate_uri("chariott_uri", &self.chariott_uri)?;
+        self.retry.validate()?;
+        validate_authority("provider_authority", &self.provider_authority)?;
+        validate_non_zero("frequency_ms", self.frequency_ms)?;
+        validate_non_zero("min_interval_ms", self.min_interval_ms)?;
+        self.proximity.validate()
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize)]
-#[serde(tag="type")]
 struct WheelchairDistanceProperty {
     #[serde(rename = "WheelchairDistance")]
-    wheelchair_distance: car_v1::car::car_wheelchair_distance::TYPE,
+    wheelchair_distance: car_v1::car::wheelchair_distance::TYPE,
     #[serde(rename = "$metadata")]
     metadata: Metadata,
 }
@@ -68,7 +109,7 @@ struct WheelchairDistanceProperty {
 /// * `constraints` - Constraints for the managed topic.
 async fn get_car_wheelchair_distance_subscription_info(
     managed_subscribe_uri: &str,
-    constraints: Vec<Constraint>
+    constraints: Vec<Constraint>,
 ) -> Result<SubscriptionInfoResponse, Status> {
     // Create gRPC client.
     let mut client = ManagedSubscribeClient::connect(managed_subscribe_uri.to_string())
@@ -76,7 +117,7 @@ async fn get_car_wheelchair_distance_subscription_info(
         .map_err(|err| Status::from_error(err.into()))?;
 
     let request = Request::new(SubscriptionInfoRequest {
-        entity_id: car_v1::car::car_wheelchair_distance::ID.to_string(),
+        entity_id: car_v1::car::wheelchair_distance::ID.to_string(),
         constraints,
     });
 
@@ -90,161 +131,119 @@ async fn get_car_wheelchair_distance_subscription_info(
 /// # Arguments
 /// * `broker_uri` - The broker URI.
 /// * `topic` - The topic.
-async fn receive_car_wheelchair_distance_updates(
+/// * `classifier` - Classifies the distances as near or far.
+/// * `distance_state` - The sender for the wheelchair distance state.
+/// * `shutdown` - Stops receiving updates.
+/// * `health` - Probes the connection to the broker.
+fn receive_car_wheelchair_distance_updates(
     broker_uri: &str,
     topic: &str,
+    mut classifier: ProximityClassifier,
+    distance_state: watch::Sender<car_v1::car::wheelchair_distance_state::TYPE>,
+    shutdown: &Shutdown,
+    health: &HealthMonitor,
 ) -> Result<JoinHandle<()>, String> {
-    // Create a unique id for the client.
-    let client_id = format!("{MQTT_CLIENT_ID}-{}", Uuid::new_v4());
-
-    let create_opts = mqtt::CreateOptionsBuilder::new()
-        .server_uri(broker_uri)
-        .client_id(client_id)
-        .finalize();
-
-    let client = mqtt::Client::new(create_opts)
-        .map_err(|err| format!("Failed to create the client due to '{err:?}'"))?;
-
-    let receiver = client.start_consuming();
-
-    // Setup task to handle clean shutdown.
-    let ctrlc_cli = client.clone();
-    tokio::spawn(async move {
-        _ = signal::ctrl_c().await;
-
-        // Tells the client to shutdown consuming thread.
-        ctrlc_cli.stop_consuming();
-    });
-
-    // Last Will and Testament
-    let lwt = mqtt::MessageBuilder::new()
-        .topic("test")
-        .payload("Receiver lost connection")
-        .finalize();
-
-    let conn_opts = mqtt::ConnectOptionsBuilder::new_v5()
-        .keep_alive_interval(Duration::from_secs(30))
-        .clean_session(false)
-        .will_message(lwt)
-        .finalize();
-
-    let _connect_response = client
-        .connect(conn_opts)
-        .map_err(|err| format!("Failed to connect due to '{err:?}"));
-
-    let mut _subscribe_response = client
-        .subscribe(topic, mqtt::types::QOS_1)
-        .map_err(|err| format!("Failed to subscribe to topic {topic} due to '{err:?}'"));
-
-    // Copy topic for separate thread.
-    let topic_string = topic.to_string();
-
-    let sub_handle = tokio::spawn(async move {
-        for msg in receiver.iter() {
-            if let Some(msg) = msg {
-                // Here we log the message received. This could be expanded to parsing the message,
-                // Obtaining the weight and making decisions based on the weight
-                // For example, adjusting body functions or powertrain of the towing vehicle.
-
-                // TODO: move interfaces into global package
-                let payload_str = msg.payload_str();
-                let msg_des: WheelchairDistanceProperty = serde_json::from_str(&payload_str).unwrap();
-                let distance = msg_des.wheelchair_distance;
-                info!("{}", distance);
-                info!("{}", msg);
-
-                if distance <= 200 {
-                    info!("Near!");
-                    STATE_DISTANCE.store(true, Ordering::Relaxed);
-                } else {
-                    info!("Far!");
-                    STATE_DISTANCE.store(false, Ordering::Relaxed);
-                }
-            } else if !client.is_connected() {
-                if client.reconnect().is_ok() {
-                    _subscribe_response = client
-                        .subscribe(topic_string.as_str(), mqtt::types::QOS_1)
-                        .map_err(|err| {
-                            format!("Failed to subscribe to topic {topic_string} due to '{err:?}'")
-                        });
-                } else {
-                    break;
-                }
+    consume(
+        broker_uri,
+        MQTT_CLIENT_ID,
+        topic,
+        shutdown,
+        health,
+        move |property: WheelchairDistanceProperty| {
+            let distance = property.wheelchair_distance;
+            info!("{}", distance);
+
+            let was_near = classifier.is_near();
+            let is_near = classifier.update(distance, Instant::now());
+            if is_near != was_near {
+                info!("{}", if is_near { "Near!" } else { "Far!" });
             }
-        }
 
-        if client.is_connected() {
-            debug!("Disconnecting");
-            client.unsubscribe(topic_string.as_str()).unwrap();
-            client.disconnect(None).unwrap();
-        }
-    });
-
-    Ok(sub_handle)
+            // Subscribers are only woken up by a change of the state.
+            distance_state.send_if_modified(|current| {
+                let modified = *current != is_near;
+                *current = is_near;
+                modified
+            });
+        },
+    )
 }
 
-/// Register the wheelchair distance property's endpoint.
+/// Receive the wheelchair distance from its provider and classify it until the shutdown.
 ///
 /// # Arguments
-/// * `invehicle_digital_twin_uri` - The In-Vehicle Digital Twin URI.
-/// * `provider_uri` - The provider's URI.
-async fn provider_register_wheelchair_distance(
-    invehicle_digital_twin_uri: &str,
-    provider_uri: &str,
-) -> Result<(), Status> {
-    let endpoint_info = EndpointInfo {
-        protocol: digital_twin_protocol::GRPC.to_string(),
-        operations: vec![digital_twin_operation::MANAGEDSUBSCRIBE.to_string()],
-        uri: provider_uri.to_string(),
-        context: "GetSubscriptionInfo".to_string(),
-    };
+/// * `provider` - The registered wheelchair distance state provider.
+/// * `frequency_ms` - The longest time between two publishes of the distance.
+/// * `classifier` - Classifies the distances as near or far.
+/// * `distance_state` - The sender for the wheelchair distance state.
+async fn receive_wheelchair_distance(
+    provider: RegisteredProvider,
+    frequency_ms: &str,
+    classifier: ProximityClassifier,
+    distance_state: watch::Sender<car_v1::car::wheelchair_distance_state::TYPE>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let RegisteredProvider {
+        invehicle_digital_twin_uri,
+        shutdown,
+        health,
+        retry_policy,
+    } = provider;
 
-    let entity_access_info = EntityAccessInfo {
-        name: car_v1::car::car_wheelchair_distance_state::NAME.to_string(),
-        id: car_v1::car::car_wheelchair_distance_state::ID.to_string(),
-        description: car_v1::car::car_wheelchair_distance_state::DESCRIPTION.to_string(),
-        endpoint_info_list: vec![endpoint_info],
+    // Retrieve the provider URI.
+    let discovery = retry_policy.retry("find the provider for WheelchairDistance", || {
+        discover_digital_twin_provider_using_ibeji(
+            &invehicle_digital_twin_uri,
+            car_v1::car::wheelchair_distance::ID,
+            digital_twin_protocol::GRPC,
+            &[digital_twin_operation::MANAGEDSUBSCRIBE.to_string()],
+        )
+    });
+    let Some(endpoint_info) = shutdown.run_until(discovery).await.transpose()? else {
+        return Ok(());
     };
+    let managed_subscribe_uri = endpoint_info.uri;
+    info!("The Managed Subscribe URI for the WheelchairDistance property's provider is {managed_subscribe_uri}");
+
+    // Create constraints for the managed subscribe call, changes are published as they happen and
+    // an unchanged value is published again after the frequency.
+    let constraints = vec![
+        Constraint {
+            r#type: constraint_type::ON_CHANGE.to_string(),
+            value: true.to_string(),
+        },
+        Constraint {
+            r#type: constraint_type::MAX_INTERVAL_MS.to_string(),
+            value: frequency_ms.to_string(),
+        },
+    ];
 
-    let mut client = InvehicleDigitalTwinClient::connect(invehicle_digital_twin_uri.to_string())
-        .await
-        .map_err(|e| Status::internal(e.to_string()))?;
-    let request = tonic::Request::new(RegisterRequest {
-        entity_access_info_list: vec![entity_access_info],
+    // Get the subscription information for a managed topic with constraints.
+    let subscription = retry_policy.retry("subscribe to WheelchairDistance", || {
+        get_car_wheelchair_distance_subscription_info(&managed_subscribe_uri, constraints.clone())
     });
-    let _response = client.register(request).await?;
-
-    Ok(())
-}
+    let Some(subscription_info) = shutdown.run_until(subscription).await.transpose()? else {
+        return Ok(());
+    };
 
-/// Start the wheelchair distance data stream.
-fn provider_start_wheelchair_distance_data_stream() {
-    debug!("Starting the Provider's wheelchair distance data stream.");
-    
-    let (sender, _receiver) = watch::channel(STATE_DISTANCE.load(Ordering::Relaxed));
-    tokio::spawn(async move {
-
-        loop {
-            if STATE_DISTANCE.load(Ordering::Relaxed) == true {
-                debug!(
-                    "Recording new value for {} of true",
-                    car_v1::car::car_wheelchair_distance_state::ID
-                );
-            } else {
-                debug!(
-                    "Recording new value for {} of false",
-                    car_v1::car::car_wheelchair_distance_state::ID
-                );
-            }
+    // Deconstruct subscription information.
+    let broker_uri = get_uri(&subscription_info.uri)?;
+    let topic = subscription_info.context;
+    info!("The broker URI for the WheelchairDistance property's provider is {broker_uri}");
 
-            if let Err(err) = sender.send(STATE_DISTANCE.load(Ordering::Relaxed)) {
-                warn!("Failed to get new value due to '{err:?}'");
-                break;
-            }
+    // Subscribe to topic.
+    let sub_handle = receive_car_wheelchair_distance_updates(
+        &broker_uri,
+        &topic,
+        classifier,
+        distance_state,
+        &shutdown,
+        &health,
+    )?;
+
+    // Wait for subscriber task to cleanly shutdown, it stops on control-c or SIGTERM.
+    _ = sub_handle.await;
 
-            debug!("Completed the publish request");
-        }
-    });
+    Ok(())
 }
 
 #[tokio::main]
@@ -257,19 +256,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     info!("The Wheelchair Distance Application has started.");
 
-    const PROVIDER_AUTHORITY: &str = "0.0.0.0:4030";
-    let provider_uri = format!("http://{PROVIDER_AUTHORITY}"); 
-    // Get the In-vehicle Digital Twin Uri from the service discovery system
-    // This could be enhanced to add retries for robustness
-    let invehicle_digital_twin_uri = discover_service_using_chariott(
-        CHARIOTT_SERVICE_DISCOVERY_URI,
-        INVEHICLE_DIGITAL_TWIN_SERVICE_NAMESPACE,
-        INVEHICLE_DIGITAL_TWIN_SERVICE_NAME,
-        INVEHICLE_DIGITAL_TWIN_SERVICE_VERSION,
-        INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_KIND,
-        INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_REFERENCE,
-    )
-    .await?;
+    let settings = load_settings("wheelchair_distance_application", &Settings::default())?;
 
     // Get subscription constraints.
     let frequency_ms = env::args()
@@ -280,73 +267,30 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             None
         })
-        .unwrap_or_else(|| DEFAULT_FREQUENCY_MS.to_string());
-
-    // Retrieve the provider URI.
-    let mut provider_endpoint_info = None;
-    let mut retries: i32 = 0;
-    while provider_endpoint_info.is_none() {
-        provider_endpoint_info = match discover_digital_twin_provider_using_ibeji(
-            &invehicle_digital_twin_uri,
-            car_v1::car::is_car_unlocked::ID,
-            digital_twin_protocol::GRPC,
-            &[digital_twin_operation::MANAGEDSUBSCRIBE.to_string()],
-        )
-        .await
-        {
-            Ok(response) => Some(response),
-            Err(status) => {
-                info!(
-                    "A provider was not found in the digital twin service for id '{}' with: '{:?}'",
-                    car_v1::car::is_car_unlocked::ID,
-                    status
-                );
-                None
-            }
-        };
-
-        if provider_endpoint_info.is_none() && retries < MAX_RETRIES {
-            info!("Retrying FindById to retrieve the properties provider endpoint in {DURATION_BETWEEN_ATTEMPTS:?}.");
-            sleep(DURATION_BETWEEN_ATTEMPTS).await;
-            retries += 1;
-        } else {
-            break;
-        }
-    }
-
-    let managed_subscribe_uri = provider_endpoint_info.ok_or("Maximum amount of retries was reached while trying to retrieve the digital twin provider.")?.uri;
-    info!("The Managed Subscribe URI for the IsCarUnlocked property's provider is {managed_subscribe_uri}");
-
-    // Create constraint for the managed subscribe call.
-    let frequency_constraint = Constraint {
-        r#type: constraint_type::FREQUENCY_MS.to_string(),
-        value: frequency_ms.to_string(),
-    };
-
-    // Get the subscription information for a managed topic with constraints.
-    let subscription_info =
-        get_car_wheelchair_distance_subscription_info(&managed_subscribe_uri, vec![frequency_constraint])
-            .await?;
-
-    // Deconstruct subscription information.
-    let broker_uri = get_uri(&subscription_info.uri)?;
-    let topic = subscription_info.context;
-    info!("The broker URI for the IsCarUnlocked property's provider is {broker_uri}");
-
-    // Subscribe to topic.
-    let sub_handle = receive_car_wheelchair_distance_updates(&broker_uri, &topic)
-        .await
-        .map_err(|err| Status::internal(format!("{err:?}")))?;
-    provider_start_wheelchair_distance_data_stream();
-    signal::ctrl_c().await?;
-
-    provider_register_wheelchair_distance(&invehicle_digital_twin_uri, &provider_uri).await?;
-    debug!("The Provider has registered with Ibeji.");
+        .unwrap_or_else(|| settings.frequency_ms.to_string());
+
+    // Serve the wheelchair distance state, it is far until the first distance arrives.
+    let (distance_state, distance_state_stream) = watch::channel(false);
+    let entities = vec![ManagedSubscribeEntity::new(
+        &settings.provider_authority,
+        car_v1::car::wheelchair_distance_state::ID,
+        car_v1::car::wheelchair_distance_state::NAME,
+        car_v1::car::wheelchair_distance_state::DESCRIPTION,
+        distance_state_stream,
+        settings.min_interval_ms,
+    )];
+
+    let classifier = ProximityClassifier::new(settings.proximity);
+    run_managed_subscribe_provider(
+        "wheelchair_distance_application",
+        &settings.chariott_uri,
+        RetryPolicy::from(&settings.retry),
+        entities,
+        |provider| receive_wheelchair_distance(provider, &frequency_ms, classifier, distance_state),
+    )
+    .await?;
 
     info!("The Consumer has completed. Shutting down...");
 
-    // Wait for subscriber task to cleanly shutdown.
-    _ = sub_handle.await;
-
     Ok(())
 }
@@ -0,0 +1,248 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! Generates the Rust bindings for the vehicle model in "dtdl/car.json".
+//!
+//! Every DTDL v3 interface becomes a module named after the interface (e.g. `car`), and every
+//! property in it becomes a nested module with its `ID`, `NAME`, `DESCRIPTION`, `WRITABLE` and
+//! `TYPE`. Commands become nested modules with their `ID`, `NAME` and `DESCRIPTION`.
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+
+const DTDL_PATH: &str = "dtdl/car.json";
+const GENERATED_FILE_NAME: &str = "car_v1.rs";
+
+/// Convert a DTDL name in PascalCase to snake_case.
+///
+/// # Arguments
+/// * `name` - The name to convert.
+fn to_snake_case(name: &str) -> String {
+    let mut result = String::new();
+
+    for (index, character) in name.chars().enumerate() {
+        if character.is_uppercase() {
+            if index != 0 {
+                result.push('_');
+            }
+            result.extend(character.to_lowercase());
+        } else {
+            result.push(character);
+        }
+    }
+
+    result
+}
+
+/// Get the name segment of a DTMI, e.g. "dtmi:sdv:Car;1" returns "Car".
+///
+/// # Arguments
+/// * `dtmi` - The DTMI.
+fn dtmi_name(dtmi: &str) -> &str {
+    let without_version = dtmi.split(';').next().unwrap_or(dtmi);
+    without_version
+        .rsplit(':')
+        .next()
+        .unwrap_or(without_version)
+}
+
+/// Map a DTDL schema to the Rust type used for it.
+///
+/// Named enum schemas are hand-written in this crate, in a module named after the schema,
+/// e.g. "dtmi:sdv:Car:AssistantState;1" maps to `crate::assistant_state::AssistantState`.
+///
+/// # Arguments
+/// * `schema` - The DTDL schema of the property.
+fn rust_type(schema: &Value) -> Result<String, String> {
+    match schema {
+        Value::String(primitive) => match primitive.as_str() {
+            "boolean" => Ok("bool".to_string()),
+            "integer" => Ok("i32".to_string()),
+            "long" => Ok("i64".to_string()),
+            "float" => Ok("f32".to_string()),
+            "double" => Ok("f64".to_string()),
+            "string" => Ok("String".to_string()),
+            other => Err(format!("Unsupported DTDL schema '{other}'")),
+        },
+        enum_schema if enum_schema.get("@type").and_then(Value::as_str) == Some("Enum") => {
+            let enum_name = dtmi_name(get_str(enum_schema, "@id")?);
+            Ok(format!("crate::{}::{enum_name}", to_snake_case(enum_name)))
+        }
+        other => Err(format!("Unsupported DTDL schema '{other}'")),
+    }
+}
+
+/// Generate compile-time checks that a hand-written enum matches the values of an integer enum
+/// schema.
+///
+/// # Arguments
+/// * `schema` - The DTDL schema of the property.
+fn enum_value_checks(schema: &Value) -> Result<String, String> {
+    let mut checks = String::new();
+
+    if schema.get("valueSchema").and_then(Value::as_str) != Some("integer") {
+        return Ok(checks);
+    }
+
+    let enum_values = schema
+        .get("enumValues")
+        .and_then(Value::as_array)
+        .ok_or_else(|| format!("Enum schema {schema} has no enumValues"))?;
+
+    for enum_value in enum_values {
+        let name = get_str(enum_value, "name")?;
+        let value = enum_value
+            .get("enumValue")
+            .and_then(Value::as_i64)
+            .ok_or_else(|| format!("Enum value '{name}' is not an integer"))?;
+
+        writeln!(
+            checks,
+            "        const _: () = assert!(TYPE::{name} as i32 == {value});"
+        )
+        .map_err(|err| err.to_string())?;
+    }
+
+    Ok(checks)
+}
+
+/// Get a required string field from a DTDL element.
+///
+/// # Arguments
+/// * `element` - The DTDL element.
+/// * `field` - The field's name.
+fn get_str<'a>(element: &'a Value, field: &str) -> Result<&'a str, String> {
+    element
+        .get(field)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("Missing string field '{field}' in {element}"))
+}
+
+/// Generate the Rust source for all interfaces in a DTDL document.
+///
+/// # Arguments
+/// * `document` - The parsed DTDL document.
+fn generate(document: &Value) -> Result<String, String> {
+    let interfaces = match document {
+        Value::Array(interfaces) => interfaces.clone(),
+        interface => vec![interface.clone()],
+    };
+
+    let mut output = String::new();
+    writeln!(
+        output,
+        "// Generated from \"{DTDL_PATH}\" by build.rs. Do not edit."
+    )
+    .map_err(|err| err.to_string())?;
+
+    for interface in interfaces.iter() {
+        let interface_id = get_str(interface, "@id")?;
+        let interface_module = to_snake_case(dtmi_name(interface_id));
+
+        writeln!(output).map_err(|err| err.to_string())?;
+        if let Some(description) = interface.get("description").and_then(Value::as_str) {
+            writeln!(output, "/// {description}").map_err(|err| err.to_string())?;
+        }
+        writeln!(output, "pub mod {interface_module} {{").map_err(|err| err.to_string())?;
+
+        let contents = interface
+            .get("contents")
+            .and_then(Value::as_array)
+            .ok_or_else(|| format!("Interface '{interface_id}' has no contents"))?;
+
+        let properties = contents
+            .iter()
+            .filter(|content| content.get("@type").and_then(Value::as_str) == Some("Property"));
+
+        for (index, property) in properties.enumerate() {
+            let id = get_str(property, "@id")?;
+            let name = get_str(property, "name")?;
+            let description = property
+                .get("description")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let writable = property
+                .get("writable")
+                .and_then(Value::as_bool)
+                .unwrap_or_default();
+            let schema = property
+                .get("schema")
+                .ok_or_else(|| format!("Property '{id}' has no schema"))?;
+            let type_name = rust_type(schema).map_err(|err| format!("Property '{id}': {err}"))?;
+
+            if index != 0 {
+                writeln!(output).map_err(|err| err.to_string())?;
+            }
+
+            write!(
+                output,
+                "    pub mod {module} {{
+        pub const ID: &str = {id:?};
+        pub const NAME: &str = {name:?};
+        pub const DESCRIPTION: &str = {description:?};
+        pub const WRITABLE: bool = {writable};
+
+        pub type TYPE = {type_name};
+",
+                module = to_snake_case(name),
+            )
+            .map_err(|err| err.to_string())?;
+
+            let checks = enum_value_checks(schema)?;
+            if !checks.is_empty() {
+                write!(output, "\n{checks}").map_err(|err| err.to_string())?;
+            }
+
+            writeln!(output, "    }}").map_err(|err| err.to_string())?;
+        }
+
+        // Commands have no value, only their identity is generated.
+        let commands = contents
+            .iter()
+            .filter(|content| content.get("@type").and_then(Value::as_str) == Some("Command"));
+
+        for command in commands {
+            let id = get_str(command, "@id")?;
+            let name = get_str(command, "name")?;
+            let description = command
+                .get("description")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+
+            write!(
+                output,
+                "
+    pub mod {module} {{
+        pub const ID: &str = {id:?};
+        pub const NAME: &str = {name:?};
+        pub const DESCRIPTION: &str = {description:?};
+    }}
+",
+                module = to_snake_case(name),
+            )
+            .map_err(|err| err.to_string())?;
+        }
+
+        writeln!(output, "}}").map_err(|err| err.to_string())?;
+    }
+
+    Ok(output)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed={DTDL_PATH}");
+
+    let dtdl = fs::read_to_string(DTDL_PATH)?;
+    let document: Value = serde_json::from_str(&dtdl)?;
+    let generated = generate(&document)?;
+
+    let out_dir = env::var("OUT_DIR")?;
+    fs::write(Path::new(&out_dir).join(GENERATED_FILE_NAME), generated)?;
+
+    Ok(())
+}
@@ -0,0 +1,136 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! The side of the car that the wheelchair approaches.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde_derive::{Deserialize, Serialize};
+
+/// The side of the car that the wheelchair approaches.
+///
+/// On the wire the side is encoded as its integer value, as described by the
+/// "WheelchairApproachSide" property in "../dtdl/car.json".
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "i32", into = "i32")]
+pub enum ApproachSide {
+    /// The side of the driver's door.
+    #[default]
+    Driver = 1,
+    /// The side of the front passenger's door.
+    Passenger = 2,
+}
+
+/// Errors for decoding an approach side.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ApproachSideError {
+    /// The value does not encode a side.
+    InvalidValue(i32),
+    /// The name does not encode a side.
+    InvalidName(String),
+}
+
+impl fmt::Display for ApproachSideError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApproachSideError::InvalidValue(value) => {
+                write!(f, "'{value}' is not a valid approach side")
+            }
+            ApproachSideError::InvalidName(name) => {
+                write!(f, "'{name}' is not a valid approach side")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ApproachSideError {}
+
+impl fmt::Display for ApproachSide {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ApproachSide::Driver => "DRIVER",
+            ApproachSide::Passenger => "PASSENGER",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for ApproachSide {
+    type Err = ApproachSideError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "DRIVER" => Ok(ApproachSide::Driver),
+            "PASSENGER" => Ok(ApproachSide::Passenger),
+            _ => Err(ApproachSideError::InvalidName(s.to_string())),
+        }
+    }
+}
+
+impl TryFrom<i32> for ApproachSide {
+    type Error = ApproachSideError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(ApproachSide::Driver),
+            2 => Ok(ApproachSide::Passenger),
+            _ => Err(ApproachSideError::InvalidValue(value)),
+        }
+    }
+}
+
+impl From<ApproachSide> for i32 {
+    fn from(side: ApproachSide) -> Self {
+        side as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIDES: [ApproachSide; 2] = [ApproachSide::Driver, ApproachSide::Passenger];
+
+    #[test]
+    fn the_integer_value_round_trips() {
+        for side in SIDES {
+            assert_eq!(ApproachSide::try_from(i32::from(side)), Ok(side));
+        }
+        assert_eq!(i32::from(ApproachSide::Driver), 1);
+        assert_eq!(i32::from(ApproachSide::Passenger), 2);
+        assert_eq!(
+            ApproachSide::try_from(0),
+            Err(ApproachSideError::InvalidValue(0))
+        );
+        assert_eq!(
+            ApproachSide::try_from(3),
+            Err(ApproachSideError::InvalidValue(3))
+        );
+    }
+
+    #[test]
+    fn the_name_round_trips() {
+        for side in SIDES {
+            assert_eq!(side.to_string().parse(), Ok(side));
+        }
+        assert_eq!("passenger".parse(), Ok(ApproachSide::Passenger));
+        assert_eq!(
+            "LEFT".parse::<ApproachSide>(),
+            Err(ApproachSideError::InvalidName("LEFT".to_string()))
+        );
+    }
+
+    #[test]
+    fn serde_uses_the_integer_value() {
+        for side in SIDES {
+            let json = serde_json::to_string(&side).unwrap();
+            assert_eq!(json, i32::from(side).to_string());
+            assert_eq!(serde_json::from_str::<ApproachSide>(&json).unwrap(), side);
+        }
+        assert!(serde_json::from_str::<ApproachSide>("0").is_err());
+        assert!(serde_json::from_str::<ApproachSide>("\"DRIVER\"").is_err());
+    }
+}
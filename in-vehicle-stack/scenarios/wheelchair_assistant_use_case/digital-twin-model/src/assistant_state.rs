@@ -0,0 +1,359 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! The wheelchair assistant state machine.
+//!
+//! The states and transitions follow the state diagram in
+//! "docs/wheelchair_assistant_use_case/diagrams/wheel_assistant_states.png".
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde_derive::{Deserialize, Serialize};
+
+/// The state of the wheelchair assistant.
+///
+/// On the wire the state is encoded as its integer value, as described by the
+/// "WheelchairAssistantState" property in "../dtdl/car.json".
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "i32", into = "i32")]
+pub enum AssistantState {
+    /// The car is parked and locked.
+    #[default]
+    Init = 1,
+    /// The car is unlocked and the wheelchair distance is being measured.
+    Open = 2,
+    /// The wheelchair is near, the car is adjusted for entry and waits to be started.
+    Hold = 3,
+    /// The car is running.
+    Drive = 4,
+}
+
+/// The events that move the wheelchair assistant from one state to another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AssistantEvent {
+    CarUnlock,
+    CarLock,
+    WheelchairNear,
+    WheelchairFar,
+    CarOn,
+    CarOff,
+}
+
+/// The transition table of the wheelchair assistant as (from, event, to).
+pub const TRANSITIONS: &[(AssistantState, AssistantEvent, AssistantState)] = &[
+    (
+        AssistantState::Init,
+        AssistantEvent::CarUnlock,
+        AssistantState::Open,
+    ),
+    (
+        AssistantState::Open,
+        AssistantEvent::CarLock,
+        AssistantState::Init,
+    ),
+    (
+        AssistantState::Open,
+        AssistantEvent::WheelchairNear,
+        AssistantState::Hold,
+    ),
+    (
+        AssistantState::Hold,
+        AssistantEvent::WheelchairFar,
+        AssistantState::Open,
+    ),
+    (
+        AssistantState::Hold,
+        AssistantEvent::CarLock,
+        AssistantState::Init,
+    ),
+    (
+        AssistantState::Hold,
+        AssistantEvent::CarOn,
+        AssistantState::Drive,
+    ),
+    (
+        AssistantState::Drive,
+        AssistantEvent::CarOff,
+        AssistantState::Hold,
+    ),
+];
+
+/// Errors for the wheelchair assistant state machine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AssistantStateError {
+    /// The value does not encode a state.
+    InvalidValue(i32),
+    /// The name does not encode a state.
+    InvalidName(String),
+    /// The event is not allowed in the current state.
+    IllegalEvent {
+        state: AssistantState,
+        event: AssistantEvent,
+    },
+    /// There is no transition between the two states.
+    IllegalTransition {
+        from: AssistantState,
+        to: AssistantState,
+    },
+}
+
+impl fmt::Display for AssistantStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssistantStateError::InvalidValue(value) => {
+                write!(f, "'{value}' is not a valid wheelchair assistant state")
+            }
+            AssistantStateError::InvalidName(name) => {
+                write!(f, "'{name}' is not a valid wheelchair assistant state")
+            }
+            AssistantStateError::IllegalEvent { state, event } => {
+                write!(f, "Event {event:?} is not allowed in state {state}")
+            }
+            AssistantStateError::IllegalTransition { from, to } => {
+                write!(
+                    f,
+                    "Transition from state {from} to state {to} is not allowed"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for AssistantStateError {}
+
+impl AssistantState {
+    /// Get the state that the event leads to from this state.
+    ///
+    /// # Arguments
+    /// * `event` - The event to handle.
+    pub fn on_event(self, event: AssistantEvent) -> Result<AssistantState, AssistantStateError> {
+        TRANSITIONS
+            .iter()
+            .find(|(from, on, _)| *from == self && *on == event)
+            .map(|(_, _, to)| *to)
+            .ok_or(AssistantStateError::IllegalEvent { state: self, event })
+    }
+
+    /// Is there a transition from this state to the provided state?
+    ///
+    /// # Arguments
+    /// * `to` - The target state.
+    pub fn can_transition_to(self, to: AssistantState) -> bool {
+        TRANSITIONS
+            .iter()
+            .any(|(from, _, target)| *from == self && *target == to)
+    }
+
+    /// Validate a transition from this state to the provided state.
+    ///
+    /// # Arguments
+    /// * `to` - The target state.
+    pub fn transition_to(self, to: AssistantState) -> Result<AssistantState, AssistantStateError> {
+        if self.can_transition_to(to) {
+            Ok(to)
+        } else {
+            Err(AssistantStateError::IllegalTransition { from: self, to })
+        }
+    }
+}
+
+impl fmt::Display for AssistantState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AssistantState::Init => "INIT",
+            AssistantState::Open => "OPEN",
+            AssistantState::Hold => "HOLD",
+            AssistantState::Drive => "DRIVE",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for AssistantState {
+    type Err = AssistantStateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "INIT" => Ok(AssistantState::Init),
+            "OPEN" => Ok(AssistantState::Open),
+            "HOLD" => Ok(AssistantState::Hold),
+            "DRIVE" => Ok(AssistantState::Drive),
+            _ => Err(AssistantStateError::InvalidName(s.to_string())),
+        }
+    }
+}
+
+impl TryFrom<i32> for AssistantState {
+    type Error = AssistantStateError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(AssistantState::Init),
+            2 => Ok(AssistantState::Open),
+            3 => Ok(AssistantState::Hold),
+            4 => Ok(AssistantState::Drive),
+            _ => Err(AssistantStateError::InvalidValue(value)),
+        }
+    }
+}
+
+impl From<AssistantState> for i32 {
+    fn from(state: AssistantState) -> Self {
+        state as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATES: [AssistantState; 4] = [
+        AssistantState::Init,
+        AssistantState::Open,
+        AssistantState::Hold,
+        AssistantState::Drive,
+    ];
+
+    const EVENTS: [AssistantEvent; 6] = [
+        AssistantEvent::CarUnlock,
+        AssistantEvent::CarLock,
+        AssistantEvent::WheelchairNear,
+        AssistantEvent::WheelchairFar,
+        AssistantEvent::CarOn,
+        AssistantEvent::CarOff,
+    ];
+
+    #[test]
+    fn every_legal_transition_is_taken() {
+        for (from, event, to) in TRANSITIONS {
+            assert_eq!(from.on_event(*event), Ok(*to));
+            assert!(from.can_transition_to(*to));
+            assert_eq!(from.transition_to(*to), Ok(*to));
+        }
+    }
+
+    #[test]
+    fn the_state_diagram_is_followed() {
+        let expected = [
+            (
+                AssistantState::Init,
+                AssistantEvent::CarUnlock,
+                AssistantState::Open,
+            ),
+            (
+                AssistantState::Open,
+                AssistantEvent::CarLock,
+                AssistantState::Init,
+            ),
+            (
+                AssistantState::Open,
+                AssistantEvent::WheelchairNear,
+                AssistantState::Hold,
+            ),
+            (
+                AssistantState::Hold,
+                AssistantEvent::WheelchairFar,
+                AssistantState::Open,
+            ),
+            (
+                AssistantState::Hold,
+                AssistantEvent::CarLock,
+                AssistantState::Init,
+            ),
+            (
+                AssistantState::Hold,
+                AssistantEvent::CarOn,
+                AssistantState::Drive,
+            ),
+            (
+                AssistantState::Drive,
+                AssistantEvent::CarOff,
+                AssistantState::Hold,
+            ),
+        ];
+        assert_eq!(TRANSITIONS, expected);
+    }
+
+    #[test]
+    fn illegal_events_are_rejected_in_every_state() {
+        for state in STATES {
+            let illegal_events = EVENTS.iter().filter(|event| {
+                !TRANSITIONS
+                    .iter()
+                    .any(|(from, on, _)| *from == state && on == *event)
+            });
+
+            let mut count = 0;
+            for event in illegal_events {
+                assert_eq!(
+                    state.on_event(*event),
+                    Err(AssistantStateError::IllegalEvent {
+                        state,
+                        event: *event
+                    })
+                );
+                count += 1;
+            }
+            assert!(count > 0, "{state} should reject some events");
+        }
+    }
+
+    #[test]
+    fn illegal_transitions_are_rejected() {
+        assert_eq!(
+            AssistantState::Init.transition_to(AssistantState::Drive),
+            Err(AssistantStateError::IllegalTransition {
+                from: AssistantState::Init,
+                to: AssistantState::Drive
+            })
+        );
+        assert!(!AssistantState::Drive.can_transition_to(AssistantState::Init));
+        assert!(!AssistantState::Open.can_transition_to(AssistantState::Open));
+    }
+
+    #[test]
+    fn the_integer_value_round_trips() {
+        for state in STATES {
+            assert_eq!(AssistantState::try_from(i32::from(state)), Ok(state));
+        }
+        assert_eq!(i32::from(AssistantState::Init), 1);
+        assert_eq!(i32::from(AssistantState::Drive), 4);
+        assert_eq!(
+            AssistantState::try_from(0),
+            Err(AssistantStateError::InvalidValue(0))
+        );
+        assert_eq!(
+            AssistantState::try_from(5),
+            Err(AssistantStateError::InvalidValue(5))
+        );
+    }
+
+    #[test]
+    fn the_name_round_trips() {
+        for state in STATES {
+            assert_eq!(state.to_string().parse(), Ok(state));
+        }
+        assert_eq!("hold".parse(), Ok(AssistantState::Hold));
+        assert_eq!(
+            "PARKED".parse::<AssistantState>(),
+            Err(AssistantStateError::InvalidName("PARKED".to_string()))
+        );
+    }
+
+    #[test]
+    fn serde_uses_the_integer_value() {
+        for state in STATES {
+            let json = serde_json::to_string(&state).unwrap();
+            assert_eq!(json, i32::from(state).to_string());
+            assert_eq!(
+                serde_json::from_str::<AssistantState>(&json).unwrap(),
+                state
+            );
+        }
+        assert!(serde_json::from_str::<AssistantState>("7").is_err());
+        assert!(serde_json::from_str::<AssistantState>("\"HOLD\"").is_err());
+    }
+}
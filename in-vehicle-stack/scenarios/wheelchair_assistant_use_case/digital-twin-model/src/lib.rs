@@ -2,6 +2,8 @@
 // Licensed under the MIT license.
 // SPDX-License-Identifier: MIT
 
+pub mod approach_side;
+pub mod assistant_state;
 pub mod car_v1;
 
 use serde_derive::{Deserialize, Serialize};
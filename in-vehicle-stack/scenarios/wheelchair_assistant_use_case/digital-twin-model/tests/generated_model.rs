@@ -0,0 +1,205 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! The bindings that build.rs generates into `car_v1` match the vehicle model in "dtdl/car.json".
+
+use std::any::type_name;
+use std::path::Path;
+
+use serde_json::Value;
+use wheelchair_digital_twin_model::car_v1::car;
+
+const DTDL_PATH: &str = "dtdl/car.json";
+const CAR_INTERFACE_ID: &str = "dtmi:sdv:Car;1";
+
+/// A generated module of a property or command.
+#[derive(Debug)]
+struct Generated {
+    module: &'static str,
+    id: &'static str,
+    name: &'static str,
+    description: &'static str,
+    /// Only set for properties.
+    writable: Option<bool>,
+    /// Only set for properties.
+    type_name: Option<&'static str>,
+}
+
+macro_rules! property {
+    ($module:ident) => {
+        Generated {
+            module: stringify!($module),
+            id: car::$module::ID,
+            name: car::$module::NAME,
+            description: car::$module::DESCRIPTION,
+            writable: Some(car::$module::WRITABLE),
+            type_name: Some(type_name::<car::$module::TYPE>()),
+        }
+    };
+}
+
+macro_rules! command {
+    ($module:ident) => {
+        Generated {
+            module: stringify!($module),
+            id: car::$module::ID,
+            name: car::$module::NAME,
+            description: car::$module::DESCRIPTION,
+            writable: None,
+            type_name: None,
+        }
+    };
+}
+
+/// Every module that is generated for the car interface.
+fn generated() -> Vec<Generated> {
+    vec![
+        property!(is_door_open),
+        property!(is_steeringwheel_in_assist_position),
+        property!(is_car_running),
+        property!(is_seat_in_assist_position),
+        property!(is_car_unlocked),
+        property!(wheelchair_distance),
+        property!(wheelchair_distance_state),
+        property!(wheelchair_bearing),
+        property!(wheelchair_approach_side),
+        property!(wheelchair_assistant_state),
+        command!(lock),
+        command!(unlock),
+        command!(start_engine),
+        command!(stop_engine),
+    ]
+}
+
+/// Read the contents of the car interface from the DTDL.
+fn dtdl_contents() -> Vec<Value> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(DTDL_PATH);
+    let dtdl = std::fs::read_to_string(path).expect("The DTDL should be readable");
+    let document: Value = serde_json::from_str(&dtdl).expect("The DTDL should be JSON");
+
+    let interfaces = document.as_array().expect("The DTDL should be an array");
+    let car = interfaces
+        .iter()
+        .find(|interface| interface["@id"] == CAR_INTERFACE_ID)
+        .expect("The DTDL should have the car interface");
+
+    car["contents"]
+        .as_array()
+        .expect("The car interface should have contents")
+        .clone()
+}
+
+/// Convert a DTDL name in PascalCase to snake_case.
+///
+/// # Arguments
+/// * `name` - The name to convert.
+fn to_snake_case(name: &str) -> String {
+    let mut result = String::new();
+    for character in name.chars() {
+        if character.is_uppercase() && !result.is_empty() {
+            result.push('_');
+        }
+        result.extend(character.to_lowercase());
+    }
+    result
+}
+
+/// Check that a generated type is the Rust type for a DTDL schema.
+///
+/// # Arguments
+/// * `schema` - The DTDL schema of the property.
+/// * `type_name` - The name of the generated type.
+fn assert_type_matches(schema: &Value, type_name: &str) {
+    match schema {
+        Value::String(primitive) => {
+            let expected = match primitive.as_str() {
+                "boolean" => "bool",
+                "integer" => "i32",
+                "long" => "i64",
+                "float" => "f32",
+                "double" => "f64",
+                "string" => "alloc::string::String",
+                other => panic!("Unexpected DTDL schema '{other}'"),
+            };
+            assert_eq!(type_name, expected, "{schema}");
+        }
+        enum_schema => {
+            assert_eq!(enum_schema["@type"], "Enum", "{schema}");
+
+            // E.g. "dtmi:sdv:Car:AssistantState;1" is `assistant_state::AssistantState`.
+            let enum_id = enum_schema["@id"]
+                .as_str()
+                .expect("The enum should have an id");
+            let enum_name = enum_id
+                .split(';')
+                .next()
+                .and_then(|id| id.rsplit(':').next())
+                .expect("The enum id should have a name");
+            let expected = format!(
+                "wheelchair_digital_twin_model::{}::{enum_name}",
+                to_snake_case(enum_name)
+            );
+            assert_eq!(type_name, expected);
+        }
+    }
+}
+
+#[test]
+fn every_generated_module_matches_its_dtdl_content() {
+    let contents = dtdl_contents();
+    let generated = generated();
+
+    for content in &contents {
+        let id = content["@id"]
+            .as_str()
+            .expect("The content should have an id");
+        let module = generated
+            .iter()
+            .find(|generated| generated.id == id)
+            .unwrap_or_else(|| panic!("No module is generated for {id}"));
+
+        let name = content["name"]
+            .as_str()
+            .expect("The content should have a name");
+        assert_eq!(module.name, name, "{id}");
+        assert_eq!(module.module, to_snake_case(name), "{id}");
+        assert_eq!(
+            module.description,
+            content["description"].as_str().unwrap_or_default(),
+            "{id}"
+        );
+
+        match content["@type"].as_str() {
+            Some("Property") => {
+                let writable = content["writable"].as_bool().unwrap_or_default();
+                assert_eq!(module.writable, Some(writable), "{id}");
+
+                let type_name = module.type_name.expect("A property should have a type");
+                assert_type_matches(&content["schema"], type_name);
+            }
+            Some("Command") => {
+                assert_eq!(module.writable, None, "{id}");
+                assert_eq!(module.type_name, None, "{id}");
+            }
+            other => panic!("Unexpected content type {other:?} of {id}"),
+        }
+    }
+
+    // Nothing is generated that the DTDL does not have.
+    assert_eq!(generated.len(), contents.len());
+}
+
+#[test]
+fn the_generated_ids_are_unique() {
+    let generated = generated();
+
+    for (index, module) in generated.iter().enumerate() {
+        assert!(
+            generated[index + 1..]
+                .iter()
+                .all(|other| other.id != module.id && other.name != module.name),
+            "{module:?}"
+        );
+    }
+}
@@ -30,4 +30,11 @@ pub mod chariott {
 /// Recognized constraint types for subscribe requests.
 pub mod constraint_type {
     pub const FREQUENCY_MS: &str = "frequency_ms";
+    pub const ON_CHANGE: &str = "on_change";
+    pub const DEADBAND: &str = "deadband";
+    pub const MIN_INTERVAL_MS: &str = "min_interval_ms";
+    pub const MAX_INTERVAL_MS: &str = "max_interval_ms";
+    pub const MAX_AGE_MS: &str = "max_age_ms";
+    pub const QOS: &str = "qos";
+    pub const BATCH_SIZE: &str = "batch_size";
 }
@@ -0,0 +1,195 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! A "Get" provider that serves the properties registered with it and rejects all others, so that
+//! one provider process can serve several properties.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use log::debug;
+use parking_lot::RwLock;
+use tonic::{Request, Response, Status};
+use wheelchair_assistant_interfaces::digital_twin_get_provider::v1::digital_twin_get_provider_server::DigitalTwinGetProvider;
+use wheelchair_assistant_interfaces::digital_twin_get_provider::v1::{
+    GetRequest, GetResponse, Quality,
+};
+use wheelchair_assistant_interfaces::property_value::PropertyValue;
+
+/// Reads a property's current value.
+type ValueSource = Arc<dyn Fn() -> GetResponse + Send + Sync>;
+
+/// Serves the properties in its registry of entity ids to value sources.
+#[derive(Clone, Default)]
+pub struct GetProvider {
+    sources: Arc<RwLock<HashMap<String, ValueSource>>>,
+}
+
+impl fmt::Debug for GetProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GetProvider")
+            .field("entity_ids", &self.entity_ids())
+            .finish()
+    }
+}
+
+impl GetProvider {
+    /// Add a property to the registry.
+    ///
+    /// # Arguments
+    /// * `entity_id` - The property's entity id.
+    /// * `source` - Reads the property's current value.
+    pub fn with_property<T, F>(self, entity_id: &str, source: F) -> Self
+    where
+        T: PropertyValue,
+        F: Fn() -> T + Send + Sync + 'static,
+    {
+        self.register_property(entity_id, source);
+        self
+    }
+
+    /// Add a property to the registry, replacing the value source of an already registered
+    /// property.
+    ///
+    /// # Arguments
+    /// * `entity_id` - The property's entity id.
+    /// * `source` - Reads the property's current value.
+    pub fn register_property<T, F>(&self, entity_id: &str, source: F)
+    where
+        T: PropertyValue,
+        F: Fn() -> T + Send + Sync + 'static,
+    {
+        let source: ValueSource =
+            Arc::new(move || GetResponse::from_property(source(), Quality::Good));
+        self.sources.write().insert(entity_id.to_string(), source);
+    }
+
+    /// Get the entity ids of the registered properties.
+    pub fn entity_ids(&self) -> Vec<String> {
+        self.sources.read().keys().cloned().collect()
+    }
+}
+
+#[tonic::async_trait]
+impl DigitalTwinGetProvider for GetProvider {
+    /// Get the value of a registered property.
+    ///
+    /// # Arguments
+    /// * `request` - The request with the property's entity id.
+    async fn get(&self, request: Request<GetRequest>) -> Result<Response<GetResponse>, Status> {
+        let entity_id = request.into_inner().entity_id;
+        let source = self.sources.read().get(&entity_id).cloned();
+
+        match source {
+            Some(source) => {
+                debug!("Get {entity_id}");
+                Ok(Response::new(source()))
+            }
+            None => Err(Status::not_found(format!(
+                "The property {entity_id} is not provided"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tonic::Code;
+
+    const DISTANCE_ID: &str = "dtmi:sdv:Test:Distance;1";
+    const UNLOCKED_ID: &str = "dtmi:sdv:Test:Unlocked;1";
+
+    /// Get a property from the provider.
+    ///
+    /// # Arguments
+    /// * `provider` - The provider.
+    /// * `entity_id` - The property's entity id.
+    async fn get(provider: &GetProvider, entity_id: &str) -> Result<GetResponse, Status> {
+        provider
+            .get(Request::new(GetRequest {
+                entity_id: entity_id.to_string(),
+            }))
+            .await
+            .map(Response::into_inner)
+    }
+
+    #[tokio::test]
+    async fn a_registered_property_is_read_from_its_source() {
+        let provider = GetProvider::default().with_property(DISTANCE_ID, || 120);
+
+        let response = get(&provider, DISTANCE_ID).await.unwrap();
+        assert_eq!(response.property::<i32>(), Ok(120));
+        assert_eq!(response.quality(), Quality::Good);
+        assert!(response.sampled_at().is_some());
+    }
+
+    #[tokio::test]
+    async fn unknown_entities_are_not_found() {
+        let provider = GetProvider::default().with_property(DISTANCE_ID, || 120);
+
+        for entity_id in [UNLOCKED_ID, "", "dtmi:sdv:Test:Distance;2"] {
+            let status = get(&provider, entity_id).await.unwrap_err();
+            assert_eq!(status.code(), Code::NotFound, "{entity_id}");
+        }
+    }
+
+    #[tokio::test]
+    async fn each_provider_answers_only_its_own_properties() {
+        let distance_provider = GetProvider::default().with_property(DISTANCE_ID, || 120);
+        let unlocked_provider = GetProvider::default().with_property(UNLOCKED_ID, || true);
+
+        assert!(get(&distance_provider, DISTANCE_ID).await.is_ok());
+        assert_eq!(
+            get(&distance_provider, UNLOCKED_ID)
+                .await
+                .unwrap_err()
+                .code(),
+            Code::NotFound
+        );
+
+        assert_eq!(
+            get(&unlocked_provider, UNLOCKED_ID)
+                .await
+                .unwrap()
+                .property::<bool>(),
+            Ok(true)
+        );
+        assert_eq!(
+            get(&unlocked_provider, DISTANCE_ID)
+                .await
+                .unwrap_err()
+                .code(),
+            Code::NotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn one_provider_serves_several_properties() {
+        let provider = GetProvider::default()
+            .with_property(DISTANCE_ID, || 120)
+            .with_property(UNLOCKED_ID, || false);
+
+        let mut entity_ids = provider.entity_ids();
+        entity_ids.sort();
+        assert_eq!(entity_ids, [DISTANCE_ID, UNLOCKED_ID]);
+
+        let distance = get(&provider, DISTANCE_ID).await.unwrap();
+        assert_eq!(distance.property::<i32>(), Ok(120));
+        let unlocked = get(&provider, UNLOCKED_ID).await.unwrap();
+        assert_eq!(unlocked.property::<bool>(), Ok(false));
+    }
+
+    #[tokio::test]
+    async fn registering_a_property_again_replaces_its_source() {
+        let provider = GetProvider::default().with_property(DISTANCE_ID, || 120);
+        provider.register_property(DISTANCE_ID, || 80);
+
+        let response = get(&provider, DISTANCE_ID).await.unwrap();
+        assert_eq!(response.property::<i32>(), Ok(80));
+        assert_eq!(provider.entity_ids(), [DISTANCE_ID]);
+    }
+}
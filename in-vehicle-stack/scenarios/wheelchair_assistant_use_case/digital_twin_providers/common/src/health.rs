@@ -0,0 +1,285 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! Liveness and readiness of a workload, so that orchestration scripts can wait for it.
+//!
+//! Every workload serves the standard `grpc.health.v1` service next to its own services:
+//! - "liveness" is SERVING while the workload runs.
+//! - "readiness" and the overall service "" are SERVING once all checks of the workload pass, e.g.
+//!   it has discovered the In-Vehicle Digital Twin, registered its entities and is connected to its
+//!   MQTT brokers, and NOT_SERVING otherwise.
+//!
+//! With `health_http_authority` set, the same is served over HTTP: `/livez` answers 200 while the
+//! workload runs, `/healthz` answers 200 once it is ready and 503 otherwise, with the checks as
+//! JSON.
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, StatusCode};
+use log::{debug, info, warn};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+use tonic_health::pb::health_server::{Health, HealthServer};
+use tonic_health::server::{health_reporter, HealthReporter};
+use tonic_health::ServingStatus;
+
+use crate::settings::{load_settings, validate_authority, SettingsError, ValidateSettings};
+use crate::shutdown::Shutdown;
+
+/// The service name of the liveness in the gRPC health service.
+pub const LIVENESS_SERVICE: &str = "liveness";
+/// The service name of the readiness in the gRPC health service.
+pub const READINESS_SERVICE: &str = "readiness";
+
+/// How often the probes of the checks are run.
+const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The checks that a workload passes to be ready.
+pub mod check {
+    /// The In-Vehicle Digital Twin has been discovered.
+    pub const DISCOVERY: &str = "discovery";
+    /// The workload's entities have been registered with the In-Vehicle Digital Twin.
+    pub const REGISTRATION: &str = "registration";
+    /// The workload is connected to its MQTT brokers.
+    pub const BROKER: &str = "broker";
+}
+
+/// Settings of the health endpoints, read from the workload's settings file.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct HealthSettings {
+    /// The authority to serve `/livez` and `/healthz` on, they are not served if it is not set.
+    pub health_http_authority: Option<String>,
+}
+
+impl ValidateSettings for HealthSettings {
+    fn validate(&self) -> Result<(), SettingsError> {
+        match &self.health_http_authority {
+            Some(authority) => validate_authority("health_http_authority", authority),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Tells whether a check passes.
+type Probe = Box<dyn Fn() -> bool + Send + Sync>;
+
+/// Tracks the checks of a workload and reports its readiness through the gRPC health service.
+#[derive(Clone)]
+pub struct HealthMonitor {
+    reporter: HealthReporter,
+    /// Whether each check passes.
+    checks: Arc<tokio::sync::Mutex<BTreeMap<String, bool>>>,
+    /// The probes of the polled checks, such a check passes if all of its probes do.
+    probes: Arc<Mutex<Vec<(String, Probe)>>>,
+}
+
+impl HealthMonitor {
+    /// Create a monitor whose checks fail until they are set, and the gRPC health service that
+    /// reports them.
+    ///
+    /// # Arguments
+    /// * `checks` - The checks that the workload has to pass to be ready.
+    pub async fn new(checks: &[&str]) -> (Self, HealthServer<impl Health>) {
+        let (mut reporter, service) = health_reporter();
+        reporter
+            .set_service_status(LIVENESS_SERVICE, ServingStatus::Serving)
+            .await;
+
+        let checks: BTreeMap<String, bool> = checks
+            .iter()
+            .map(|check| (check.to_string(), false))
+            .collect();
+        let monitor = HealthMonitor {
+            reporter,
+            checks: Arc::new(tokio::sync::Mutex::new(checks)),
+            probes: Arc::default(),
+        };
+        monitor.report(&*monitor.checks.lock().await).await;
+
+        (monitor, service)
+    }
+
+    /// Report the readiness for the checks through the gRPC health service.
+    ///
+    /// # Arguments
+    /// * `checks` - Whether each check passes.
+    async fn report(&self, checks: &BTreeMap<String, bool>) {
+        let status = if checks.values().all(|is_passing| *is_passing) {
+            ServingStatus::Serving
+        } else {
+            ServingStatus::NotServing
+        };
+
+        let mut reporter = self.reporter.clone();
+        reporter.set_service_status(READINESS_SERVICE, status).await;
+        reporter.set_service_status("", status).await;
+    }
+
+    /// Set whether a check passes.
+    ///
+    /// # Arguments
+    /// * `check` - The check.
+    /// * `is_passing` - Whether it passes.
+    pub async fn set(&self, check: &str, is_passing: bool) {
+        let mut checks = self.checks.lock().await;
+
+        if checks.insert(check.to_string(), is_passing) != Some(is_passing) {
+            let state = if is_passing { "passing" } else { "failing" };
+            info!("The health check {check} is {state}.");
+            self.report(&checks).await;
+        }
+    }
+
+    /// Poll a check, it passes if all of its probes do.
+    ///
+    /// # Arguments
+    /// * `check` - The check.
+    /// * `probe` - Tells whether the check passes, it must not block.
+    pub fn add_probe<F>(&self, check: &str, probe: F)
+    where
+        F: Fn() -> bool + Send + Sync + 'static,
+    {
+        self.probes
+            .lock()
+            .push((check.to_string(), Box::new(probe)));
+    }
+
+    /// Whether the workload is ready, and whether each check passes.
+    pub async fn readiness(&self) -> (bool, BTreeMap<String, bool>) {
+        let checks = self.checks.lock().await.clone();
+
+        (checks.values().all(|is_passing| *is_passing), checks)
+    }
+
+    /// Run the probes once and set their checks.
+    async fn probe(&self) {
+        let mut results: BTreeMap<String, bool> = BTreeMap::new();
+        for (check, probe) in self.probes.lock().iter() {
+            *results.entry(check.clone()).or_insert(true) &= probe();
+        }
+
+        for (check, is_passing) in results {
+            self.set(&check, is_passing).await;
+        }
+    }
+
+    /// Run the probes periodically until the shutdown.
+    ///
+    /// # Arguments
+    /// * `shutdown` - Stops probing.
+    pub fn start_probing(&self, shutdown: &Shutdown) -> JoinHandle<()> {
+        let monitor = self.clone();
+        let shutdown = shutdown.clone();
+
+        tokio::spawn(async move {
+            let mut ticks = interval(PROBE_INTERVAL);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            while shutdown.run_until(ticks.tick()).await.is_some() {
+                monitor.probe().await;
+            }
+        })
+    }
+
+    /// Answer an HTTP health request.
+    ///
+    /// # Arguments
+    /// * `path` - The request's path.
+    async fn respond(&self, path: &str) -> Response<Body> {
+        let (status, body) = match path {
+            "/livez" => (StatusCode::OK, json!({ "live": true })),
+            "/healthz" => {
+                let (is_ready, checks) = self.readiness().await;
+                let status = if is_ready {
+                    StatusCode::OK
+                } else {
+                    StatusCode::SERVICE_UNAVAILABLE
+                };
+
+                (status, json!({ "ready": is_ready, "checks": checks }))
+            }
+            _ => (
+                StatusCode::NOT_FOUND,
+                json!({ "error": format!("Unknown path {path}") }),
+            ),
+        };
+
+        let mut response = Response::new(Body::from(body.to_string()));
+        *response.status_mut() = status;
+        response
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        response
+    }
+
+    /// Serve `/livez` and `/healthz` over HTTP until the shutdown.
+    ///
+    /// # Arguments
+    /// * `authority` - The authority to serve on.
+    /// * `shutdown` - Stops serving.
+    pub fn start_http_server(
+        &self,
+        authority: &str,
+        shutdown: &Shutdown,
+    ) -> Result<JoinHandle<()>, Box<dyn std::error::Error>> {
+        let addr: SocketAddr = authority.parse()?;
+        let monitor = self.clone();
+
+        let make_service = make_service_fn(move |_| {
+            let monitor = monitor.clone();
+
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let monitor = monitor.clone();
+
+                    async move { Ok::<_, Infallible>(monitor.respond(request.uri().path()).await) }
+                }))
+            }
+        });
+
+        let server = hyper::Server::try_bind(&addr)?
+            .serve(make_service)
+            .with_graceful_shutdown(shutdown.signal());
+        debug!("Serving the health on http://{addr}/healthz"); // Devskim: ignore DS137138
+
+        Ok(tokio::spawn(async move {
+            if let Err(err) = server.await {
+                warn!("The health server stopped due to '{err}'");
+            }
+        }))
+    }
+}
+
+/// Start monitoring the health of a workload: load its health settings, run the probes of its
+/// checks and serve the health over HTTP if `health_http_authority` is set.
+///
+/// # Arguments
+/// * `name` - The workload's binary name, used for the settings file name.
+/// * `checks` - The checks that the workload has to pass to be ready.
+/// * `shutdown` - Stops probing and serving over HTTP.
+pub async fn start_health_monitor(
+    name: &str,
+    checks: &[&str],
+    shutdown: &Shutdown,
+) -> Result<(HealthMonitor, HealthServer<impl Health>), Box<dyn std::error::Error>> {
+    let settings = load_settings(name, &HealthSettings::default())?;
+
+    let (monitor, service) = HealthMonitor::new(checks).await;
+    monitor.start_probing(shutdown);
+
+    if let Some(authority) = &settings.health_http_authority {
+        monitor.start_http_server(authority, shutdown)?;
+    }
+
+    Ok((monitor, service))
+}
@@ -3,4 +3,13 @@
 // SPDX-License-Identifier: MIT
 
 pub mod constants;
+pub mod get_provider;
+pub mod health;
+pub mod managed_subscribe_provider;
+pub mod mqtt_consumer;
+pub mod mqtt_publisher;
+pub mod publish_policy;
+pub mod retry;
+pub mod settings;
+pub mod shutdown;
 pub mod utils;
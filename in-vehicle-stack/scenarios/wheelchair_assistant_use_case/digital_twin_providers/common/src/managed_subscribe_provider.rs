@@ -0,0 +1,936 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! A managed subscribe provider that publishes one digital twin property to the topics that the
+//! Pub Sub Service asks for.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+
+use interfaces::invehicle_digital_twin::v1::invehicle_digital_twin_client::InvehicleDigitalTwinClient;
+use interfaces::invehicle_digital_twin::v1::{EndpointInfo, EntityAccessInfo, RegisterRequest};
+use interfaces::module::managed_subscribe::v1::managed_subscribe_callback_server::{
+    ManagedSubscribeCallback, ManagedSubscribeCallbackServer,
+};
+use interfaces::module::managed_subscribe::v1::{
+    CallbackPayload, TopicManagementRequest, TopicManagementResponse,
+};
+use log::{debug, info, warn};
+use parking_lot::RwLock;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use strum_macros::{Display, EnumString};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, sleep_until, timeout, Duration, Instant};
+use tonic::transport::server::Router;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+
+use crate::constants::chariott::{
+    INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_KIND,
+    INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_REFERENCE, INVEHICLE_DIGITAL_TWIN_SERVICE_NAME,
+    INVEHICLE_DIGITAL_TWIN_SERVICE_NAMESPACE, INVEHICLE_DIGITAL_TWIN_SERVICE_VERSION,
+};
+use crate::constants::{digital_twin_operation, digital_twin_protocol};
+use crate::health::{check, start_health_monitor, HealthMonitor};
+use crate::mqtt_publisher::{MqttPublisher, MqttPublisherPool};
+use crate::publish_policy::{should_publish_change, LastPublished, PublishMode, PublishPolicy};
+use crate::retry::RetryPolicy;
+use crate::shutdown::{deregister_entities, Shutdown, DRAIN_TIMEOUT};
+use crate::utils::{self, discover_service_using_chariott, ProtocolMatching};
+
+/// Actions that are returned from the Pub Sub Service.
+#[derive(Clone, EnumString, Eq, Display, Debug, PartialEq)]
+pub enum ProviderAction {
+    #[strum(serialize = "PUBLISH")]
+    Publish,
+
+    #[strum(serialize = "STOP_PUBLISH")]
+    StopPublish,
+}
+
+#[derive(Debug)]
+pub struct TopicInfo {
+    topic: String,
+    stop_channel: mpsc::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+/// Publishes the values of a data stream for one entity. Clones share the published topics.
+#[derive(Clone, Debug)]
+pub struct ManagedSubscribeProvider<T> {
+    entity_id: String,
+    entity_name: String,
+    data_stream: watch::Receiver<T>,
+    min_interval_ms: u64,
+    entity_map: Arc<RwLock<HashMap<String, Vec<TopicInfo>>>>,
+    mqtt_publishers: Arc<MqttPublisherPool>,
+}
+
+/// Create the JSON object for a property.
+///
+/// # Arguments
+/// * `entity_id` - The property's entity id.
+/// * `entity_name` - The property's name.
+/// * `value` - The property's value.
+pub fn create_property_value<T: Serialize>(
+    entity_id: &str,
+    entity_name: &str,
+    value: &T,
+) -> Result<Value, serde_json::Error> {
+    let mut property = Map::new();
+    property.insert(entity_name.to_string(), serde_json::to_value(value)?);
+    property.insert("$metadata".to_string(), json!({ "$model": entity_id }));
+
+    Ok(Value::Object(property))
+}
+
+/// Create the JSON for a property.
+///
+/// # Arguments
+/// * `entity_id` - The property's entity id.
+/// * `entity_name` - The property's name.
+/// * `value` - The property's value.
+pub fn create_property_json<T: Serialize>(
+    entity_id: &str,
+    entity_name: &str,
+    value: &T,
+) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&create_property_value(entity_id, entity_name, value)?)
+}
+
+/// The context of the managed subscribe endpoints that this process registers.
+///
+/// Ibeji replaces the URI of managed subscribe endpoints with its Managed Subscribe module's, so the
+/// context is unique per process to tell its endpoints apart from another provider's.
+pub fn registration_context() -> &'static str {
+    static CONTEXT: OnceLock<String> = OnceLock::new();
+
+    CONTEXT.get_or_init(|| format!("GetSubscriptionInfo/{}", Uuid::new_v4()))
+}
+
+/// Create the access information of a managed subscribe entity, as it is registered with the
+/// In-Vehicle Digital Twin.
+///
+/// # Arguments
+/// * `provider_uri` - The provider's URI.
+/// * `entity_id` - The entity's id.
+/// * `entity_name` - The entity's name.
+/// * `entity_description` - The entity's description.
+pub fn create_managed_subscribe_entity_access_info(
+    provider_uri: &str,
+    entity_id: &str,
+    entity_name: &str,
+    entity_description: &str,
+) -> EntityAccessInfo {
+    let endpoint_info = EndpointInfo {
+        protocol: digital_twin_protocol::GRPC.to_string(),
+        operations: vec![digital_twin_operation::MANAGEDSUBSCRIBE.to_string()],
+        uri: provider_uri.to_string(),
+        context: registration_context().to_string(),
+    };
+
+    EntityAccessInfo {
+        name: entity_name.to_string(),
+        id: entity_id.to_string(),
+        description: entity_description.to_string(),
+        endpoint_info_list: vec![endpoint_info],
+    }
+}
+
+/// Register a managed subscribe entity's endpoint with the In-Vehicle Digital Twin.
+///
+/// # Arguments
+/// * `invehicle_digital_twin_uri` - The In-Vehicle Digital Twin URI.
+/// * `provider_uri` - The provider's URI.
+/// * `entity_id` - The entity's id.
+/// * `entity_name` - The entity's name.
+/// * `entity_description` - The entity's description.
+pub async fn register_managed_subscribe_entity(
+    invehicle_digital_twin_uri: &str,
+    provider_uri: &str,
+    entity_id: &str,
+    entity_name: &str,
+    entity_description: &str,
+) -> Result<(), Status> {
+    let entity_access_info = create_managed_subscribe_entity_access_info(
+        provider_uri,
+        entity_id,
+        entity_name,
+        entity_description,
+    );
+
+    let mut client = InvehicleDigitalTwinClient::connect(invehicle_digital_twin_uri.to_string())
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+    let request = tonic::Request::new(RegisterRequest {
+        entity_access_info_list: vec![entity_access_info],
+    });
+    let _response = client.register(request).await?;
+
+    Ok(())
+}
+
+/// Publishes the values of a data stream to one topic.
+struct TopicPublisher {
+    topic: String,
+    entity_id: String,
+    entity_name: String,
+    publisher: Arc<MqttPublisher>,
+    qos: i32,
+    batch_size: usize,
+    max_age: Option<Duration>,
+    /// The properties that have not been published yet.
+    batch: Vec<Value>,
+    /// When the first property of the batch has been added.
+    batch_started_at: Option<Instant>,
+}
+
+impl TopicPublisher {
+    /// Create a publisher for a topic.
+    ///
+    /// # Arguments
+    /// * `topic` - The topic.
+    /// * `entity_id` - The entity's id.
+    /// * `entity_name` - The entity's name.
+    /// * `publisher` - The publisher for the topic's broker.
+    /// * `policy` - How the topic is published.
+    fn new(
+        topic: String,
+        entity_id: String,
+        entity_name: String,
+        publisher: Arc<MqttPublisher>,
+        policy: &PublishPolicy,
+    ) -> Self {
+        TopicPublisher {
+            topic,
+            entity_id,
+            entity_name,
+            publisher,
+            qos: policy.qos,
+            batch_size: policy.batch_size,
+            max_age: policy.max_age_ms.map(Duration::from_millis),
+            batch: Vec::with_capacity(policy.batch_size),
+            batch_started_at: None,
+        }
+    }
+
+    /// Add a value to the batch and publish the batch once it is full.
+    ///
+    /// # Arguments
+    /// * `data` - The value.
+    async fn publish<T: Serialize + Debug>(&mut self, data: &T) -> Result<(), serde_json::Error> {
+        let property = create_property_value(&self.entity_id, &self.entity_name, data)?;
+        debug!("Add {data:?} to the batch for {}", self.topic);
+
+        self.batch.push(property);
+        self.batch_started_at.get_or_insert_with(Instant::now);
+
+        if self.batch.len() >= self.batch_size {
+            self.flush().await;
+        }
+
+        Ok(())
+    }
+
+    /// The time at which the batch is published even if it is not full, `None` if there is no
+    /// such time or the batch is empty.
+    fn flush_deadline(&self) -> Option<Instant> {
+        self.max_age
+            .zip(self.batch_started_at)
+            .map(|(max_age, started_at)| started_at + max_age)
+    }
+
+    /// Publish the batch. A failed publish is logged, the next one is tried again.
+    async fn flush(&mut self) {
+        self.batch_started_at = None;
+        let content = match self.batch.len() {
+            0 => return,
+            // A single property is published on its own, as subscribers expect without batching.
+            1 if self.batch_size == 1 => self.batch.remove(0).to_string(),
+            _ => Value::Array(self.batch.drain(..).collect()).to_string(),
+        };
+
+        // Publish message to broker.
+        let topic = &self.topic;
+        info!("Publish to {topic} for {} with {content}", self.entity_name);
+
+        match self.publisher.publish(topic, &content, self.qos).await {
+            Ok(()) => debug!("Completed publish to {topic}."),
+            Err(err) => warn!("Publish failed due to '{err:?}'"),
+        }
+    }
+
+    /// Publish the current value at a fixed rate until the topic is stopped, then publish what is
+    /// left of the batch.
+    ///
+    /// # Arguments
+    /// * `data_stream` - Receiver for the values.
+    /// * `stop` - Receiver that is disconnected when the topic is stopped.
+    /// * `frequency_ms` - The time between two publishes.
+    async fn publish_periodically<T: Serialize + Clone + Debug>(
+        &mut self,
+        data_stream: watch::Receiver<T>,
+        mut stop: mpsc::Receiver<bool>,
+        frequency_ms: u64,
+    ) {
+        let frequency = Duration::from_millis(frequency_ms);
+        let mut next_sample_at = Instant::now();
+
+        loop {
+            let flush_at = self.flush_deadline();
+
+            // Wait for the next sample, the time to publish the batch or the end of the topic.
+            tokio::select! {
+                _ = stop.recv() => {
+                    info!("Shutdown thread for {}.", self.topic);
+                    self.flush().await;
+                    return;
+                }
+                _ = sleep_until(next_sample_at) => {
+                    // Get data from stream at the current instant.
+                    let data = data_stream.borrow().clone();
+                    if let Err(err) = self.publish(&data).await {
+                        warn!("Failed to serialize {} due to '{err:?}'", self.entity_name);
+                        return;
+                    }
+
+                    next_sample_at = Instant::now() + frequency;
+                }
+                _ = sleep_until(flush_at.unwrap_or(next_sample_at)), if flush_at.is_some() => {
+                    self.flush().await;
+                }
+            }
+        }
+    }
+
+    /// Publish the value when it changes until the topic is stopped, then publish what is left of
+    /// the batch.
+    ///
+    /// # Arguments
+    /// * `data_stream` - Receiver for the values.
+    /// * `stop` - Receiver that is disconnected when the topic is stopped.
+    /// * `deadband` - The smallest numeric change that is published.
+    /// * `min_interval_ms` - The minimum time between two publishes.
+    /// * `max_interval_ms` - The time after which an unchanged value is published again.
+    async fn publish_on_change<T: Serialize + Clone + Debug>(
+        &mut self,
+        mut data_stream: watch::Receiver<T>,
+        mut stop: mpsc::Receiver<bool>,
+        deadband: f64,
+        min_interval_ms: u64,
+        max_interval_ms: Option<u64>,
+    ) {
+        let min_interval = Duration::from_millis(min_interval_ms);
+        let mut last_published: Option<LastPublished> = None;
+
+        loop {
+            let data = data_stream.borrow_and_update().clone();
+            let value = match serde_json::to_value(&data) {
+                Ok(value) => value,
+                Err(err) => {
+                    warn!("Failed to serialize {} due to '{err:?}'", self.entity_name);
+                    return;
+                }
+            };
+
+            if should_publish_change(last_published.as_ref(), &value, deadband, max_interval_ms) {
+                if let Err(err) = self.publish(&data).await {
+                    warn!("Failed to serialize {} due to '{err:?}'", self.entity_name);
+                    return;
+                }
+
+                last_published = Some(LastPublished {
+                    value,
+                    at: Instant::now(),
+                });
+            }
+
+            // The first value is always published, so there is a last publish from here on.
+            let published_at = last_published
+                .as_ref()
+                .map_or_else(Instant::now, |last| last.at);
+            let republish_at = max_interval_ms
+                .map(|max_interval_ms| published_at + Duration::from_millis(max_interval_ms));
+
+            // Wait for a change, the time to publish again or the end of the topic.
+            let mut changed = false;
+            while !changed {
+                let flush_at = self.flush_deadline();
+                let republish = sleep_until(republish_at.unwrap_or(published_at));
+
+                tokio::select! {
+                    _ = stop.recv() => {
+                        info!("Shutdown thread for {}.", self.topic);
+                        self.flush().await;
+                        return;
+                    }
+                    result = data_stream.changed() => {
+                        if result.is_err() {
+                            info!("The data stream for {} has ended.", self.topic);
+                            return;
+                        }
+                        changed = true;
+                    }
+                    _ = republish, if republish_at.is_some() => changed = true,
+                    _ = sleep_until(flush_at.unwrap_or(published_at)), if flush_at.is_some() => {
+                        self.flush().await;
+                    }
+                }
+            }
+
+            // Hold back the change until the minimum interval has passed.
+            let hold_until = published_at + min_interval;
+            while Instant::now() < hold_until {
+                let flush_at = self.flush_deadline();
+
+                tokio::select! {
+                    _ = stop.recv() => {
+                        info!("Shutdown thread for {}.", self.topic);
+                        self.flush().await;
+                        return;
+                    }
+                    _ = sleep_until(hold_until) => {}
+                    _ = sleep_until(flush_at.unwrap_or(hold_until)), if flush_at.is_some() => {
+                        self.flush().await;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<T> ManagedSubscribeProvider<T>
+where
+    T: Serialize + Clone + Debug + Send + Sync + 'static,
+{
+    /// Initializes provider with the entity it publishes.
+    ///
+    /// # Arguments
+    /// * `entity_id` - The entity's id.
+    /// * `entity_name` - The entity's name, used as the property name in published messages.
+    /// * `data_stream` - Receiver for data stream for entity.
+    /// * `min_interval_ms` - The frequency of the data coming over the data stream.
+    pub fn new(
+        entity_id: &str,
+        entity_name: &str,
+        data_stream: watch::Receiver<T>,
+        min_interval_ms: u64,
+    ) -> Self {
+        // Initialize entity map.
+        let mut entity_map = HashMap::new();
+
+        // Insert entry for entity id associated with provider.
+        entity_map.insert(entity_id.to_string(), Vec::new());
+
+        // Create new instance.
+        ManagedSubscribeProvider {
+            entity_id: entity_id.to_string(),
+            entity_name: entity_name.to_string(),
+            data_stream,
+            min_interval_ms,
+            entity_map: Arc::new(RwLock::new(entity_map)),
+            mqtt_publishers: Arc::new(MqttPublisherPool::new(&format!("{entity_name}-publisher"))),
+        }
+    }
+
+    /// Check that a payload is for the provider's entity and names a topic.
+    ///
+    /// # Arguments
+    /// `payload` - Payload sent with an action.
+    fn validate_payload(&self, payload: &CallbackPayload) -> Result<(), Status> {
+        if payload.entity_id != self.entity_id {
+            return Err(Status::not_found(format!(
+                "The provider does not publish the entity '{}'",
+                payload.entity_id
+            )));
+        }
+
+        if payload.topic.is_empty() {
+            return Err(Status::invalid_argument("The payload has no topic"));
+        }
+
+        Ok(())
+    }
+
+    /// Handles the 'PUBLISH' action from the callback. Constraints that are not valid reject the
+    /// topic with an invalid argument status. A topic that is already published is left as it is.
+    ///
+    /// # Arguments
+    /// `payload` - Payload sent with the 'PUBLISH' action.
+    pub fn handle_publish_action(&self, payload: CallbackPayload) -> Result<(), Status> {
+        self.validate_payload(&payload)?;
+
+        // Get payload information.
+        let topic = payload.topic;
+        let policy = PublishPolicy::from_constraints(&payload.constraints, self.min_interval_ms)
+            .map_err(|err| {
+                warn!("Rejected the constraints for {topic} due to '{err}'");
+                Status::from(err)
+            })?;
+        let entity_id = self.entity_id.clone();
+        let entity_name = self.entity_name.clone();
+        let mqtt_publishers = Arc::clone(&self.mqtt_publishers);
+
+        let mut subscription_info = payload.subscription_info.ok_or_else(|| {
+            Status::invalid_argument(format!("The payload for {topic} has no subscription info"))
+        })?;
+
+        subscription_info.uri = utils::get_uri(&subscription_info.uri)?;
+
+        // Create stop publish channel.
+        let (sender, reciever) = mpsc::channel(10);
+
+        let data_stream = self.data_stream.clone();
+        let topic_name = topic.clone();
+
+        // Publishing to the new topic, started once the topic is recorded.
+        let publishing = async move {
+            // Reuse the session to the broker that other topics may already have opened.
+            let publisher = match mqtt_publishers.get(&subscription_info.uri) {
+                Ok(publisher) => publisher,
+                Err(err) => {
+                    warn!("Unable to publish to {topic} due to '{err}'");
+                    return;
+                }
+            };
+
+            let mut topic_publisher =
+                TopicPublisher::new(topic, entity_id, entity_name, publisher, &policy);
+
+            match policy.mode {
+                PublishMode::Periodic { frequency_ms } => {
+                    topic_publisher
+                        .publish_periodically(data_stream, reciever, frequency_ms)
+                        .await
+                }
+                PublishMode::OnChange {
+                    deadband,
+                    min_interval_ms,
+                    max_interval_ms,
+                } => {
+                    topic_publisher
+                        .publish_on_change(
+                            data_stream,
+                            reciever,
+                            deadband,
+                            min_interval_ms,
+                            max_interval_ms,
+                        )
+                        .await
+                }
+            }
+        };
+
+        // Record new topic in entity map.
+        let mut entity_lock = self.entity_map.write();
+        let topics = entity_lock.entry(payload.entity_id).or_default();
+
+        // A topic whose publishing has ended on its own is started again.
+        topics.retain(|topic_info| !topic_info.stop_channel.is_closed());
+
+        if topics
+            .iter()
+            .any(|topic_info| topic_info.topic == topic_name)
+        {
+            debug!("Already publishing to {topic_name}.");
+            return Ok(());
+        }
+
+        // Start thread for new topic.
+        topics.push(TopicInfo {
+            topic: topic_name,
+            stop_channel: sender,
+            task: tokio::spawn(publishing),
+        });
+
+        Ok(())
+    }
+
+    /// Handles the 'STOP_PUBLISH' action from the callback. A topic that is not published is
+    /// left as it is.
+    ///
+    /// # Arguments
+    /// `payload` - Payload sent with the 'STOP_PUBLISH' action.
+    pub fn handle_stop_publish_action(&self, payload: CallbackPayload) -> Result<(), Status> {
+        self.validate_payload(&payload)?;
+
+        let mut entity_lock = self.entity_map.write();
+        let topics = entity_lock.entry(payload.entity_id).or_default();
+
+        // Check to see if topic exists.
+        if let Some(index) = topics.iter().position(|t| t.topic == payload.topic) {
+            // Remove topic, dropping its stop channel stops publishing to it.
+            let topic_info = topics.swap_remove(index);
+            drop(topic_info.stop_channel);
+        } else {
+            debug!("Not publishing to {}, nothing to stop.", payload.topic);
+        }
+
+        Ok(())
+    }
+
+    /// Whether the provider is connected to the brokers of its topics, true if it has published
+    /// to none yet.
+    pub fn is_broker_connected(&self) -> bool {
+        self.mqtt_publishers.is_connected()
+    }
+
+    /// Stop publishing to all topics, wait for the publishing threads to publish what they have
+    /// batched and disconnect from the brokers. Call it once the callback is no longer served, so
+    /// that no topics are added in the meantime.
+    ///
+    /// # Arguments
+    /// * `drain_timeout` - How long to wait for the publishing threads, those that take longer
+    ///   are aborted.
+    pub async fn shutdown(&self, drain_timeout: Duration) {
+        let topics: Vec<TopicInfo> = self
+            .entity_map
+            .write()
+            .values_mut()
+            .flat_map(|topics| topics.drain(..))
+            .collect();
+
+        info!(
+            "Stopping {} topic(s) for {}.",
+            topics.len(),
+            self.entity_name
+        );
+
+        // Dropping the stop channels stops publishing to the topics.
+        let tasks: Vec<(String, JoinHandle<()>)> = topics
+            .into_iter()
+            .map(|topic_info| {
+                drop(topic_info.stop_channel);
+                (topic_info.topic, topic_info.task)
+            })
+            .collect();
+
+        let drain_until = Instant::now() + drain_timeout;
+        for (topic, mut task) in tasks {
+            let remaining = drain_until.saturating_duration_since(Instant::now());
+            if timeout(remaining, &mut task).await.is_err() {
+                warn!("Publishing to {topic} did not stop in time, aborting it.");
+                task.abort();
+            }
+        }
+
+        self.mqtt_publishers.disconnect_all().await;
+        info!("The provider for {} has shut down.", self.entity_name);
+    }
+}
+
+#[tonic::async_trait]
+impl<T> ManagedSubscribeCallback for ManagedSubscribeProvider<T>
+where
+    T: Serialize + Clone + Debug + Send + Sync + 'static,
+{
+    /// Callback for a provider, will process a provider action.
+    ///
+    /// # Arguments
+    /// * `request` - The request with the action and associated payload.
+    async fn topic_management_cb(
+        &self,
+        request: Request<TopicManagementRequest>,
+    ) -> Result<Response<TopicManagementResponse>, Status> {
+        let inner = request.into_inner();
+        let action = inner.action;
+        let payload = inner.payload.ok_or_else(|| {
+            Status::invalid_argument(format!("The {action} request has no payload"))
+        })?;
+
+        let provider_action = ProviderAction::from_str(&action)
+            .map_err(|_| Status::invalid_argument(format!("Unknown action '{action}'")))?;
+
+        match provider_action {
+            ProviderAction::Publish => Self::handle_publish_action(self, payload)?,
+            ProviderAction::StopPublish => Self::handle_stop_publish_action(self, payload)?,
+        }
+
+        Ok(Response::new(TopicManagementResponse {}))
+    }
+}
+
+/// A managed subscribe provider of any property type, so that the providers of different
+/// properties are served alike.
+trait ServedProvider {
+    /// Add the provider's callback service to a router.
+    ///
+    /// # Arguments
+    /// * `router` - The router of the provider's server.
+    fn add_to(&self, router: Router) -> Router;
+
+    /// Whether the provider is connected to the brokers of its topics.
+    fn is_broker_connected(&self) -> bool;
+
+    /// Stop publishing to all topics.
+    ///
+    /// # Arguments
+    /// * `drain_timeout` - How long to wait for the publishing threads.
+    fn shutdown(&self, drain_timeout: Duration) -> Pin<Box<dyn Future<Output = ()> + '_>>;
+}
+
+impl<T> ServedProvider for ManagedSubscribeProvider<T>
+where
+    T: Serialize + Clone + Debug + Send + Sync + 'static,
+{
+    fn add_to(&self, router: Router) -> Router {
+        router.add_service(ManagedSubscribeCallbackServer::new(self.clone()))
+    }
+
+    fn is_broker_connected(&self) -> bool {
+        ManagedSubscribeProvider::is_broker_connected(self)
+    }
+
+    fn shutdown(&self, drain_timeout: Duration) -> Pin<Box<dyn Future<Output = ()> + '_>> {
+        Box::pin(ManagedSubscribeProvider::shutdown(self, drain_timeout))
+    }
+}
+
+/// An entity that a managed subscribe provider publishes, served on an authority of its own.
+pub struct ManagedSubscribeEntity {
+    authority: String,
+    id: String,
+    name: String,
+    description: String,
+    provider: Arc<dyn ServedProvider + Send + Sync>,
+}
+
+impl ManagedSubscribeEntity {
+    /// Create an entity and the provider that publishes it.
+    ///
+    /// # Arguments
+    /// * `authority` - The authority to serve the provider on.
+    /// * `id` - The entity's id.
+    /// * `name` - The entity's name.
+    /// * `description` - The entity's description.
+    /// * `data_stream` - Receiver for the entity's values.
+    /// * `min_interval_ms` - The default publish interval.
+    pub fn new<T>(
+        authority: &str,
+        id: &str,
+        name: &str,
+        description: &str,
+        data_stream: watch::Receiver<T>,
+        min_interval_ms: u64,
+    ) -> Self
+    where
+        T: Serialize + Clone + Debug + Send + Sync + 'static,
+    {
+        ManagedSubscribeEntity {
+            authority: authority.to_string(),
+            id: id.to_string(),
+            name: name.to_string(),
+            description: description.to_string(),
+            provider: Arc::new(ManagedSubscribeProvider::new(
+                id,
+                name,
+                data_stream,
+                min_interval_ms,
+            )),
+        }
+    }
+
+    /// The URI that the entity's provider is registered with.
+    fn provider_uri(&self) -> String {
+        format!("http://{}", self.authority) // Devskim: ignore DS137138
+    }
+
+    /// The entity's access information, as it is registered with the In-Vehicle Digital Twin.
+    fn access_info(&self) -> EntityAccessInfo {
+        create_managed_subscribe_entity_access_info(
+            &self.provider_uri(),
+            &self.id,
+            &self.name,
+            &self.description,
+        )
+    }
+}
+
+/// What a workload runs with once its entities are registered.
+pub struct RegisteredProvider {
+    /// The In-Vehicle Digital Twin URI.
+    pub invehicle_digital_twin_uri: String,
+    /// Stops the workload.
+    pub shutdown: Shutdown,
+    /// The workload's health, e.g. to probe the connections of its own MQTT clients.
+    pub health: HealthMonitor,
+    /// The policy for retrying the workload's requests.
+    pub retry_policy: RetryPolicy,
+}
+
+/// Run a workload that publishes entities through managed subscribe providers until control-c or
+/// SIGTERM: serve the providers next to the gRPC health service, discover the In-Vehicle Digital
+/// Twin, register the entities, run the workload's own part, then stop publishing and deregister
+/// the entities.
+///
+/// # Arguments
+/// * `name` - The workload's binary name, used for the health settings file name.
+/// * `chariott_uri` - Chariott's Service Discovery URI.
+/// * `retry_policy` - The policy for retrying discovery, registration and the workload's requests.
+/// * `entities` - The entities to publish.
+/// * `run` - The workload's own part, started once the entities are registered. The providers are
+///   served until the shutdown if it returns `Ok`, an error stops them.
+pub async fn run_managed_subscribe_provider<F, Fut>(
+    name: &str,
+    chariott_uri: &str,
+    retry_policy: RetryPolicy,
+    entities: Vec<ManagedSubscribeEntity>,
+    run: F,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    F: FnOnce(RegisteredProvider) -> Fut,
+    Fut: Future<Output = Result<(), Box<dyn std::error::Error>>>,
+{
+    let shutdown = Shutdown::on_signals();
+    let (health, health_service) = start_health_monitor(
+        name,
+        &[check::DISCOVERY, check::REGISTRATION, check::BROKER],
+        &shutdown,
+    )
+    .await?;
+
+    // Serve the health service next to the providers right away, so that the workload can be
+    // checked while it waits for the In-Vehicle Digital Twin. The providers are served until the
+    // shutdown, a provider that fails stops the others.
+    let mut server_handles = Vec::new();
+    for entity in &entities {
+        let addr: SocketAddr = entity.authority.parse()?;
+
+        let broker_probe = Arc::clone(&entity.provider);
+        health.add_probe(check::BROKER, move || broker_probe.is_broker_connected());
+
+        debug!("Starting the Provider for {} on {addr}.", entity.name);
+        let router = entity
+            .provider
+            .add_to(Server::builder().add_service(health_service.clone()));
+        let signal = shutdown.signal();
+        let server_shutdown = shutdown.clone();
+        server_handles.push(tokio::spawn(async move {
+            let result = router.serve_with_shutdown(addr, signal).await;
+            if result.is_err() {
+                server_shutdown.trigger();
+            }
+            result
+        }));
+    }
+
+    let mut result: Result<(), Box<dyn std::error::Error>> = Ok(());
+
+    // Get the In-vehicle Digital Twin Uri from the service discovery system
+    let discovery = retry_policy.retry("discover the In-Vehicle Digital Twin Service", || {
+        discover_service_using_chariott(
+            chariott_uri,
+            INVEHICLE_DIGITAL_TWIN_SERVICE_NAMESPACE,
+            INVEHICLE_DIGITAL_TWIN_SERVICE_NAME,
+            INVEHICLE_DIGITAL_TWIN_SERVICE_VERSION,
+            INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_KIND,
+            INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_REFERENCE,
+            ProtocolMatching::Strict,
+        )
+    });
+    let invehicle_digital_twin_uri = match shutdown.run_until(discovery).await {
+        None => {
+            info!("{name} has stopped before discovering the In-Vehicle Digital Twin Service.");
+            None
+        }
+        Some(Err(err)) => {
+            warn!("{name} failed to discover the In-Vehicle Digital Twin Service due to '{err}'");
+            result = Err(err.into());
+            shutdown.trigger();
+            None
+        }
+        Some(Ok(invehicle_digital_twin_uri)) => Some(invehicle_digital_twin_uri),
+    };
+
+    let mut registered = Vec::new();
+    if let Some(invehicle_digital_twin_uri) = &invehicle_digital_twin_uri {
+        health.set(check::DISCOVERY, true).await;
+        debug!("{name} retrieved the In-Vehicle Digital Twin URI {invehicle_digital_twin_uri}.");
+
+        for entity in &entities {
+            let provider_uri = entity.provider_uri();
+            let registration =
+                retry_policy.retry("register with the In-Vehicle Digital Twin Service", || {
+                    register_managed_subscribe_entity(
+                        invehicle_digital_twin_uri,
+                        &provider_uri,
+                        &entity.id,
+                        &entity.name,
+                        &entity.description,
+                    )
+                });
+            match shutdown.run_until(registration).await {
+                None => break,
+                Some(Err(err)) => {
+                    warn!(
+                        "Failed to register {} with Ibeji due to '{err}'",
+                        entity.name
+                    );
+                    result = Err(err.into());
+
+                    // Stop the providers, the entities registered so far are deregistered below.
+                    shutdown.trigger();
+                    break;
+                }
+                Some(Ok(())) => {}
+            }
+            debug!(
+                "The Provider for {} has registered with Ibeji.",
+                entity.name
+            );
+
+            registered.push(entity.access_info());
+        }
+
+        if !shutdown.is_triggered() {
+            health.set(check::REGISTRATION, true).await;
+
+            let registered_provider = RegisteredProvider {
+                invehicle_digital_twin_uri: invehicle_digital_twin_uri.clone(),
+                shutdown: shutdown.clone(),
+                health: health.clone(),
+                retry_policy: retry_policy.clone(),
+            };
+            if let Err(err) = run(registered_provider).await {
+                warn!("{name} has stopped due to '{err}'");
+                result = Err(err);
+                shutdown.trigger();
+            }
+        }
+    }
+
+    for server_handle in server_handles {
+        match server_handle.await {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => {
+                warn!("A Provider of {name} has stopped due to '{err}'");
+                result = result.and(Err(err.into()));
+            }
+            Err(err) => warn!("A Provider of {name} has failed due to '{err}'"),
+        }
+    }
+
+    for entity in &entities {
+        entity.provider.shutdown(DRAIN_TIMEOUT).await;
+    }
+
+    if let (Some(invehicle_digital_twin_uri), false) =
+        (&invehicle_digital_twin_uri, registered.is_empty())
+    {
+        match deregister_entities(invehicle_digital_twin_uri, &registered).await {
+            Ok(()) => debug!("The Providers of {name} have deregistered from Ibeji."),
+            Err(err) => warn!("Failed to deregister from Ibeji due to '{err}'"),
+        }
+    }
+
+    result
+}
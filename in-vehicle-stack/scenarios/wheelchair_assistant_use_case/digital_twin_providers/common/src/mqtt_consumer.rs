@@ -0,0 +1,142 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! MQTT consumers of the JSON messages that the managed subscribe providers publish.
+
+use std::thread;
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use paho_mqtt as mqtt;
+use serde::de::DeserializeOwned;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::health::{check, HealthMonitor};
+use crate::shutdown::Shutdown;
+
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30);
+const MIN_RECONNECT_DELAY: Duration = Duration::from_millis(100);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+/// Consume the JSON messages of a topic until the shutdown. Messages that cannot be parsed are
+/// skipped, a lost connection is reestablished with exponential backoff.
+///
+/// # Arguments
+/// * `broker_uri` - The broker URI.
+/// * `client_id_prefix` - The prefix for the MQTT client id, which is made unique per process.
+/// * `topic` - The topic.
+/// * `shutdown` - Stops consuming.
+/// * `health` - Probes the connection to the broker.
+/// * `on_message` - Called with each parsed message.
+pub fn consume<T, F>(
+    broker_uri: &str,
+    client_id_prefix: &str,
+    topic: &str,
+    shutdown: &Shutdown,
+    health: &HealthMonitor,
+    mut on_message: F,
+) -> Result<JoinHandle<()>, String>
+where
+    T: DeserializeOwned,
+    F: FnMut(T) + Send + 'static,
+{
+    // Create a unique id for the client.
+    let client_id = format!("{client_id_prefix}-{}", Uuid::new_v4());
+
+    let create_opts = mqtt::CreateOptionsBuilder::new()
+        .server_uri(broker_uri)
+        .client_id(client_id)
+        .finalize();
+
+    let client = mqtt::Client::new(create_opts)
+        .map_err(|err| format!("Failed to create the client due to '{err:?}'"))?;
+
+    let receiver = client.start_consuming();
+
+    // Setup task to handle clean shutdown.
+    let ctrlc_cli = client.clone();
+    let signal = shutdown.signal();
+    tokio::spawn(async move {
+        signal.await;
+
+        // Tells the client to shutdown consuming thread.
+        ctrlc_cli.stop_consuming();
+    });
+
+    let broker_probe = client.clone();
+    health.add_probe(check::BROKER, move || broker_probe.is_connected());
+
+    let conn_opts = mqtt::ConnectOptionsBuilder::new_v5()
+        .keep_alive_interval(KEEP_ALIVE_INTERVAL)
+        .clean_session(false)
+        .finalize();
+
+    client
+        .connect(conn_opts)
+        .map_err(|err| format!("Failed to connect due to '{err:?}'"))?;
+
+    client
+        .subscribe(topic, mqtt::types::QOS_1)
+        .map_err(|err| format!("Failed to subscribe to topic {topic} due to '{err:?}'"))?;
+
+    // Copy topic and shutdown for separate thread.
+    let topic = topic.to_string();
+    let shutdown = shutdown.clone();
+
+    // The receiver blocks while it waits for messages, so it gets a thread of its own.
+    let sub_handle = tokio::task::spawn_blocking(move || {
+        for msg in receiver.iter() {
+            if let Some(msg) = msg {
+                debug!("Received {msg}");
+                match serde_json::from_str(&msg.payload_str()) {
+                    Ok(value) => on_message(value),
+                    Err(err) => warn!("Failed to parse the message {msg} due to '{err}'"),
+                }
+            } else if !client.is_connected() {
+                reconnect(&client, &topic, &shutdown);
+            }
+        }
+
+        if client.is_connected() {
+            debug!("Disconnecting");
+            if let Err(err) = client.unsubscribe(topic.as_str()) {
+                warn!("Failed to unsubscribe from topic {topic} due to '{err:?}'");
+            }
+            if let Err(err) = client.disconnect(None) {
+                warn!("Failed to disconnect from the broker due to '{err:?}'");
+            }
+        }
+    });
+
+    Ok(sub_handle)
+}
+
+/// Reconnect to the broker and subscribe to the topic again, retrying with exponential backoff
+/// until it succeeds or the shutdown is triggered.
+///
+/// # Arguments
+/// * `client` - The client that lost its connection.
+/// * `topic` - The topic.
+/// * `shutdown` - Stops reconnecting.
+fn reconnect(client: &mqtt::Client, topic: &str, shutdown: &Shutdown) {
+    let mut delay = MIN_RECONNECT_DELAY;
+
+    while !shutdown.is_triggered() {
+        match client.reconnect() {
+            Ok(_) => {
+                info!("Reconnected to the broker");
+                if let Err(err) = client.subscribe(topic, mqtt::types::QOS_1) {
+                    warn!("Failed to subscribe to topic {topic} due to '{err:?}'");
+                }
+                return;
+            }
+            Err(err) => {
+                warn!("Failed to reconnect to the broker due to '{err:?}', retrying in {delay:?}");
+                thread::sleep(delay);
+                delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+            }
+        }
+    }
+}
@@ -0,0 +1,192 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! Long-lived MQTT publishers, one session per broker.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use log::{debug, info, warn};
+use paho_mqtt as mqtt;
+use parking_lot::Mutex;
+use tokio::time::{sleep, Duration};
+use uuid::Uuid;
+
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30);
+const MIN_RECONNECT_DELAY: Duration = Duration::from_millis(100);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(10);
+const MAX_CONNECT_ATTEMPTS: u32 = 5;
+
+/// A publisher that keeps one connection to a MQTT broker open and reconnects when it is lost.
+pub struct MqttPublisher {
+    broker_uri: String,
+    client: mqtt::AsyncClient,
+    connect_lock: tokio::sync::Mutex<()>,
+}
+
+impl MqttPublisher {
+    /// Create a publisher for a broker. The connection is established on the first publish.
+    ///
+    /// # Arguments
+    /// * `broker_uri` - The MQTT broker's URI.
+    /// * `client_id` - The MQTT client id.
+    pub fn new(broker_uri: &str, client_id: &str) -> Result<Self, String> {
+        let create_opts = mqtt::CreateOptionsBuilder::new()
+            .server_uri(broker_uri)
+            .client_id(client_id)
+            .persistence(mqtt::PersistenceType::None)
+            .finalize();
+
+        let client = mqtt::AsyncClient::new(create_opts)
+            .map_err(|err| format!("Failed to create the client due to '{err:?}'"))?;
+
+        Ok(MqttPublisher {
+            broker_uri: broker_uri.to_string(),
+            client,
+            connect_lock: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    /// Connect to the broker if there is no connection, retrying with exponential backoff.
+    async fn ensure_connected(&self) -> Result<(), String> {
+        // Only one task connects at a time, the others wait for its result.
+        let _connect_guard = self.connect_lock.lock().await;
+
+        let mut delay = MIN_RECONNECT_DELAY;
+        let mut attempt = 1;
+
+        while !self.client.is_connected() {
+            let conn_opts = mqtt::ConnectOptionsBuilder::new()
+                .keep_alive_interval(KEEP_ALIVE_INTERVAL)
+                .clean_session(true)
+                .automatic_reconnect(MIN_RECONNECT_DELAY, MAX_RECONNECT_DELAY)
+                .finalize();
+
+            match self.client.connect(conn_opts).await {
+                Ok(_) => info!("Connected to broker {}", self.broker_uri),
+                Err(err) if attempt < MAX_CONNECT_ATTEMPTS => {
+                    warn!(
+                        "Failed to connect to broker {} due to '{err:?}', retrying in {delay:?}",
+                        self.broker_uri
+                    );
+                    sleep(delay).await;
+                    delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+                    attempt += 1;
+                }
+                Err(err) => {
+                    return Err(format!(
+                        "Failed to connect to broker {} after {attempt} attempts due to '{err:?}'",
+                        self.broker_uri
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Publish a message.
+    ///
+    /// # Arguments
+    /// * `topic` - The topic to publish to.
+    /// * `content` - The message to publish.
+    /// * `qos` - The quality of service, 0, 1 or 2.
+    pub async fn publish(&self, topic: &str, content: &str, qos: i32) -> Result<(), String> {
+        self.ensure_connected().await?;
+
+        let msg = mqtt::Message::new(topic, content, qos);
+        self.client
+            .publish(msg)
+            .await
+            .map_err(|err| format!("Failed to publish message due to '{err:?}'"))
+    }
+
+    /// Whether the publisher is connected to the broker.
+    pub fn is_connected(&self) -> bool {
+        self.client.is_connected()
+    }
+
+    /// Disconnect from the broker.
+    pub async fn disconnect(&self) {
+        if self.client.is_connected() {
+            if let Err(err) = self.client.disconnect(None).await {
+                warn!(
+                    "Failed to disconnect from broker {} due to {err:?}",
+                    self.broker_uri
+                );
+            }
+        }
+    }
+}
+
+/// A pool of publishers that share one session per broker.
+pub struct MqttPublisherPool {
+    client_id_prefix: String,
+    publishers: Mutex<HashMap<String, Arc<MqttPublisher>>>,
+}
+
+impl MqttPublisherPool {
+    /// Create an empty pool.
+    ///
+    /// # Arguments
+    /// * `client_id_prefix` - The prefix for the MQTT client ids of the publishers.
+    pub fn new(client_id_prefix: &str) -> Self {
+        MqttPublisherPool {
+            client_id_prefix: client_id_prefix.to_string(),
+            publishers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the publisher for a broker, creating it if there is none yet.
+    ///
+    /// # Arguments
+    /// * `broker_uri` - The MQTT broker's URI.
+    pub fn get(&self, broker_uri: &str) -> Result<Arc<MqttPublisher>, String> {
+        let mut publishers = self.publishers.lock();
+
+        if let Some(publisher) = publishers.get(broker_uri) {
+            return Ok(Arc::clone(publisher));
+        }
+
+        // Create a unique id for the client, the brokers disconnect a client whose id is reused.
+        let client_id = format!("{}-{}", self.client_id_prefix, Uuid::new_v4());
+        debug!("Creating publisher {client_id} for broker {broker_uri}");
+
+        let publisher = Arc::new(MqttPublisher::new(broker_uri, &client_id)?);
+        publishers.insert(broker_uri.to_string(), Arc::clone(&publisher));
+
+        Ok(publisher)
+    }
+
+    /// Whether all publishers in the pool are connected to their brokers, true for an empty pool.
+    pub fn is_connected(&self) -> bool {
+        self.publishers
+            .lock()
+            .values()
+            .all(|publisher| publisher.is_connected())
+    }
+
+    /// Disconnect all publishers in the pool.
+    pub async fn disconnect_all(&self) {
+        let publishers: Vec<Arc<MqttPublisher>> =
+            self.publishers.lock().values().cloned().collect();
+
+        for publisher in publishers {
+            publisher.disconnect().await;
+        }
+    }
+}
+
+impl fmt::Debug for MqttPublisherPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MqttPublisherPool")
+            .field("client_id_prefix", &self.client_id_prefix)
+            .field(
+                "brokers",
+                &self.publishers.lock().keys().collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
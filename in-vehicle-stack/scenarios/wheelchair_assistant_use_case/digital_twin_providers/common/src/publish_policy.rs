@@ -0,0 +1,629 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! Decides how a managed subscribe topic is published, from the constraints of its subscription.
+//!
+//! By default a topic is published at a fixed rate, `frequency_ms`. With `on_change` set to "true"
+//! it is published when the value changes instead:
+//! - `deadband` ignores numeric changes smaller than it, compared to the last published value.
+//! - `min_interval_ms` holds back changes until that time has passed since the last publish.
+//! - `max_interval_ms` publishes the current value again if nothing has been published for that
+//!   time, so that subscribers can tell a steady value from a lost provider.
+//!
+//! Independent of the mode:
+//! - `qos` is the MQTT quality of service of the messages, 0, 1 or 2.
+//! - `batch_size` publishes that many values together, as a JSON array of properties.
+//! - `max_age_ms` publishes a batch that is not full once its first value is that old.
+//!
+//! A constraint that is unknown, given twice or has an invalid value rejects the subscription.
+
+use std::fmt;
+use std::str::FromStr;
+
+use interfaces::module::managed_subscribe::v1::Constraint;
+use paho_mqtt as mqtt;
+use serde_json::Value;
+use tokio::time::{Duration, Instant};
+use tonic::Status;
+
+use crate::constants::constraint_type;
+
+/// The largest number of values that are published together.
+pub const MAX_BATCH_SIZE: usize = 1000;
+
+/// Errors for the constraints of a subscription.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConstraintError {
+    /// The constraint type is not supported.
+    UnknownType(String),
+    /// The constraint type is given more than once.
+    Duplicate(String),
+    /// The constraint's value is not valid for its type.
+    InvalidValue {
+        constraint_type: String,
+        value: String,
+        message: String,
+    },
+    /// The constraint only applies to topics that are published on change.
+    RequiresOnChange(String),
+}
+
+impl fmt::Display for ConstraintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstraintError::UnknownType(constraint_type) => {
+                write!(f, "Unknown constraint type '{constraint_type}'")
+            }
+            ConstraintError::Duplicate(constraint_type) => {
+                write!(
+                    f,
+                    "The constraint '{constraint_type}' is given more than once"
+                )
+            }
+            ConstraintError::InvalidValue {
+                constraint_type,
+                value,
+                message,
+            } => write!(
+                f,
+                "Invalid value '{value}' for the constraint '{constraint_type}': {message}"
+            ),
+            ConstraintError::RequiresOnChange(constraint_type) => write!(
+                f,
+                "The constraint '{constraint_type}' requires '{}' to be true",
+                constraint_type::ON_CHANGE
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConstraintError {}
+
+impl From<ConstraintError> for Status {
+    fn from(err: ConstraintError) -> Self {
+        Status::invalid_argument(err.to_string())
+    }
+}
+
+/// When a topic is published.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PublishMode {
+    /// Publish the current value at a fixed rate.
+    Periodic { frequency_ms: u64 },
+    /// Publish the value when it changes.
+    OnChange {
+        deadband: f64,
+        min_interval_ms: u64,
+        max_interval_ms: Option<u64>,
+    },
+}
+
+/// How a topic is published.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PublishPolicy {
+    pub mode: PublishMode,
+    /// The MQTT quality of service of the messages.
+    pub qos: i32,
+    /// The number of values that are published together.
+    pub batch_size: usize,
+    /// The time after which a batch that is not full is published.
+    pub max_age_ms: Option<u64>,
+}
+
+/// Parse the value of a constraint and check it.
+///
+/// # Arguments
+/// * `constraint` - The constraint.
+/// * `is_valid` - Checks the parsed value.
+/// * `expected` - Describes the valid values, used in errors.
+fn parse_value<T>(
+    constraint: &Constraint,
+    is_valid: impl Fn(&T) -> bool,
+    expected: &str,
+) -> Result<T, ConstraintError>
+where
+    T: FromStr,
+{
+    constraint
+        .value
+        .trim()
+        .parse()
+        .ok()
+        .filter(|value| is_valid(value))
+        .ok_or_else(|| ConstraintError::InvalidValue {
+            constraint_type: constraint.r#type.clone(),
+            value: constraint.value.clone(),
+            message: format!("expected {expected}"),
+        })
+}
+
+impl PublishPolicy {
+    /// Create the policy that the constraints of a subscription ask for.
+    ///
+    /// # Arguments
+    /// * `constraints` - The subscription's constraints.
+    /// * `default_interval_ms` - The provider's publish interval, used as the frequency of a
+    ///   periodic topic and the minimum interval of an on change topic unless the constraints set
+    ///   them.
+    pub fn from_constraints(
+        constraints: &[Constraint],
+        default_interval_ms: u64,
+    ) -> Result<Self, ConstraintError> {
+        let mut frequency_ms = None;
+        let mut on_change = None;
+        let mut deadband = None;
+        let mut min_interval_ms = None;
+        let mut max_interval_ms = None;
+        let mut qos = None;
+        let mut batch_size = None;
+        let mut max_age_ms = None;
+
+        let positive = |value: &u64| *value > 0;
+        let milliseconds = "a number of milliseconds greater than 0";
+
+        for constraint in constraints {
+            let is_new = match constraint.r#type.as_str() {
+                constraint_type::FREQUENCY_MS => frequency_ms
+                    .replace(parse_value(constraint, positive, milliseconds)?)
+                    .is_none(),
+                constraint_type::ON_CHANGE => on_change
+                    .replace(parse_value(constraint, |_: &bool| true, "true or false")?)
+                    .is_none(),
+                constraint_type::DEADBAND => deadband
+                    .replace(parse_value(
+                        constraint,
+                        |value: &f64| value.is_finite() && *value >= 0.0,
+                        "a number that is not negative",
+                    )?)
+                    .is_none(),
+                constraint_type::MIN_INTERVAL_MS => min_interval_ms
+                    .replace(parse_value(constraint, positive, milliseconds)?)
+                    .is_none(),
+                constraint_type::MAX_INTERVAL_MS => max_interval_ms
+                    .replace(parse_value(constraint, positive, milliseconds)?)
+                    .is_none(),
+                constraint_type::QOS => qos
+                    .replace(parse_value(
+                        constraint,
+                        |value: &i32| (mqtt::types::QOS_0..=mqtt::types::QOS_2).contains(value),
+                        "0, 1 or 2",
+                    )?)
+                    .is_none(),
+                constraint_type::BATCH_SIZE => batch_size
+                    .replace(parse_value(
+                        constraint,
+                        |value: &usize| (1..=MAX_BATCH_SIZE).contains(value),
+                        &format!("a number from 1 to {MAX_BATCH_SIZE}"),
+                    )?)
+                    .is_none(),
+                constraint_type::MAX_AGE_MS => max_age_ms
+                    .replace(parse_value(constraint, positive, milliseconds)?)
+                    .is_none(),
+                _ => return Err(ConstraintError::UnknownType(constraint.r#type.clone())),
+            };
+
+            if !is_new {
+                return Err(ConstraintError::Duplicate(constraint.r#type.clone()));
+            }
+        }
+
+        let mode = if on_change.unwrap_or(false) {
+            if let Some(frequency_ms) = frequency_ms {
+                return Err(ConstraintError::InvalidValue {
+                    constraint_type: constraint_type::FREQUENCY_MS.to_string(),
+                    value: frequency_ms.to_string(),
+                    message: format!(
+                        "a topic that is published on change uses '{}' instead",
+                        constraint_type::MAX_INTERVAL_MS
+                    ),
+                });
+            }
+
+            let min_interval_ms = min_interval_ms.unwrap_or(default_interval_ms);
+            if let Some(max_interval_ms) =
+                max_interval_ms.filter(|max_interval_ms| *max_interval_ms < min_interval_ms)
+            {
+                return Err(ConstraintError::InvalidValue {
+                    constraint_type: constraint_type::MAX_INTERVAL_MS.to_string(),
+                    value: max_interval_ms.to_string(),
+                    message: format!("expected at least the min_interval_ms {min_interval_ms}"),
+                });
+            }
+
+            PublishMode::OnChange {
+                deadband: deadband.unwrap_or(0.0),
+                min_interval_ms,
+                max_interval_ms,
+            }
+        } else {
+            let on_change_only = [
+                (constraint_type::DEADBAND, deadband.is_some()),
+                (constraint_type::MIN_INTERVAL_MS, min_interval_ms.is_some()),
+                (constraint_type::MAX_INTERVAL_MS, max_interval_ms.is_some()),
+            ];
+            if let Some((name, _)) = on_change_only.iter().find(|(_, is_set)| *is_set) {
+                return Err(ConstraintError::RequiresOnChange(name.to_string()));
+            }
+
+            PublishMode::Periodic {
+                frequency_ms: frequency_ms.unwrap_or(default_interval_ms),
+            }
+        };
+
+        Ok(PublishPolicy {
+            mode,
+            qos: qos.unwrap_or(mqtt::types::QOS_1),
+            batch_size: batch_size.unwrap_or(1),
+            max_age_ms,
+        })
+    }
+}
+
+/// The last value of an on change topic that has been published.
+#[derive(Clone, Debug)]
+pub struct LastPublished {
+    pub value: Value,
+    pub at: Instant,
+}
+
+/// Whether an on change topic publishes a value.
+///
+/// # Arguments
+/// * `last` - The last published value, `None` if nothing has been published yet.
+/// * `value` - The current value.
+/// * `deadband` - The smallest numeric change that is published.
+/// * `max_interval_ms` - The time after which the value is published even if it has not changed.
+pub fn should_publish_change(
+    last: Option<&LastPublished>,
+    value: &Value,
+    deadband: f64,
+    max_interval_ms: Option<u64>,
+) -> bool {
+    let Some(last) = last else {
+        return true;
+    };
+
+    if max_interval_ms
+        .is_some_and(|max_interval_ms| last.at.elapsed() >= Duration::from_millis(max_interval_ms))
+    {
+        return true;
+    }
+
+    match (last.value.as_f64(), value.as_f64()) {
+        (Some(last_number), Some(number)) if deadband > 0.0 => {
+            (number - last_number).abs() >= deadband
+        }
+        _ => last.value != *value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+    use tokio::time::advance;
+
+    const DEFAULT_INTERVAL_MS: u64 = 100;
+    const MAX_INTERVAL_MS: u64 = 1000;
+
+    /// The last published value, published now.
+    ///
+    /// # Arguments
+    /// * `value` - The value.
+    fn published(value: Value) -> LastPublished {
+        LastPublished {
+            value,
+            at: Instant::now(),
+        }
+    }
+
+    /// Create a constraint.
+    ///
+    /// # Arguments
+    /// * `r#type` - The constraint's type.
+    /// * `value` - The constraint's value.
+    fn constraint(r#type: &str, value: &str) -> Constraint {
+        Constraint {
+            r#type: r#type.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    /// Create the policy of the constraints with the default interval.
+    ///
+    /// # Arguments
+    /// * `constraints` - The constraints as (type, value).
+    fn policy(constraints: &[(&str, &str)]) -> Result<PublishPolicy, ConstraintError> {
+        let constraints: Vec<Constraint> = constraints
+            .iter()
+            .map(|(r#type, value)| constraint(r#type, value))
+            .collect();
+        PublishPolicy::from_constraints(&constraints, DEFAULT_INTERVAL_MS)
+    }
+
+    /// Whether the constraints are rejected because of an invalid value of the constraint type.
+    ///
+    /// # Arguments
+    /// * `constraints` - The constraints as (type, value).
+    /// * `invalid_type` - The constraint type with the invalid value.
+    fn is_invalid_value(constraints: &[(&str, &str)], invalid_type: &str) -> bool {
+        matches!(
+            policy(constraints),
+            Err(ConstraintError::InvalidValue { constraint_type, .. })
+                if constraint_type == invalid_type
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_first_value_is_published() {
+        assert!(should_publish_change(None, &json!(1), 0.0, None));
+        assert!(should_publish_change(
+            None,
+            &json!("OPEN"),
+            5.0,
+            Some(MAX_INTERVAL_MS)
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn changes_within_the_deadband_are_not_published() {
+        let last = published(json!(10.0));
+
+        assert!(!should_publish_change(Some(&last), &json!(10.0), 0.5, None));
+        assert!(!should_publish_change(Some(&last), &json!(10.4), 0.5, None));
+        assert!(!should_publish_change(Some(&last), &json!(9.6), 0.5, None));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn changes_of_at_least_the_deadband_are_published() {
+        let last = published(json!(10.0));
+
+        assert!(should_publish_change(Some(&last), &json!(10.5), 0.5, None));
+        assert!(should_publish_change(Some(&last), &json!(9.5), 0.5, None));
+        assert!(should_publish_change(Some(&last), &json!(12), 0.5, None));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn without_a_deadband_every_change_is_published() {
+        let last = published(json!(10));
+
+        assert!(!should_publish_change(Some(&last), &json!(10), 0.0, None));
+        assert!(should_publish_change(
+            Some(&last),
+            &json!(10.001),
+            0.0,
+            None
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn values_that_are_not_numbers_ignore_the_deadband() {
+        let last = published(json!("OPEN"));
+
+        assert!(!should_publish_change(
+            Some(&last),
+            &json!("OPEN"),
+            0.5,
+            None
+        ));
+        assert!(should_publish_change(
+            Some(&last),
+            &json!("HOLD"),
+            0.5,
+            None
+        ));
+        assert!(should_publish_change(Some(&last), &json!(1), 0.5, None));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn an_unchanged_value_is_published_again_after_the_max_interval() {
+        let last = published(json!(10));
+
+        advance(Duration::from_millis(MAX_INTERVAL_MS - 1)).await;
+        assert!(!should_publish_change(
+            Some(&last),
+            &json!(10),
+            0.0,
+            Some(MAX_INTERVAL_MS)
+        ));
+
+        advance(Duration::from_millis(1)).await;
+        assert!(should_publish_change(
+            Some(&last),
+            &json!(10),
+            0.0,
+            Some(MAX_INTERVAL_MS)
+        ));
+
+        // The max interval overrides the deadband.
+        assert!(should_publish_change(
+            Some(&last),
+            &json!(10.1),
+            0.5,
+            Some(MAX_INTERVAL_MS)
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn an_unchanged_value_is_not_published_again_without_a_max_interval() {
+        let last = published(json!(10));
+
+        advance(Duration::from_secs(3600)).await;
+        assert!(!should_publish_change(Some(&last), &json!(10), 0.0, None));
+    }
+
+    #[test]
+    fn without_constraints_the_topic_is_published_periodically() {
+        assert_eq!(
+            policy(&[]),
+            Ok(PublishPolicy {
+                mode: PublishMode::Periodic {
+                    frequency_ms: DEFAULT_INTERVAL_MS
+                },
+                qos: mqtt::types::QOS_1,
+                batch_size: 1,
+                max_age_ms: None,
+            })
+        );
+    }
+
+    #[test]
+    fn the_constraints_are_applied() {
+        assert_eq!(
+            policy(&[
+                (constraint_type::ON_CHANGE, "true"),
+                (constraint_type::DEADBAND, "0.5"),
+                (constraint_type::MIN_INTERVAL_MS, "200"),
+                (constraint_type::MAX_INTERVAL_MS, "5000"),
+                (constraint_type::QOS, "2"),
+                (constraint_type::BATCH_SIZE, "10"),
+                (constraint_type::MAX_AGE_MS, "1000"),
+            ]),
+            Ok(PublishPolicy {
+                mode: PublishMode::OnChange {
+                    deadband: 0.5,
+                    min_interval_ms: 200,
+                    max_interval_ms: Some(5000),
+                },
+                qos: mqtt::types::QOS_2,
+                batch_size: 10,
+                max_age_ms: Some(1000),
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_constraint_types_are_rejected() {
+        assert_eq!(
+            policy(&[("frequency", "100")]),
+            Err(ConstraintError::UnknownType("frequency".to_string()))
+        );
+        assert_eq!(
+            policy(&[("", "")]),
+            Err(ConstraintError::UnknownType(String::new()))
+        );
+    }
+
+    #[test]
+    fn duplicate_constraint_types_are_rejected() {
+        assert_eq!(
+            policy(&[(constraint_type::QOS, "1"), (constraint_type::QOS, "1"),]),
+            Err(ConstraintError::Duplicate(constraint_type::QOS.to_string()))
+        );
+        assert_eq!(
+            policy(&[
+                (constraint_type::ON_CHANGE, "true"),
+                (constraint_type::ON_CHANGE, "false"),
+            ]),
+            Err(ConstraintError::Duplicate(
+                constraint_type::ON_CHANGE.to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn qos_must_be_0_1_or_2() {
+        for qos in ["0", "1", "2"] {
+            assert!(policy(&[(constraint_type::QOS, qos)]).is_ok(), "{qos}");
+        }
+        for qos in ["3", "-1", "one"] {
+            assert!(
+                is_invalid_value(&[(constraint_type::QOS, qos)], constraint_type::QOS),
+                "{qos}"
+            );
+        }
+    }
+
+    #[test]
+    fn batch_size_must_be_from_1_to_the_max() {
+        let max = MAX_BATCH_SIZE.to_string();
+        let over_max = (MAX_BATCH_SIZE + 1).to_string();
+
+        for batch_size in ["1", max.as_str()] {
+            assert!(policy(&[(constraint_type::BATCH_SIZE, batch_size)]).is_ok());
+        }
+        for batch_size in ["0", over_max.as_str(), "-1"] {
+            assert!(
+                is_invalid_value(
+                    &[(constraint_type::BATCH_SIZE, batch_size)],
+                    constraint_type::BATCH_SIZE
+                ),
+                "{batch_size}"
+            );
+        }
+    }
+
+    #[test]
+    fn intervals_must_be_greater_than_0() {
+        for name in [constraint_type::FREQUENCY_MS, constraint_type::MAX_AGE_MS] {
+            assert!(is_invalid_value(&[(name, "0")], name));
+        }
+        for name in [
+            constraint_type::MIN_INTERVAL_MS,
+            constraint_type::MAX_INTERVAL_MS,
+        ] {
+            assert!(is_invalid_value(
+                &[(constraint_type::ON_CHANGE, "true"), (name, "0")],
+                name
+            ));
+        }
+    }
+
+    #[test]
+    fn the_deadband_must_not_be_negative() {
+        for deadband in ["-0.1", "NaN", "inf"] {
+            assert!(
+                is_invalid_value(
+                    &[
+                        (constraint_type::ON_CHANGE, "true"),
+                        (constraint_type::DEADBAND, deadband)
+                    ],
+                    constraint_type::DEADBAND
+                ),
+                "{deadband}"
+            );
+        }
+    }
+
+    #[test]
+    fn on_change_only_constraints_require_on_change() {
+        for name in [
+            constraint_type::DEADBAND,
+            constraint_type::MIN_INTERVAL_MS,
+            constraint_type::MAX_INTERVAL_MS,
+        ] {
+            let expected = Err(ConstraintError::RequiresOnChange(name.to_string()));
+
+            assert_eq!(policy(&[(name, "1")]), expected);
+            assert_eq!(
+                policy(&[(constraint_type::ON_CHANGE, "false"), (name, "1")]),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn on_change_topics_have_no_frequency() {
+        assert!(is_invalid_value(
+            &[
+                (constraint_type::ON_CHANGE, "true"),
+                (constraint_type::FREQUENCY_MS, "100")
+            ],
+            constraint_type::FREQUENCY_MS
+        ));
+    }
+
+    #[test]
+    fn the_max_interval_must_not_be_less_than_the_min_interval() {
+        assert!(is_invalid_value(
+            &[
+                (constraint_type::ON_CHANGE, "true"),
+                (constraint_type::MIN_INTERVAL_MS, "500"),
+                (constraint_type::MAX_INTERVAL_MS, "400"),
+            ],
+            constraint_type::MAX_INTERVAL_MS
+        ));
+    }
+}
@@ -0,0 +1,332 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! Retries with exponential backoff and jitter, used for service discovery, FindById and
+//! Register so that it does not matter in which order the workloads are started.
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+
+use log::{info, warn};
+use tokio::signal;
+use tokio::time::{sleep, Duration, Instant};
+
+/// Policy for retrying an operation.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// The delay before the first retry.
+    pub initial_delay: Duration,
+    /// The upper bound for the delay between two attempts.
+    pub max_delay: Duration,
+    /// The factor by which the delay grows after each attempt.
+    pub multiplier: f64,
+    /// The fraction of the delay that is randomly added or subtracted, between 0 and 1.
+    pub jitter: f64,
+    /// The maximum number of attempts, unlimited if `None`.
+    pub max_attempts: Option<u32>,
+    /// The time after which no further attempt is started, unlimited if `None`.
+    pub deadline: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: 0.2,
+            max_attempts: None,
+            deadline: Some(Duration::from_secs(300)),
+        }
+    }
+}
+
+/// Errors for a retried operation.
+#[derive(Debug)]
+pub enum RetryError<E> {
+    /// Ctrl-C was received while retrying.
+    Cancelled,
+    /// The maximum number of attempts was reached.
+    Exhausted { attempts: u32, last_error: E },
+    /// The deadline was reached.
+    DeadlineExceeded { attempts: u32, last_error: E },
+}
+
+impl<E: fmt::Display> fmt::Display for RetryError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetryError::Cancelled => write!(f, "Cancelled while retrying"),
+            RetryError::Exhausted {
+                attempts,
+                last_error,
+            } => write!(
+                f,
+                "Gave up after {attempts} attempts, last error: {last_error}"
+            ),
+            RetryError::DeadlineExceeded {
+                attempts,
+                last_error,
+            } => write!(
+                f,
+                "Reached the deadline after {attempts} attempts, last error: {last_error}"
+            ),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for RetryError<E> {}
+
+impl RetryPolicy {
+    /// Get the delay before the next attempt with jitter applied.
+    ///
+    /// # Arguments
+    /// * `delay` - The delay without jitter.
+    fn with_jitter(&self, delay: Duration) -> Duration {
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return delay;
+        }
+
+        // RandomState is seeded randomly, which is good enough to spread out the retries.
+        let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        let factor = 1.0 + jitter * (2.0 * random - 1.0);
+
+        delay.mul_f64(factor)
+    }
+
+    /// Run an operation until it succeeds, retrying according to the policy.
+    ///
+    /// # Arguments
+    /// * `description` - The description of the operation, used for logging.
+    /// * `operation` - The operation to run.
+    pub async fn retry<T, E, F, Fut>(
+        &self,
+        description: &str,
+        mut operation: F,
+    ) -> Result<T, RetryError<E>>
+    where
+        E: fmt::Display,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let start = Instant::now();
+        let mut delay = self.initial_delay;
+        let mut attempts: u32 = 0;
+
+        loop {
+            attempts += 1;
+
+            let result = tokio::select! {
+                _ = signal::ctrl_c() => return Err(RetryError::Cancelled),
+                result = operation() => result,
+            };
+
+            let last_error = match result {
+                Ok(value) => return Ok(value),
+                Err(err) => err,
+            };
+
+            if self
+                .max_attempts
+                .is_some_and(|max_attempts| attempts >= max_attempts)
+            {
+                warn!("Failed to {description} after {attempts} attempts: {last_error}");
+                return Err(RetryError::Exhausted {
+                    attempts,
+                    last_error,
+                });
+            }
+
+            let next_delay = self.with_jitter(delay);
+            if self
+                .deadline
+                .is_some_and(|deadline| start.elapsed() + next_delay > deadline)
+            {
+                warn!("Failed to {description} before the deadline: {last_error}");
+                return Err(RetryError::DeadlineExceeded {
+                    attempts,
+                    last_error,
+                });
+            }
+
+            info!("Failed to {description} due to '{last_error}', retrying in {next_delay:?}.");
+
+            tokio::select! {
+                _ = signal::ctrl_c() => return Err(RetryError::Cancelled),
+                _ = sleep(next_delay) => {},
+            }
+
+            delay = delay.mul_f64(self.multiplier).min(self.max_delay);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+
+    /// A policy without jitter, deadline or attempt limit.
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+            multiplier: 2.0,
+            jitter: 0.0,
+            max_attempts: None,
+            deadline: None,
+        }
+    }
+
+    /// Retry an operation that fails a number of times and return the result and the times of
+    /// the attempts since the start.
+    ///
+    /// # Arguments
+    /// * `policy` - The policy.
+    /// * `failures` - The number of times that the operation fails before it succeeds.
+    async fn retry_failing(
+        policy: &RetryPolicy,
+        failures: u32,
+    ) -> (Result<u32, RetryError<String>>, Vec<Duration>) {
+        let start = Instant::now();
+        let attempts = RefCell::new(Vec::new());
+
+        let result = policy
+            .retry("test", || {
+                let mut attempts = attempts.borrow_mut();
+                attempts.push(start.elapsed());
+                let attempt = attempts.len() as u32;
+
+                async move {
+                    if attempt > failures {
+                        Ok(attempt)
+                    } else {
+                        Err(format!("attempt {attempt} failed"))
+                    }
+                }
+            })
+            .await;
+
+        (result, attempts.into_inner())
+    }
+
+    /// The delays between the attempts.
+    ///
+    /// # Arguments
+    /// * `attempts` - The times of the attempts.
+    fn delays(attempts: &[Duration]) -> Vec<Duration> {
+        attempts.windows(2).map(|pair| pair[1] - pair[0]).collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_successful_operation_is_not_retried() {
+        let (result, attempts) = retry_failing(&policy(), 0).await;
+
+        assert_eq!(result.unwrap(), 1);
+        assert_eq!(attempts, [Duration::ZERO]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_delay_grows_up_to_the_max_delay() {
+        let (result, attempts) = retry_failing(&policy(), 6).await;
+
+        assert_eq!(result.unwrap(), 7);
+        assert_eq!(
+            delays(&attempts),
+            [100, 200, 400, 800, 1000, 1000].map(Duration::from_millis)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_jitter_stays_within_its_fraction_of_the_delay() {
+        let policy = RetryPolicy {
+            jitter: 0.2,
+            ..policy()
+        };
+
+        let (result, attempts) = retry_failing(&policy, 6).await;
+        assert!(result.is_ok());
+
+        let expected = [100, 200, 400, 800, 1000, 1000].map(Duration::from_millis);
+        for (delay, expected) in delays(&attempts).into_iter().zip(expected) {
+            assert!(delay >= expected.mul_f64(0.8), "{delay:?}");
+            assert!(delay <= expected.mul_f64(1.2), "{delay:?}");
+        }
+    }
+
+    #[test]
+    fn the_jitter_is_applied_within_its_bounds() {
+        let delay = Duration::from_secs(1);
+
+        let without_jitter = policy();
+        assert_eq!(without_jitter.with_jitter(delay), delay);
+
+        let with_jitter = RetryPolicy {
+            jitter: 0.5,
+            ..policy()
+        };
+        let jittered: Vec<Duration> = (0..1000).map(|_| with_jitter.with_jitter(delay)).collect();
+        assert!(jittered
+            .iter()
+            .all(|jittered| (delay / 2..=delay * 3 / 2).contains(jittered)));
+        assert!(jittered.iter().any(|jittered| *jittered != delay));
+
+        // A jitter above 1 is clamped, so that the delay never becomes negative.
+        let excessive_jitter = RetryPolicy {
+            jitter: 3.0,
+            ..policy()
+        };
+        assert!((0..1000)
+            .map(|_| excessive_jitter.with_jitter(delay))
+            .all(|jittered| jittered <= delay * 2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn no_attempt_is_started_after_the_deadline() {
+        let policy = RetryPolicy {
+            deadline: Some(Duration::from_millis(1000)),
+            ..policy()
+        };
+
+        let (result, attempts) = retry_failing(&policy, u32::MAX).await;
+
+        // The attempt after 700 ms would start after 1500 ms.
+        assert_eq!(attempts, [0, 100, 300, 700].map(Duration::from_millis));
+        match result {
+            Err(RetryError::DeadlineExceeded {
+                attempts,
+                last_error,
+            }) => {
+                assert_eq!(attempts, 4);
+                assert_eq!(last_error, "attempt 4 failed");
+            }
+            other => panic!("The deadline should be exceeded, got {other:?}"),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_attempts_are_limited() {
+        let policy = RetryPolicy {
+            max_attempts: Some(3),
+            ..policy()
+        };
+
+        let (result, attempts) = retry_failing(&policy, u32::MAX).await;
+
+        assert_eq!(attempts.len(), 3);
+        match result {
+            Err(RetryError::Exhausted {
+                attempts,
+                last_error,
+            }) => {
+                assert_eq!(attempts, 3);
+                assert_eq!(last_error, "attempt 3 failed");
+            }
+            other => panic!("The attempts should be exhausted, got {other:?}"),
+        }
+    }
+}
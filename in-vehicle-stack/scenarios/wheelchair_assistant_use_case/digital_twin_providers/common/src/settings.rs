@@ -0,0 +1,272 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! Settings for the providers and applications.
+//!
+//! Settings are layered: the defaults of the binary, then the optional file
+//! "{config_path}/{name}_settings.yaml", then environment variables prefixed with
+//! "WHEELCHAIR_", e.g. `WHEELCHAIR_CHARIOTT_URI`. A double underscore separates the keys of nested
+//! settings, e.g. `WHEELCHAIR_PROXIMITY__NEAR_ENTER_CM`. The config path is read from
+//! `WHEELCHAIR_CONFIG_PATH` and defaults to "/mnt/config", where Ankaios workloads mount it.
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+
+use config::{Config, Environment, File, FileFormat};
+use log::{debug, info};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tonic::transport::Uri;
+
+use crate::retry::RetryPolicy;
+
+pub const CONFIG_PATH_ENV_VAR: &str = "WHEELCHAIR_CONFIG_PATH";
+pub const DEFAULT_CONFIG_PATH: &str = "/mnt/config";
+pub const ENV_PREFIX: &str = "WHEELCHAIR";
+
+/// Errors for loading settings.
+#[derive(Debug)]
+pub enum SettingsError {
+    /// The settings could not be read or deserialized.
+    Load(String),
+    /// A setting has an invalid value.
+    Invalid { field: String, message: String },
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsError::Load(message) => write!(f, "Unable to load the settings: {message}"),
+            SettingsError::Invalid { field, message } => {
+                write!(f, "Invalid setting '{field}': {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+impl From<config::ConfigError> for SettingsError {
+    fn from(error: config::ConfigError) -> Self {
+        SettingsError::Load(error.to_string())
+    }
+}
+
+/// Settings that can check their values after loading.
+pub trait ValidateSettings {
+    /// Validate the settings.
+    fn validate(&self) -> Result<(), SettingsError>;
+}
+
+/// Settings for retrying service discovery, FindById and Register, see [`RetryPolicy`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetrySettings {
+    /// The delay before the first retry.
+    pub initial_delay_ms: u64,
+    /// The upper bound for the delay between two attempts.
+    pub max_delay_ms: u64,
+    /// The factor by which the delay grows after each attempt.
+    pub multiplier: f64,
+    /// The fraction of the delay that is randomly added or subtracted, between 0 and 1.
+    pub jitter: f64,
+    /// The maximum number of attempts, unlimited if not set.
+    pub max_attempts: Option<u32>,
+    /// The time after which no further attempt is started, unlimited if not set.
+    pub deadline_ms: Option<u64>,
+}
+
+impl Default for RetrySettings {
+    fn default() -> Self {
+        let policy = RetryPolicy::default();
+
+        RetrySettings {
+            initial_delay_ms: policy.initial_delay.as_millis() as u64,
+            max_delay_ms: policy.max_delay.as_millis() as u64,
+            multiplier: policy.multiplier,
+            jitter: policy.jitter,
+            max_attempts: policy.max_attempts,
+            deadline_ms: policy.deadline.map(|deadline| deadline.as_millis() as u64),
+        }
+    }
+}
+
+impl ValidateSettings for RetrySettings {
+    fn validate(&self) -> Result<(), SettingsError> {
+        validate_non_zero("retry.initial_delay_ms", self.initial_delay_ms)?;
+
+        if self.max_delay_ms < self.initial_delay_ms {
+            return Err(SettingsError::Invalid {
+                field: "retry.max_delay_ms".to_string(),
+                message: format!(
+                    "must not be less than retry.initial_delay_ms {}",
+                    self.initial_delay_ms
+                ),
+            });
+        }
+
+        if !(self.multiplier.is_finite() && self.multiplier >= 1.0) {
+            return Err(SettingsError::Invalid {
+                field: "retry.multiplier".to_string(),
+                message: "must be at least 1".to_string(),
+            });
+        }
+
+        if !(0.0..=1.0).contains(&self.jitter) {
+            return Err(SettingsError::Invalid {
+                field: "retry.jitter".to_string(),
+                message: "must be between 0 and 1".to_string(),
+            });
+        }
+
+        if let Some(max_attempts) = self.max_attempts {
+            validate_non_zero("retry.max_attempts", u64::from(max_attempts))?;
+        }
+
+        if let Some(deadline_ms) = self.deadline_ms {
+            validate_non_zero("retry.deadline_ms", deadline_ms)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl From<&RetrySettings> for RetryPolicy {
+    fn from(settings: &RetrySettings) -> Self {
+        RetryPolicy {
+            initial_delay: Duration::from_millis(settings.initial_delay_ms),
+            max_delay: Duration::from_millis(settings.max_delay_ms),
+            multiplier: settings.multiplier,
+            jitter: settings.jitter,
+            max_attempts: settings.max_attempts,
+            deadline: settings.deadline_ms.map(Duration::from_millis),
+        }
+    }
+}
+
+/// Settings shared by every provider.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProviderSettings {
+    /// Chariott's Service Discovery URI.
+    pub chariott_uri: String,
+    /// The authority that the provider listens on.
+    pub provider_authority: String,
+    /// How discovery and registration are retried.
+    #[serde(default)]
+    pub retry: RetrySettings,
+}
+
+impl ValidateSettings for ProviderSettings {
+    fn validate(&self) -> Result<(), SettingsError> {
+        validate_uri("chariott_uri", &self.chariott_uri)?;
+        validate_authority("provider_authority", &self.provider_authority)?;
+        self.retry.validate()
+    }
+}
+
+/// Settings shared by every managed subscribe provider.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ManagedSubscribeProviderSettings {
+    #[serde(flatten)]
+    pub provider: ProviderSettings,
+    /// The default publish interval if the subscriber does not request one.
+    pub min_interval_ms: u64,
+}
+
+impl ValidateSettings for ManagedSubscribeProviderSettings {
+    fn validate(&self) -> Result<(), SettingsError> {
+        self.provider.validate()?;
+        validate_non_zero("min_interval_ms", self.min_interval_ms)
+    }
+}
+
+/// Check that a setting is an absolute URI.
+///
+/// # Arguments
+/// * `field` - The setting's name.
+/// * `value` - The setting's value.
+pub fn validate_uri(field: &str, value: &str) -> Result<(), SettingsError> {
+    let uri: Uri = value.parse().map_err(|err| SettingsError::Invalid {
+        field: field.to_string(),
+        message: format!("'{value}' is not a valid URI: {err}"),
+    })?;
+
+    if uri.scheme().is_none() || uri.authority().is_none() {
+        return Err(SettingsError::Invalid {
+            field: field.to_string(),
+            message: format!("'{value}' is not an absolute URI"),
+        });
+    }
+
+    Ok(())
+}
+
+/// Check that a setting is an authority the provider can listen on, e.g. "0.0.0.0:4010".
+///
+/// # Arguments
+/// * `field` - The setting's name.
+/// * `value` - The setting's value.
+pub fn validate_authority(field: &str, value: &str) -> Result<(), SettingsError> {
+    value
+        .parse::<SocketAddr>()
+        .map(|_| ())
+        .map_err(|err| SettingsError::Invalid {
+            field: field.to_string(),
+            message: format!("'{value}' is not a valid socket address: {err}"),
+        })
+}
+
+/// Check that a setting is greater than zero.
+///
+/// # Arguments
+/// * `field` - The setting's name.
+/// * `value` - The setting's value.
+pub fn validate_non_zero(field: &str, value: u64) -> Result<(), SettingsError> {
+    if value == 0 {
+        return Err(SettingsError::Invalid {
+            field: field.to_string(),
+            message: "must be greater than 0".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Load and validate the settings of a binary.
+///
+/// # Arguments
+/// * `name` - The binary's name, used for the settings file name.
+/// * `defaults` - The settings that are used if neither the file nor the environment set them.
+pub fn load_settings<T>(name: &str, defaults: &T) -> Result<T, SettingsError>
+where
+    T: Serialize + DeserializeOwned + ValidateSettings,
+{
+    let config_path =
+        std::env::var(CONFIG_PATH_ENV_VAR).unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
+    let config_file = Path::new(&config_path).join(format!("{name}_settings.yaml"));
+    debug!("Loading the settings from {config_file:?}");
+
+    let settings: T = Config::builder()
+        .add_source(Config::try_from(defaults)?)
+        .add_source(
+            File::from(config_file.as_path())
+                .format(FileFormat::Yaml)
+                .required(false),
+        )
+        .add_source(
+            Environment::with_prefix(ENV_PREFIX)
+                .prefix_separator("_")
+                .separator("__")
+                .try_parsing(true),
+        )
+        .build()?
+        .try_deserialize()?;
+
+    settings.validate()?;
+    info!("Loaded the settings for {name}");
+
+    Ok(settings)
+}
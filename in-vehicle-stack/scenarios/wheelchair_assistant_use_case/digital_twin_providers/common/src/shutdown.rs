@@ -0,0 +1,222 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! Coordinated shutdown of a provider.
+//!
+//! Control-c or SIGTERM, which is how Ankaios stops a workload, triggers the shutdown. The provider
+//! then stops its gRPC server, stops publishing to its topics, disconnects from the MQTT brokers
+//! and deregisters its entities from the In-Vehicle Digital Twin, in that order.
+//!
+//! The In-Vehicle Digital Twin has no unregister operation. An entity is deregistered by
+//! registering it again without the provider's endpoints, which replaces the registered entity.
+//! Consumers then find no endpoint for it until a provider registers it again.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use interfaces::invehicle_digital_twin::v1::invehicle_digital_twin_client::InvehicleDigitalTwinClient;
+use interfaces::invehicle_digital_twin::v1::{
+    EndpointInfo, EntityAccessInfo, FindByIdRequest, RegisterRequest,
+};
+use log::{debug, info, warn};
+use tokio::signal;
+use tokio::sync::watch;
+use tokio::time::Duration;
+use tonic::{Code, Status};
+
+use crate::constants::digital_twin_operation;
+
+/// How long a provider waits for its publishing threads to stop.
+pub const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Triggers the shutdown of a provider and lets its parts wait for it.
+#[derive(Clone, Debug)]
+pub struct Shutdown {
+    sender: Arc<watch::Sender<bool>>,
+    receiver: watch::Receiver<bool>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    /// Create a shutdown that is only triggered by calling `trigger`.
+    pub fn new() -> Self {
+        let (sender, receiver) = watch::channel(false);
+
+        Shutdown {
+            sender: Arc::new(sender),
+            receiver,
+        }
+    }
+
+    /// Create a shutdown that is also triggered by control-c or SIGTERM.
+    pub fn on_signals() -> Self {
+        let shutdown = Self::new();
+        let trigger = shutdown.clone();
+
+        tokio::spawn(async move {
+            wait_for_signal().await;
+            trigger.trigger();
+        });
+
+        shutdown
+    }
+
+    /// Trigger the shutdown.
+    pub fn trigger(&self) {
+        self.sender.send_replace(true);
+    }
+
+    /// Whether the shutdown has been triggered.
+    pub fn is_triggered(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// Wait until the shutdown is triggered.
+    pub async fn wait(&self) {
+        let mut receiver = self.receiver.clone();
+
+        // The sender lives as long as this shutdown, so waiting does not fail.
+        _ = receiver.wait_for(|is_triggered| *is_triggered).await;
+    }
+
+    /// A future that completes when the shutdown is triggered, e.g. for a gRPC server's
+    /// `serve_with_shutdown`.
+    pub fn signal(&self) -> impl Future<Output = ()> + Send + 'static {
+        let shutdown = self.clone();
+
+        async move { shutdown.wait().await }
+    }
+
+    /// Run a future until it completes or the shutdown is triggered, whichever is first.
+    ///
+    /// # Arguments
+    /// * `future` - The future, `None` is returned if the shutdown is triggered first.
+    pub async fn run_until<F: Future>(&self, future: F) -> Option<F::Output> {
+        tokio::select! {
+            output = future => Some(output),
+            _ = self.wait() => None,
+        }
+    }
+}
+
+/// Wait for control-c or, on Unix, SIGTERM.
+async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    result = signal::ctrl_c() => log_ctrl_c(result),
+                    _ = terminate.recv() => info!("Received SIGTERM, shutting down."),
+                }
+            }
+            Err(err) => {
+                warn!("Unable to listen for SIGTERM due to '{err}'");
+                log_ctrl_c(signal::ctrl_c().await);
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    log_ctrl_c(signal::ctrl_c().await);
+}
+
+/// Log the result of waiting for control-c.
+///
+/// # Arguments
+/// * `result` - The result of waiting for control-c.
+fn log_ctrl_c(result: std::io::Result<()>) {
+    match result {
+        Ok(()) => info!("Received control-c, shutting down."),
+        Err(err) => warn!("Unable to listen for control-c due to '{err}', shutting down."),
+    }
+}
+
+/// Is an endpoint that the In-Vehicle Digital Twin returns one that the provider has registered?
+///
+/// # Arguments
+/// * `registered` - The endpoint as the provider has registered it.
+/// * `endpoint` - The endpoint as the In-Vehicle Digital Twin returns it.
+fn is_registered_endpoint(registered: &EndpointInfo, endpoint: &EndpointInfo) -> bool {
+    // Ibeji replaces the URI of managed subscribe endpoints with its Managed Subscribe module's, their
+    // context identifies the provider instead.
+    let is_managed_subscribe = registered
+        .operations
+        .iter()
+        .any(|operation| operation == digital_twin_operation::MANAGEDSUBSCRIBE);
+
+    registered.protocol == endpoint.protocol
+        && registered.operations == endpoint.operations
+        && registered.context == endpoint.context
+        && (is_managed_subscribe || registered.uri == endpoint.uri)
+}
+
+/// Deregister entities from the In-Vehicle Digital Twin by registering them again without the
+/// provider's endpoints. An entity that another provider has registered since is left as it is.
+///
+/// # Arguments
+/// * `invehicle_digital_twin_uri` - The In-Vehicle Digital Twin URI.
+/// * `entity_access_info_list` - The entities as the provider has registered them.
+pub async fn deregister_entities(
+    invehicle_digital_twin_uri: &str,
+    entity_access_info_list: &[EntityAccessInfo],
+) -> Result<(), Status> {
+    let mut client = InvehicleDigitalTwinClient::connect(invehicle_digital_twin_uri.to_string())
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    let mut deregistered_list = Vec::new();
+    for registered in entity_access_info_list {
+        let request = tonic::Request::new(FindByIdRequest {
+            id: registered.id.clone(),
+        });
+        let current = match client.find_by_id(request).await {
+            Ok(response) => response.into_inner().entity_access_info,
+            Err(status) if status.code() == Code::NotFound => None,
+            Err(status) => return Err(status),
+        };
+
+        let Some(mut current) = current else {
+            debug!(
+                "The entity {} is not registered, nothing to deregister.",
+                registered.id
+            );
+            continue;
+        };
+
+        let endpoint_count = current.endpoint_info_list.len();
+        current.endpoint_info_list.retain(|endpoint| {
+            !registered
+                .endpoint_info_list
+                .iter()
+                .any(|registered_endpoint| is_registered_endpoint(registered_endpoint, endpoint))
+        });
+
+        if current.endpoint_info_list.len() == endpoint_count {
+            debug!(
+                "The entity {} is registered by another provider.",
+                registered.id
+            );
+            continue;
+        }
+
+        deregistered_list.push(current);
+    }
+
+    if deregistered_list.is_empty() {
+        return Ok(());
+    }
+
+    let request = tonic::Request::new(RegisterRequest {
+        entity_access_info_list: deregistered_list,
+    });
+    let _response = client.register(request).await?;
+
+    Ok(())
+}
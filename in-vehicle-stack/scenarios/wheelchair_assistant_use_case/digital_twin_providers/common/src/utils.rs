@@ -3,21 +3,209 @@
 // SPDX-License-Identifier: MIT
 
 use interfaces::chariott::service_discovery::core::v1::service_registry_client::ServiceRegistryClient;
-use interfaces::chariott::service_discovery::core::v1::DiscoverRequest;
+use interfaces::chariott::service_discovery::core::v1::{
+    DiscoverByNamespaceRequest, DiscoverRequest, ServiceMetadata,
+};
 use interfaces::invehicle_digital_twin::v1::invehicle_digital_twin_client::InvehicleDigitalTwinClient;
 use interfaces::invehicle_digital_twin::v1::{EndpointInfo, FindByIdRequest};
-use log::{debug, info};
-use tonic::{Request, Status};
+use log::{debug, info, warn};
+use std::fmt;
+use tonic::{Code, Request, Status};
+
+/// How strictly a discovered service's protocol has to match the requested one.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ProtocolMatching {
+    /// Both the communication kind and the communication reference have to match.
+    #[default]
+    Strict,
+    /// Only the communication kind has to match, a different communication reference is logged.
+    Relaxed,
+}
+
+impl ProtocolMatching {
+    /// Does the service have the requested protocol?
+    ///
+    /// # Arguments
+    /// * `service` - The discovered service.
+    /// * `communication_kind` - The requested communication kind.
+    /// * `communication_reference` - The requested communication reference.
+    fn matches(
+        self,
+        service: &ServiceMetadata,
+        communication_kind: &str,
+        communication_reference: &str,
+    ) -> bool {
+        if service.communication_kind != communication_kind {
+            return false;
+        }
+
+        if service.communication_reference == communication_reference {
+            return true;
+        }
+
+        match self {
+            ProtocolMatching::Strict => false,
+            ProtocolMatching::Relaxed => {
+                warn!(
+                    "Accepting service '{}' version {} with communication reference '{}' instead of '{communication_reference}'",
+                    service.name, service.version, service.communication_reference
+                );
+                true
+            }
+        }
+    }
+}
+
+/// Parse a "major.minor[.patch]" version.
+///
+/// # Arguments
+/// * `version` - The version to parse.
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = version
+        .trim()
+        .split('.')
+        .map(|part| part.parse::<u64>().ok());
+
+    let major = parts.next()??;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+
+    if parts.next().is_some() {
+        return None;
+    }
+
+    Some((major, minor, patch))
+}
+
+/// Is the available version compatible with the requested version? A version is compatible if it
+/// has the same major version and is not older, e.g. "1.0" accepts "1.0" and "1.2" but not "2.0".
+/// Versions that cannot be parsed have to be equal.
+///
+/// # Arguments
+/// * `requested` - The requested version.
+/// * `available` - The available version.
+pub fn is_version_compatible(requested: &str, available: &str) -> bool {
+    match (parse_version(requested), parse_version(available)) {
+        (Some(requested), Some(available)) => requested.0 == available.0 && available >= requested,
+        _ => requested == available,
+    }
+}
+
+/// Errors for discovering services and digital twin providers.
+#[derive(Debug)]
+pub enum DiscoveryError {
+    /// Unable to connect to the service at the URI.
+    Connect { uri: String, message: String },
+    /// The service rejected the request.
+    Request { uri: String, status: Status },
+    /// Chariott does not know the service.
+    ServiceNotFound {
+        namespace: String,
+        name: String,
+        version: String,
+    },
+    /// The service does not have the required communication kind and reference.
+    ProtocolMismatch {
+        namespace: String,
+        name: String,
+        version: String,
+        expected_communication_kind: String,
+        expected_communication_reference: String,
+        communication_kind: String,
+        communication_reference: String,
+    },
+    /// Ibeji does not know the entity.
+    EntityNotFound { entity_id: String },
+    /// No endpoint of the entity has the required protocol and operations.
+    MissingOperations {
+        entity_id: String,
+        protocol: String,
+        operations: Vec<String>,
+    },
+    /// The discovered URI could not be resolved.
+    InvalidUri { uri: String, status: Status },
+}
+
+impl fmt::Display for DiscoveryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiscoveryError::Connect { uri, message } => {
+                write!(f, "Unable to connect to '{uri}' due to: {message}")
+            }
+            DiscoveryError::Request { uri, status } => {
+                write!(f, "The request to '{uri}' failed with: {status}")
+            }
+            DiscoveryError::ServiceNotFound {
+                namespace,
+                name,
+                version,
+            } => write!(
+                f,
+                "Did not find a service in Chariott with namespace '{namespace}', name '{name}' and version {version}"
+            ),
+            DiscoveryError::ProtocolMismatch {
+                namespace,
+                name,
+                version,
+                expected_communication_kind,
+                expected_communication_reference,
+                communication_kind,
+                communication_reference,
+            } => write!(
+                f,
+                "Did not find a service in Chariott with namespace '{namespace}', name '{name}' and version {version} that has communication kind '{expected_communication_kind}' and communication reference '{expected_communication_reference}', found communication kind '{communication_kind}' and communication reference '{communication_reference}'"
+            ),
+            DiscoveryError::EntityNotFound { entity_id } => {
+                write!(f, "Did not find the entity '{entity_id}'")
+            }
+            DiscoveryError::MissingOperations {
+                entity_id,
+                protocol,
+                operations,
+            } => write!(
+                f,
+                "Did not find an endpoint for entity '{entity_id}' with protocol '{protocol}' and operations {operations:?}"
+            ),
+            DiscoveryError::InvalidUri { uri, status } => {
+                write!(f, "Failed to get the URI for '{uri}' due to: {status}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DiscoveryError {}
+
+impl From<DiscoveryError> for Status {
+    fn from(error: DiscoveryError) -> Self {
+        let code = match &error {
+            DiscoveryError::Connect { .. } => Code::Unavailable,
+            DiscoveryError::Request { status, .. } => status.code(),
+            DiscoveryError::ServiceNotFound { .. } | DiscoveryError::EntityNotFound { .. } => {
+                Code::NotFound
+            }
+            DiscoveryError::ProtocolMismatch { .. } | DiscoveryError::MissingOperations { .. } => {
+                Code::FailedPrecondition
+            }
+            DiscoveryError::InvalidUri { status, .. } => status.code(),
+        };
+
+        Status::new(code, error.to_string())
+    }
+}
 
 /// Use Chariott Service Discovery to discover a service.
 ///
+/// The exact version is tried first. If it is not registered or does not have the requested
+/// protocol, the newest compatible version in the namespace is used, see [`is_version_compatible`].
+///
 /// # Arguments
 /// * `chariott_uri` - Chariott's URI.
 /// * `namespace` - The service's namespace.
 /// * `name` - The service's name.
 /// * `version` - The service's version.
-/// # `communication_kind` - The service's communication kind.
-/// # `communication_reference` - The service's communication reference.
+/// * `communication_kind` - The service's communication kind.
+/// * `communication_reference` - The service's communication reference.
+/// * `protocol_matching` - How strictly the service's protocol has to match.
 pub async fn discover_service_using_chariott(
     chariott_uri: &str,
     namespace: &str,
@@ -25,10 +213,19 @@ pub async fn discover_service_using_chariott(
     version: &str,
     communication_kind: &str,
     communication_reference: &str,
-) -> Result<String, Status> {
+    protocol_matching: ProtocolMatching,
+) -> Result<String, DiscoveryError> {
     let mut client = ServiceRegistryClient::connect(chariott_uri.to_string())
         .await
-        .map_err(|e| Status::internal(e.to_string()))?;
+        .map_err(|error| DiscoveryError::Connect {
+            uri: chariott_uri.to_string(),
+            message: error.to_string(),
+        })?;
+
+    let request_error = |status: Status| DiscoveryError::Request {
+        uri: chariott_uri.to_string(),
+        status,
+    };
 
     let request = Request::new(DiscoverRequest {
         namespace: namespace.to_string(),
@@ -36,22 +233,67 @@ pub async fn discover_service_using_chariott(
         version: version.to_string(),
     });
 
-    let response = client
-        .discover(request)
-        .await
-        .map_err(|error| Status::internal(error.to_string()))?;
+    match client.discover(request).await {
+        Ok(response) => {
+            if let Some(service) = response.into_inner().service {
+                if protocol_matching.matches(&service, communication_kind, communication_reference)
+                {
+                    return Ok(service.uri);
+                }
+            }
+        }
+        Err(status) if status.code() == Code::NotFound => {}
+        Err(status) => return Err(request_error(status)),
+    }
 
-    let service = response.into_inner().service.ok_or_else(|| Status::not_found("Did not find a service in Chariott with namespace '{namespace}', name '{name}' and version {version}"))?;
+    debug!("Looking for a version of '{name}' in '{namespace}' that is compatible with {version}");
 
-    if service.communication_kind != communication_kind
-        && service.communication_reference != communication_reference
-    {
-        return Err(Status::not_found(
-            "Did not find a service in Chariott with namespace '{namespace}', name '{name}' and version {version} that has communication kind '{communication_kind} and communication_reference '{communication_reference}''",
-        ));
-    }
+    let request = Request::new(DiscoverByNamespaceRequest {
+        namespace: namespace.to_string(),
+    });
+
+    let services = match client.discover_by_namespace(request).await {
+        Ok(response) => response.into_inner().services,
+        Err(status) if status.code() == Code::NotFound => Vec::new(),
+        Err(status) => return Err(request_error(status)),
+    };
+
+    let mut compatible_services: Vec<ServiceMetadata> = services
+        .into_iter()
+        .filter(|service| service.name == name && is_version_compatible(version, &service.version))
+        .collect();
+
+    // Prefer the newest version.
+    compatible_services.sort_by_key(|service| std::cmp::Reverse(parse_version(&service.version)));
+
+    let Some(newest_service) = compatible_services.first() else {
+        return Err(DiscoveryError::ServiceNotFound {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+            version: version.to_string(),
+        });
+    };
 
-    Ok(service.uri)
+    match compatible_services.iter().find(|service| {
+        protocol_matching.matches(service, communication_kind, communication_reference)
+    }) {
+        Some(service) => {
+            info!(
+                "Discovered '{name}' version {} for version {version}",
+                service.version
+            );
+            Ok(service.uri.clone())
+        }
+        None => Err(DiscoveryError::ProtocolMismatch {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+            version: newest_service.version.clone(),
+            expected_communication_kind: communication_kind.to_string(),
+            expected_communication_reference: communication_reference.to_string(),
+            communication_kind: newest_service.communication_kind.clone(),
+            communication_reference: newest_service.communication_reference.clone(),
+        }),
+    }
 }
 
 /// If the 'containerize' feature is set, this function will modify the localhost URI to point to
@@ -96,27 +338,40 @@ pub async fn discover_digital_twin_provider_using_ibeji(
     entity_id: &str,
     protocol: &str,
     operations: &[String],
-) -> Result<EndpointInfo, String> {
+) -> Result<EndpointInfo, DiscoveryError> {
     info!("Sending a find_by_id request for entity id {entity_id} to the In-Vehicle Digital Twin Service URI {invehicle_digitial_twin_service_uri}");
 
     let mut client =
         InvehicleDigitalTwinClient::connect(invehicle_digitial_twin_service_uri.to_string())
             .await
-            .map_err(|error| format!("{error}"))?;
+            .map_err(|error| DiscoveryError::Connect {
+                uri: invehicle_digitial_twin_service_uri.to_string(),
+                message: error.to_string(),
+            })?;
     let request = tonic::Request::new(FindByIdRequest {
         id: entity_id.to_string(),
     });
-    let response = client
-        .find_by_id(request)
-        .await
-        .map_err(|error| error.to_string())?;
+    let response = client.find_by_id(request).await.map_err(|status| {
+        if status.code() == Code::NotFound {
+            DiscoveryError::EntityNotFound {
+                entity_id: entity_id.to_string(),
+            }
+        } else {
+            DiscoveryError::Request {
+                uri: invehicle_digitial_twin_service_uri.to_string(),
+                status,
+            }
+        }
+    })?;
     let response_inner = response.into_inner();
     debug!("Received the response for the find_by_id request");
     info!("response_payload: {:?}", response_inner.entity_access_info);
 
     match response_inner
         .entity_access_info
-        .ok_or_else(|| "Did not find the entity".to_string())?
+        .ok_or_else(|| DiscoveryError::EntityNotFound {
+            entity_id: entity_id.to_string(),
+        })?
         .endpoint_info_list
         .iter()
         .find(|endpoint_info| {
@@ -131,12 +386,18 @@ pub async fn discover_digital_twin_provider_using_ibeji(
                 result.uri
             );
 
-            result.uri = get_uri(&result.uri)
-                .map_err(|err| format!("Failed to get provider URI due to error: {err}"))?;
+            result.uri = get_uri(&result.uri).map_err(|status| DiscoveryError::InvalidUri {
+                uri: result.uri.clone(),
+                status,
+            })?;
 
             Ok(result)
         }
-        None => Err("Did not find an endpoint that met our requirements".to_string()),
+        None => Err(DiscoveryError::MissingOperations {
+            entity_id: entity_id.to_string(),
+            protocol: protocol.to_string(),
+            operations: operations.to_vec(),
+        }),
     }
 }
 
@@ -0,0 +1,116 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! Deregistering managed subscribe entities from the fake of Ibeji's In-Vehicle Digital Twin
+//! Service.
+
+use interfaces::invehicle_digital_twin::v1::invehicle_digital_twin_client::InvehicleDigitalTwinClient;
+use interfaces::invehicle_digital_twin::v1::RegisterRequest;
+use wheelchair_digital_twin_providers_common::managed_subscribe_provider::{
+    create_managed_subscribe_entity_access_info, register_managed_subscribe_entity,
+};
+use wheelchair_digital_twin_providers_common::shutdown::deregister_entities;
+use wheelchair_test_support::TestEnvironment;
+
+const ENTITY_ID: &str = "dtmi:sdv:Test:Counter;1";
+const ENTITY_NAME: &str = "Counter";
+const ENTITY_DESCRIPTION: &str = "A counter";
+const PROVIDER_URI: &str = "http://provider-a"; // Devskim: ignore DS137138
+const OTHER_PROVIDER_URI: &str = "http://provider-b"; // Devskim: ignore DS137138
+
+#[tokio::test]
+async fn the_provider_deregisters_its_own_endpoint() {
+    let environment = TestEnvironment::start()
+        .await
+        .expect("The test environment should start");
+    let invehicle_digital_twin_uri = environment.invehicle_digital_twin_uri();
+
+    register_managed_subscribe_entity(
+        &invehicle_digital_twin_uri,
+        PROVIDER_URI,
+        ENTITY_ID,
+        ENTITY_NAME,
+        ENTITY_DESCRIPTION,
+    )
+    .await
+    .expect("The entity should be registered");
+
+    let registered = create_managed_subscribe_entity_access_info(
+        PROVIDER_URI,
+        ENTITY_ID,
+        ENTITY_NAME,
+        ENTITY_DESCRIPTION,
+    );
+    deregister_entities(&invehicle_digital_twin_uri, &[registered])
+        .await
+        .expect("The entity should be deregistered");
+
+    let entity = environment
+        .invehicle_digital_twin
+        .entity(ENTITY_ID)
+        .expect("The entity should still be known");
+    assert!(entity.endpoint_info_list.is_empty());
+
+    environment.shutdown().await;
+}
+
+#[tokio::test]
+async fn another_providers_registration_is_left_as_it_is() {
+    let environment = TestEnvironment::start()
+        .await
+        .expect("The test environment should start");
+    let invehicle_digital_twin_uri = environment.invehicle_digital_twin_uri();
+
+    register_managed_subscribe_entity(
+        &invehicle_digital_twin_uri,
+        PROVIDER_URI,
+        ENTITY_ID,
+        ENTITY_NAME,
+        ENTITY_DESCRIPTION,
+    )
+    .await
+    .expect("The entity should be registered");
+
+    // Another process registers the same entity with its own context.
+    let mut other = create_managed_subscribe_entity_access_info(
+        OTHER_PROVIDER_URI,
+        ENTITY_ID,
+        ENTITY_NAME,
+        ENTITY_DESCRIPTION,
+    );
+    for endpoint_info in other.endpoint_info_list.iter_mut() {
+        endpoint_info.context = "GetSubscriptionInfo/other".to_string();
+    }
+    let mut client = InvehicleDigitalTwinClient::connect(invehicle_digital_twin_uri.clone())
+        .await
+        .expect("The In-Vehicle Digital Twin should be reachable");
+    client
+        .register(RegisterRequest {
+            entity_access_info_list: vec![other],
+        })
+        .await
+        .expect("The other provider should register the entity");
+
+    let registered = create_managed_subscribe_entity_access_info(
+        PROVIDER_URI,
+        ENTITY_ID,
+        ENTITY_NAME,
+        ENTITY_DESCRIPTION,
+    );
+    deregister_entities(&invehicle_digital_twin_uri, &[registered])
+        .await
+        .expect("The deregistration should succeed");
+
+    let entity = environment
+        .invehicle_digital_twin
+        .entity(ENTITY_ID)
+        .expect("The entity should still be known");
+    assert_eq!(entity.endpoint_info_list.len(), 1);
+    assert_eq!(
+        entity.endpoint_info_list[0].context,
+        "GetSubscriptionInfo/other"
+    );
+
+    environment.shutdown().await;
+}
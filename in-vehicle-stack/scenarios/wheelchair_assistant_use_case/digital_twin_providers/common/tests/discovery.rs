@@ -0,0 +1,204 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! Discovering services through the fake of Chariott's Service Registry.
+
+use interfaces::chariott::service_discovery::core::v1::service_registry_server::ServiceRegistryServer;
+use interfaces::chariott::service_discovery::core::v1::ServiceMetadata;
+use tonic::transport::Server;
+use wheelchair_digital_twin_providers_common::utils::{
+    discover_service_using_chariott, is_version_compatible, DiscoveryError, ProtocolMatching,
+};
+use wheelchair_test_support::server::{serve, ServerHandle};
+use wheelchair_test_support::service_registry::FakeServiceRegistry;
+
+const NAMESPACE: &str = "sdv.test";
+const NAME: &str = "test_service";
+const KIND: &str = "grpc+proto";
+const REFERENCE: &str = "test_service.v1.proto";
+
+/// Create the metadata of a service in the test namespace.
+///
+/// # Arguments
+/// * `version` - The service's version.
+/// * `communication_kind` - The service's communication kind.
+/// * `communication_reference` - The service's communication reference.
+fn service(
+    version: &str,
+    communication_kind: &str,
+    communication_reference: &str,
+) -> ServiceMetadata {
+    ServiceMetadata {
+        namespace: NAMESPACE.to_string(),
+        name: NAME.to_string(),
+        version: version.to_string(),
+        uri: format!("http://service-{version}"), // Devskim: ignore DS137138
+        communication_kind: communication_kind.to_string(),
+        communication_reference: communication_reference.to_string(),
+    }
+}
+
+/// Serve a fake Service Registry with the services.
+///
+/// # Arguments
+/// * `services` - The registered services.
+async fn serve_registry(services: Vec<ServiceMetadata>) -> ServerHandle {
+    let registry = FakeServiceRegistry::default();
+    for service in services {
+        registry.insert(service);
+    }
+
+    serve(Server::builder().add_service(ServiceRegistryServer::new(registry)))
+        .await
+        .expect("The fake Service Registry should start")
+}
+
+/// Discover the test service.
+///
+/// # Arguments
+/// * `chariott` - The fake Service Registry.
+/// * `version` - The requested version.
+/// * `protocol_matching` - How strictly the protocol has to match.
+async fn discover(
+    chariott: &ServerHandle,
+    version: &str,
+    protocol_matching: ProtocolMatching,
+) -> Result<String, DiscoveryError> {
+    discover_service_using_chariott(
+        &chariott.uri(),
+        NAMESPACE,
+        NAME,
+        version,
+        KIND,
+        REFERENCE,
+        protocol_matching,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn strict_matching_requires_the_communication_reference() {
+    let chariott = serve_registry(vec![service("1.0", KIND, "other.v1.proto")]).await;
+
+    let result = discover(&chariott, "1.0", ProtocolMatching::Strict).await;
+
+    assert!(matches!(
+        result,
+        Err(DiscoveryError::ProtocolMismatch { communication_reference, .. })
+            if communication_reference == "other.v1.proto"
+    ));
+}
+
+#[tokio::test]
+async fn relaxed_matching_accepts_another_communication_reference() {
+    let chariott = serve_registry(vec![service("1.0", KIND, "other.v1.proto")]).await;
+
+    let uri = discover(&chariott, "1.0", ProtocolMatching::Relaxed)
+        .await
+        .unwrap();
+
+    assert_eq!(uri, "http://service-1.0"); // Devskim: ignore DS137138
+}
+
+#[tokio::test]
+async fn both_matchings_accept_the_requested_protocol() {
+    let chariott = serve_registry(vec![service("1.0", KIND, REFERENCE)]).await;
+
+    for protocol_matching in [ProtocolMatching::Strict, ProtocolMatching::Relaxed] {
+        let uri = discover(&chariott, "1.0", protocol_matching).await.unwrap();
+        assert_eq!(uri, "http://service-1.0"); // Devskim: ignore DS137138
+    }
+}
+
+#[tokio::test]
+async fn the_right_reference_with_the_wrong_kind_is_rejected() {
+    let chariott = serve_registry(vec![service("1.0", "grpc+json", REFERENCE)]).await;
+
+    for protocol_matching in [ProtocolMatching::Strict, ProtocolMatching::Relaxed] {
+        let result = discover(&chariott, "1.0", protocol_matching).await;
+
+        assert!(matches!(
+            result,
+            Err(DiscoveryError::ProtocolMismatch { communication_kind, .. })
+                if communication_kind == "grpc+json"
+        ));
+    }
+}
+
+#[tokio::test]
+async fn the_exact_version_is_preferred() {
+    let chariott = serve_registry(vec![
+        service("1.0", KIND, REFERENCE),
+        service("1.3", KIND, REFERENCE),
+    ])
+    .await;
+
+    let uri = discover(&chariott, "1.0", ProtocolMatching::Strict)
+        .await
+        .unwrap();
+
+    assert_eq!(uri, "http://service-1.0"); // Devskim: ignore DS137138
+}
+
+#[tokio::test]
+async fn the_newest_compatible_minor_version_is_used() {
+    let chariott = serve_registry(vec![
+        service("1.1", KIND, REFERENCE),
+        service("1.4.2", KIND, REFERENCE),
+        service("2.0", KIND, REFERENCE),
+    ])
+    .await;
+
+    let uri = discover(&chariott, "1.0", ProtocolMatching::Strict)
+        .await
+        .unwrap();
+
+    assert_eq!(uri, "http://service-1.4.2"); // Devskim: ignore DS137138
+}
+
+#[tokio::test]
+async fn another_major_version_is_not_found() {
+    let chariott = serve_registry(vec![service("2.0", KIND, REFERENCE)]).await;
+
+    let result = discover(&chariott, "1.0", ProtocolMatching::Relaxed).await;
+
+    assert!(matches!(
+        result,
+        Err(DiscoveryError::ServiceNotFound { version, .. }) if version == "1.0"
+    ));
+}
+
+#[tokio::test]
+async fn an_older_minor_version_is_not_found() {
+    let chariott = serve_registry(vec![service("1.0", KIND, REFERENCE)]).await;
+
+    let result = discover(&chariott, "1.2", ProtocolMatching::Strict).await;
+
+    assert!(matches!(
+        result,
+        Err(DiscoveryError::ServiceNotFound { .. })
+    ));
+}
+
+#[test]
+fn versions_are_compatible_within_a_major_version() {
+    assert!(is_version_compatible("1.0", "1.0"));
+    assert!(is_version_compatible("1.0", "1.0.0"));
+    assert!(is_version_compatible("1.0", "1.5"));
+    assert!(is_version_compatible("1.0", "1.5.3"));
+    assert!(is_version_compatible("1.2", "1.10"));
+
+    assert!(!is_version_compatible("1.2", "1.1"));
+    assert!(!is_version_compatible("1.0", "2.0"));
+    assert!(!is_version_compatible("2.0", "1.9"));
+    assert!(!is_version_compatible("1.0", "0.9"));
+}
+
+#[test]
+fn unparsable_versions_have_to_be_equal() {
+    assert!(is_version_compatible("latest", "latest"));
+    assert!(!is_version_compatible("latest", "1.0"));
+    assert!(!is_version_compatible("1.0", "1.x"));
+    assert!(!is_version_compatible("1.0", "1.0.0.1"));
+}
@@ -0,0 +1,156 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! A topic that is published on change holds back changes for its minimum interval and publishes
+//! an unchanged value again after its maximum interval.
+
+use interfaces::module::managed_subscribe::v1::managed_subscribe_callback_server::ManagedSubscribeCallback;
+use interfaces::module::managed_subscribe::v1::{
+    CallbackPayload, Constraint, SubscriptionInfo, TopicManagementRequest,
+};
+use serde_json::Value;
+use tokio::sync::{broadcast, watch};
+use tokio::time::{timeout, Duration, Instant};
+use tonic::Request;
+use wheelchair_digital_twin_providers_common::constants::{constraint_type, digital_twin_protocol};
+use wheelchair_digital_twin_providers_common::managed_subscribe_provider::ManagedSubscribeProvider;
+use wheelchair_digital_twin_providers_common::shutdown::DRAIN_TIMEOUT;
+use wheelchair_test_support::mqtt_broker::{EmbeddedMqttBroker, PublishedMessage};
+
+const ENTITY_ID: &str = "dtmi:sdv:Test:Counter;1";
+const ENTITY_NAME: &str = "Counter";
+const TOPIC: &str = "counter";
+const DEFAULT_INTERVAL_MS: u64 = 10;
+const MIN_INTERVAL_MS: u64 = 400;
+const MAX_INTERVAL_MS: u64 = 400;
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Some slack for the timers of a loaded test machine.
+const TOLERANCE: Duration = Duration::from_millis(50);
+
+/// Start publishing the topic on change.
+///
+/// # Arguments
+/// * `provider` - The provider.
+/// * `broker_uri` - The broker to publish to.
+/// * `constraints` - The subscription's constraints besides `on_change`.
+async fn publish_on_change(
+    provider: &ManagedSubscribeProvider<i32>,
+    broker_uri: &str,
+    constraints: &[(&str, u64)],
+) {
+    let mut constraints: Vec<Constraint> = constraints
+        .iter()
+        .map(|(r#type, value)| Constraint {
+            r#type: r#type.to_string(),
+            value: value.to_string(),
+        })
+        .collect();
+    constraints.push(Constraint {
+        r#type: constraint_type::ON_CHANGE.to_string(),
+        value: true.to_string(),
+    });
+
+    let request = Request::new(TopicManagementRequest {
+        action: "PUBLISH".to_string(),
+        payload: Some(CallbackPayload {
+            entity_id: ENTITY_ID.to_string(),
+            topic: TOPIC.to_string(),
+            constraints,
+            subscription_info: Some(SubscriptionInfo {
+                protocol: digital_twin_protocol::MQTT.to_string(),
+                uri: broker_uri.to_string(),
+            }),
+        }),
+    });
+    provider
+        .topic_management_cb(request)
+        .await
+        .expect("The topic should be published");
+}
+
+/// Wait for the next counter that is published to the topic.
+///
+/// # Arguments
+/// * `messages` - Receiver for the messages that are published to the broker.
+async fn next_counter(messages: &mut broadcast::Receiver<PublishedMessage>) -> i64 {
+    let message = timeout(RECEIVE_TIMEOUT, async {
+        loop {
+            let message = messages.recv().await.expect("The broker should be running");
+            if message.topic == TOPIC {
+                return message;
+            }
+        }
+    })
+    .await
+    .expect("A message should be published in time");
+
+    let property: Value =
+        serde_json::from_str(&message.payload_str()).expect("The property should be JSON");
+    property[ENTITY_NAME]
+        .as_i64()
+        .expect("The property should have the counter")
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn changes_are_held_back_for_the_min_interval() {
+    let broker = EmbeddedMqttBroker::start()
+        .await
+        .expect("The broker should start");
+    let mut messages = broker.messages();
+    let (counter_sender, data_stream) = watch::channel(1);
+    let provider =
+        ManagedSubscribeProvider::new(ENTITY_ID, ENTITY_NAME, data_stream, DEFAULT_INTERVAL_MS);
+
+    publish_on_change(
+        &provider,
+        &broker.uri(),
+        &[(constraint_type::MIN_INTERVAL_MS, MIN_INTERVAL_MS)],
+    )
+    .await;
+    assert_eq!(next_counter(&mut messages).await, 1);
+    let first_published_at = Instant::now();
+
+    // Changes within the min interval are collapsed into the latest one.
+    for counter in 2..=4 {
+        counter_sender.send_replace(counter);
+    }
+    assert_eq!(next_counter(&mut messages).await, 4);
+    assert!(first_published_at.elapsed() + TOLERANCE >= Duration::from_millis(MIN_INTERVAL_MS));
+
+    // Without a change nothing is published again.
+    let unchanged = timeout(
+        Duration::from_millis(2 * MIN_INTERVAL_MS),
+        next_counter(&mut messages),
+    )
+    .await;
+    assert!(unchanged.is_err());
+
+    provider.shutdown(DRAIN_TIMEOUT).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn an_unchanged_value_is_published_again_after_the_max_interval() {
+    let broker = EmbeddedMqttBroker::start()
+        .await
+        .expect("The broker should start");
+    let mut messages = broker.messages();
+    let (_counter_sender, data_stream) = watch::channel(1);
+    let provider =
+        ManagedSubscribeProvider::new(ENTITY_ID, ENTITY_NAME, data_stream, DEFAULT_INTERVAL_MS);
+
+    publish_on_change(
+        &provider,
+        &broker.uri(),
+        &[(constraint_type::MAX_INTERVAL_MS, MAX_INTERVAL_MS)],
+    )
+    .await;
+    assert_eq!(next_counter(&mut messages).await, 1);
+    let first_published_at = Instant::now();
+
+    assert_eq!(next_counter(&mut messages).await, 1);
+    assert!(first_published_at.elapsed() + TOLERANCE >= Duration::from_millis(MAX_INTERVAL_MS));
+
+    provider.shutdown(DRAIN_TIMEOUT).await;
+}
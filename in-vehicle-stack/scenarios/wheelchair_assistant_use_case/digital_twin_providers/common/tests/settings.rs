@@ -0,0 +1,226 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! Loading the layered settings: the defaults, then the settings file, then the environment.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+use wheelchair_digital_twin_providers_common::settings::{
+    load_settings, ManagedSubscribeProviderSettings, ProviderSettings, RetrySettings,
+    SettingsError, CONFIG_PATH_ENV_VAR,
+};
+
+const NAME: &str = "test_provider";
+const DEFAULT_CHARIOTT_URI: &str = "http://0.0.0.0:50000"; // Devskim: ignore DS137138
+const DEFAULT_PROVIDER_AUTHORITY: &str = "0.0.0.0:4010";
+const DEFAULT_MIN_INTERVAL_MS: u64 = 100;
+
+/// The environment is shared by the tests of this binary, so only one of them may change it.
+static ENVIRONMENT: Mutex<()> = Mutex::new(());
+
+/// An environment with a settings directory of its own, the variables that it sets are removed
+/// and the directory is deleted when it is dropped.
+struct TestEnvironment {
+    config_path: PathBuf,
+    variables: Vec<String>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl TestEnvironment {
+    /// Create the environment with an empty settings directory.
+    ///
+    /// # Arguments
+    /// * `test` - The test's name, used for the settings directory.
+    fn new(test: &str) -> Self {
+        let lock = ENVIRONMENT.lock().unwrap_or_else(|err| err.into_inner());
+
+        let config_path =
+            std::env::temp_dir().join(format!("wheelchair-settings-{}-{test}", std::process::id()));
+        fs::create_dir_all(&config_path).expect("The settings directory should be created");
+
+        let mut environment = TestEnvironment {
+            config_path,
+            variables: Vec::new(),
+            _lock: lock,
+        };
+        let config_path = environment.config_path.to_string_lossy().to_string();
+        environment.set_var(CONFIG_PATH_ENV_VAR, &config_path);
+
+        environment
+    }
+
+    /// Set an environment variable.
+    ///
+    /// # Arguments
+    /// * `key` - The variable's name.
+    /// * `value` - The variable's value.
+    fn set_var(&mut self, key: &str, value: &str) {
+        std::env::set_var(key, value);
+        self.variables.push(key.to_string());
+    }
+
+    /// Write the settings file of the test provider.
+    ///
+    /// # Arguments
+    /// * `contents` - The YAML contents of the file.
+    fn write_settings_file(&self, contents: &str) {
+        fs::write(
+            self.config_path.join(format!("{NAME}_settings.yaml")),
+            contents,
+        )
+        .expect("The settings file should be written");
+    }
+}
+
+impl Drop for TestEnvironment {
+    fn drop(&mut self) {
+        for key in &self.variables {
+            std::env::remove_var(key);
+        }
+        let _ = fs::remove_dir_all(&self.config_path);
+    }
+}
+
+/// The defaults of the test provider.
+fn defaults() -> ManagedSubscribeProviderSettings {
+    ManagedSubscribeProviderSettings {
+        provider: ProviderSettings {
+            chariott_uri: DEFAULT_CHARIOTT_URI.to_string(),
+            provider_authority: DEFAULT_PROVIDER_AUTHORITY.to_string(),
+            retry: RetrySettings::default(),
+        },
+        min_interval_ms: DEFAULT_MIN_INTERVAL_MS,
+    }
+}
+
+/// Load the settings of the test provider.
+fn load() -> Result<ManagedSubscribeProviderSettings, SettingsError> {
+    load_settings(NAME, &defaults())
+}
+
+/// Assert that loading fails because of an invalid setting.
+///
+/// # Arguments
+/// * `expected_field` - The name of the setting that is expected to be invalid.
+fn assert_invalid(expected_field: &str) {
+    match load() {
+        Err(SettingsError::Invalid { field, .. }) => assert_eq!(field, expected_field),
+        other => panic!("The setting {expected_field} should be invalid, got {other:?}"),
+    }
+}
+
+#[test]
+fn the_defaults_are_used_without_file_and_environment() {
+    let _environment = TestEnvironment::new("defaults");
+
+    let settings = load().expect("The settings should load");
+
+    assert_eq!(settings.provider.chariott_uri, DEFAULT_CHARIOTT_URI);
+    assert_eq!(
+        settings.provider.provider_authority,
+        DEFAULT_PROVIDER_AUTHORITY
+    );
+    assert_eq!(settings.min_interval_ms, DEFAULT_MIN_INTERVAL_MS);
+    assert_eq!(
+        settings.provider.retry.max_attempts,
+        RetrySettings::default().max_attempts
+    );
+}
+
+#[test]
+fn the_file_overrides_the_defaults() {
+    let environment = TestEnvironment::new("file");
+    environment.write_settings_file(
+        "chariott_uri: http://chariott:50000\n\
+         min_interval_ms: 250\n\
+         retry:\n  \
+           max_attempts: 3\n",
+    );
+
+    let settings = load().expect("The settings should load");
+
+    assert_eq!(settings.provider.chariott_uri, "http://chariott:50000"); // Devskim: ignore DS137138
+    assert_eq!(settings.min_interval_ms, 250);
+    assert_eq!(settings.provider.retry.max_attempts, Some(3));
+
+    // Settings that the file does not set keep their defaults, also within a nested section.
+    assert_eq!(
+        settings.provider.provider_authority,
+        DEFAULT_PROVIDER_AUTHORITY
+    );
+    assert_eq!(
+        settings.provider.retry.initial_delay_ms,
+        RetrySettings::default().initial_delay_ms
+    );
+}
+
+#[test]
+fn the_environment_overrides_the_file() {
+    let mut environment = TestEnvironment::new("environment");
+    environment.write_settings_file(
+        "chariott_uri: http://chariott:50000\n\
+         retry:\n  \
+           max_attempts: 3\n  \
+           jitter: 0.5\n",
+    );
+    environment.set_var("WHEELCHAIR_CHARIOTT_URI", "http://localhost:50001"); // Devskim: ignore DS137138
+    environment.set_var("WHEELCHAIR_MIN_INTERVAL_MS", "500");
+    environment.set_var("WHEELCHAIR_RETRY__MAX_ATTEMPTS", "7");
+
+    let settings = load().expect("The settings should load");
+
+    assert_eq!(settings.provider.chariott_uri, "http://localhost:50001"); // Devskim: ignore DS137138
+    assert_eq!(settings.min_interval_ms, 500);
+    assert_eq!(settings.provider.retry.max_attempts, Some(7));
+
+    // The nested setting that the environment does not override is still read from the file.
+    assert_eq!(settings.provider.retry.jitter, 0.5);
+}
+
+#[test]
+fn an_invalid_uri_is_rejected() {
+    let environment = TestEnvironment::new("invalid-uri");
+    environment.write_settings_file("chariott_uri: chariott:50000\n");
+
+    assert_invalid("chariott_uri");
+}
+
+#[test]
+fn an_invalid_authority_is_rejected() {
+    let mut environment = TestEnvironment::new("invalid-authority");
+    environment.set_var("WHEELCHAIR_PROVIDER_AUTHORITY", "localhost");
+
+    assert_invalid("provider_authority");
+}
+
+#[test]
+fn an_invalid_nested_setting_is_rejected() {
+    let mut environment = TestEnvironment::new("invalid-nested");
+    environment.set_var("WHEELCHAIR_RETRY__JITTER", "1.5");
+
+    assert_invalid("retry.jitter");
+}
+
+#[test]
+fn a_zero_interval_is_rejected() {
+    let environment = TestEnvironment::new("zero-interval");
+    environment.write_settings_file("min_interval_ms: 0\n");
+
+    assert_invalid("min_interval_ms");
+}
+
+#[test]
+fn a_malformed_file_is_not_loaded() {
+    let environment = TestEnvironment::new("malformed");
+    environment.write_settings_file("min_interval_ms: often\n");
+
+    let result = load();
+
+    assert!(
+        matches!(result, Err(SettingsError::Load(_))),
+        "The settings should not load, got {result:?}"
+    );
+}
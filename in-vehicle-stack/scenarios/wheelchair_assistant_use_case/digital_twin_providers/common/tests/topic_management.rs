@@ -0,0 +1,366 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! The topic management callback of a managed subscribe provider answers every request, however
+//! malformed, with a gRPC status instead of panicking.
+
+use std::collections::HashSet;
+
+use interfaces::module::managed_subscribe::v1::managed_subscribe_callback_server::ManagedSubscribeCallback;
+use interfaces::module::managed_subscribe::v1::{
+    CallbackPayload, Constraint, SubscriptionInfo, TopicManagementRequest,
+};
+use tokio::sync::watch;
+use tonic::{Code, Request};
+use wheelchair_digital_twin_providers_common::constants::{constraint_type, digital_twin_protocol};
+use wheelchair_digital_twin_providers_common::managed_subscribe_provider::ManagedSubscribeProvider;
+use wheelchair_digital_twin_providers_common::shutdown::DRAIN_TIMEOUT;
+use wheelchair_test_support::mqtt_broker::EmbeddedMqttBroker;
+
+const ENTITY_ID: &str = "dtmi:sdv:Test:Counter;1";
+const ENTITY_NAME: &str = "Counter";
+const MIN_INTERVAL_MS: u64 = 100;
+const PUBLISH: &str = "PUBLISH";
+const STOP_PUBLISH: &str = "STOP_PUBLISH";
+
+const SEEDS: [u64; 4] = [1, 42, 0x5eed, 0xdead_beef];
+const REQUESTS_PER_SEED: usize = 200;
+
+const ACTIONS: [&str; 7] = [
+    PUBLISH,
+    STOP_PUBLISH,
+    "publish",
+    "",
+    "SUBSCRIBE",
+    "PUBLISH ",
+    "STOP-PUBLISH",
+];
+const ENTITY_IDS: [&str; 3] = [ENTITY_ID, "", "dtmi:sdv:Test:Other;1"];
+const TOPICS: [&str; 3] = ["topic-a", "topic-b", ""];
+
+/// A small deterministic generator, so that a failing sequence can be replayed from its seed.
+struct Xorshift(u64);
+
+impl Xorshift {
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Pick one of the items.
+    ///
+    /// # Arguments
+    /// * `items` - The items to pick from.
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[(self.next_u64() % items.len() as u64) as usize]
+    }
+
+    /// Whether an event with the probability of one in `n` happens.
+    ///
+    /// # Arguments
+    /// * `n` - The inverse of the probability.
+    fn one_in(&mut self, n: u64) -> bool {
+        self.next_u64() % n == 0
+    }
+}
+
+/// Create a constraint.
+///
+/// # Arguments
+/// * `r#type` - The constraint's type.
+/// * `value` - The constraint's value.
+fn constraint(r#type: &str, value: &str) -> Constraint {
+    Constraint {
+        r#type: r#type.to_string(),
+        value: value.to_string(),
+    }
+}
+
+/// Sets of constraints and whether the provider accepts them.
+fn constraint_sets() -> Vec<(Vec<Constraint>, bool)> {
+    vec![
+        (vec![], true),
+        (vec![constraint(constraint_type::FREQUENCY_MS, "100")], true),
+        (
+            vec![
+                constraint(constraint_type::ON_CHANGE, "true"),
+                constraint(constraint_type::MAX_INTERVAL_MS, "100"),
+            ],
+            true,
+        ),
+        (
+            vec![constraint(constraint_type::FREQUENCY_MS, "fast")],
+            false,
+        ),
+        (vec![constraint(constraint_type::FREQUENCY_MS, "0")], false),
+        (vec![constraint(constraint_type::QOS, "3")], false),
+        (vec![constraint("colour", "red")], false),
+        (vec![constraint(constraint_type::DEADBAND, "1")], false),
+        (
+            vec![
+                constraint(constraint_type::FREQUENCY_MS, "100"),
+                constraint(constraint_type::FREQUENCY_MS, "200"),
+            ],
+            false,
+        ),
+    ]
+}
+
+/// Create a payload.
+///
+/// # Arguments
+/// * `entity_id` - The payload's entity id.
+/// * `topic` - The payload's topic.
+/// * `constraints` - The payload's constraints.
+/// * `broker_uri` - The broker to publish to, none for a payload without subscription info.
+fn payload(
+    entity_id: &str,
+    topic: &str,
+    constraints: Vec<Constraint>,
+    broker_uri: Option<&str>,
+) -> CallbackPayload {
+    CallbackPayload {
+        entity_id: entity_id.to_string(),
+        topic: topic.to_string(),
+        constraints,
+        subscription_info: broker_uri.map(|uri| SubscriptionInfo {
+            protocol: digital_twin_protocol::MQTT.to_string(),
+            uri: uri.to_string(),
+        }),
+    }
+}
+
+/// Send a request to the provider's callback and return the status code of its answer.
+///
+/// # Arguments
+/// * `provider` - The provider.
+/// * `action` - The request's action.
+/// * `payload` - The request's payload.
+async fn topic_management(
+    provider: &ManagedSubscribeProvider<i32>,
+    action: &str,
+    payload: Option<CallbackPayload>,
+) -> Code {
+    let request = Request::new(TopicManagementRequest {
+        action: action.to_string(),
+        payload,
+    });
+
+    match provider.topic_management_cb(request).await {
+        Ok(_) => Code::Ok,
+        Err(status) => status.code(),
+    }
+}
+
+/// The status code that the provider is expected to answer a request with, in the order that the
+/// request is checked.
+///
+/// # Arguments
+/// * `action` - The request's action.
+/// * `payload` - The request's payload.
+/// * `constraints_valid` - Whether the payload's constraints are accepted.
+fn expected_code(action: &str, payload: Option<&CallbackPayload>, constraints_valid: bool) -> Code {
+    let Some(payload) = payload else {
+        return Code::InvalidArgument;
+    };
+
+    if action != PUBLISH && action != STOP_PUBLISH {
+        return Code::InvalidArgument;
+    }
+
+    if payload.entity_id != ENTITY_ID {
+        return Code::NotFound;
+    }
+
+    if payload.topic.is_empty() {
+        return Code::InvalidArgument;
+    }
+
+    if action == PUBLISH && (!constraints_valid || payload.subscription_info.is_none()) {
+        return Code::InvalidArgument;
+    }
+
+    Code::Ok
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn malformed_requests_are_rejected_with_a_status() {
+    let broker = EmbeddedMqttBroker::start()
+        .await
+        .expect("The broker should start");
+    let broker_uri = broker.uri();
+    let (_sender, data_stream) = watch::channel(0);
+    let provider =
+        ManagedSubscribeProvider::new(ENTITY_ID, ENTITY_NAME, data_stream, MIN_INTERVAL_MS);
+
+    let cases = [
+        (
+            "a request without payload",
+            PUBLISH,
+            None,
+            Code::InvalidArgument,
+        ),
+        (
+            "an unknown action",
+            "UNSUBSCRIBE",
+            Some(payload(
+                ENTITY_ID,
+                "topic",
+                vec![],
+                Some(broker_uri.as_str()),
+            )),
+            Code::InvalidArgument,
+        ),
+        (
+            "another provider's entity",
+            PUBLISH,
+            Some(payload(
+                "dtmi:sdv:Test:Other;1",
+                "topic",
+                vec![],
+                Some(broker_uri.as_str()),
+            )),
+            Code::NotFound,
+        ),
+        (
+            "an empty topic",
+            PUBLISH,
+            Some(payload(ENTITY_ID, "", vec![], Some(broker_uri.as_str()))),
+            Code::InvalidArgument,
+        ),
+        (
+            "a publish without subscription info",
+            PUBLISH,
+            Some(payload(ENTITY_ID, "topic", vec![], None)),
+            Code::InvalidArgument,
+        ),
+        (
+            "an invalid constraint",
+            PUBLISH,
+            Some(payload(
+                ENTITY_ID,
+                "topic",
+                vec![constraint(constraint_type::FREQUENCY_MS, "-1")],
+                Some(broker_uri.as_str()),
+            )),
+            Code::InvalidArgument,
+        ),
+        (
+            "a stop of another provider's entity",
+            STOP_PUBLISH,
+            Some(payload("", "topic", vec![], None)),
+            Code::NotFound,
+        ),
+        (
+            "a stop of an empty topic",
+            STOP_PUBLISH,
+            Some(payload(ENTITY_ID, "", vec![], None)),
+            Code::InvalidArgument,
+        ),
+    ];
+
+    for (description, action, payload, expected) in cases {
+        let code = topic_management(&provider, action, payload).await;
+        assert_eq!(code, expected, "{description}");
+    }
+
+    provider.shutdown(DRAIN_TIMEOUT).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn repeated_actions_are_accepted() {
+    let broker = EmbeddedMqttBroker::start()
+        .await
+        .expect("The broker should start");
+    let broker_uri = broker.uri();
+    let (_sender, data_stream) = watch::channel(0);
+    let provider =
+        ManagedSubscribeProvider::new(ENTITY_ID, ENTITY_NAME, data_stream, MIN_INTERVAL_MS);
+
+    // Stopping a topic that is not published is not an error.
+    let stop = || Some(payload(ENTITY_ID, "topic", vec![], None));
+    assert_eq!(
+        topic_management(&provider, STOP_PUBLISH, stop()).await,
+        Code::Ok
+    );
+
+    let publish = || {
+        Some(payload(
+            ENTITY_ID,
+            "topic",
+            vec![],
+            Some(broker_uri.as_str()),
+        ))
+    };
+    for _ in 0..3 {
+        assert_eq!(
+            topic_management(&provider, PUBLISH, publish()).await,
+            Code::Ok
+        );
+    }
+    for _ in 0..3 {
+        assert_eq!(
+            topic_management(&provider, STOP_PUBLISH, stop()).await,
+            Code::Ok
+        );
+    }
+
+    // A stopped topic can be published again.
+    assert_eq!(
+        topic_management(&provider, PUBLISH, publish()).await,
+        Code::Ok
+    );
+
+    provider.shutdown(DRAIN_TIMEOUT).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn arbitrary_requests_are_answered_with_the_expected_status() {
+    let broker = EmbeddedMqttBroker::start()
+        .await
+        .expect("The broker should start");
+    let broker_uri = broker.uri();
+    let constraint_sets = constraint_sets();
+    let mut published_topics = HashSet::new();
+
+    for seed in SEEDS {
+        let (_sender, data_stream) = watch::channel(0);
+        let provider =
+            ManagedSubscribeProvider::new(ENTITY_ID, ENTITY_NAME, data_stream, MIN_INTERVAL_MS);
+        let mut rng = Xorshift(seed);
+
+        for index in 0..REQUESTS_PER_SEED {
+            let action = *rng.pick(&ACTIONS);
+            let (constraints, constraints_valid) = rng.pick(&constraint_sets).clone();
+            let payload = (!rng.one_in(10)).then(|| {
+                let entity_id = *rng.pick(&ENTITY_IDS);
+                let topic = *rng.pick(&TOPICS);
+                let broker_uri = (!rng.one_in(5)).then_some(broker_uri.as_str());
+                payload(entity_id, topic, constraints, broker_uri)
+            });
+
+            let expected = expected_code(action, payload.as_ref(), constraints_valid);
+            let description = format!("request {index} of seed {seed}: {action:?} {payload:?}");
+            if expected == Code::Ok && action == PUBLISH {
+                published_topics.extend(payload.as_ref().map(|payload| payload.topic.clone()));
+            }
+
+            let code = topic_management(&provider, action, payload).await;
+            assert_eq!(code, expected, "{description}");
+        }
+
+        provider.shutdown(DRAIN_TIMEOUT).await;
+    }
+
+    // Only the topics of accepted requests have been published to.
+    for message in broker.published() {
+        assert!(
+            published_topics.contains(&message.topic),
+            "Published to {}",
+            message.topic
+        );
+    }
+}
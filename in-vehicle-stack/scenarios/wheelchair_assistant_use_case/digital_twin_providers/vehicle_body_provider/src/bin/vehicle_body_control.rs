@@ -0,0 +1,45 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! Sends a command to the vehicle body provider, so that a scenario changes the car's state with a
+//! message instead of restarting a container.
+//!
+//! Usage: `vehicle_body_control <lock|unlock|start-engine|stop-engine> [provider_uri]`
+
+use std::env;
+
+use wheelchair_assistant_interfaces::digital_twin_invoke_provider::v1::digital_twin_invoke_provider_client::DigitalTwinInvokeProviderClient;
+use wheelchair_assistant_interfaces::digital_twin_invoke_provider::v1::InvokeRequest;
+use wheelchair_digital_twin_model::car_v1;
+
+const DEFAULT_PROVIDER_URI: &str = "http://0.0.0.0:4020";
+const USAGE: &str =
+    "Usage: vehicle_body_control <lock|unlock|start-engine|stop-engine> [provider_uri]";
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = env::args().skip(1);
+
+    let command_id = match args.next().as_deref() {
+        Some("lock") => car_v1::car::lock::ID,
+        Some("unlock") => car_v1::car::unlock::ID,
+        Some("start-engine") => car_v1::car::start_engine::ID,
+        Some("stop-engine") => car_v1::car::stop_engine::ID,
+        _ => return Err(USAGE.into()),
+    };
+    let provider_uri = args
+        .next()
+        .unwrap_or_else(|| DEFAULT_PROVIDER_URI.to_string());
+
+    let mut client = DigitalTwinInvokeProviderClient::connect(provider_uri).await?;
+    let request = tonic::Request::new(InvokeRequest {
+        entity_id: command_id.to_string(),
+        payload: String::new(),
+    });
+    let response = client.invoke(request).await?;
+
+    println!("{}", response.into_inner().payload);
+
+    Ok(())
+}
@@ -0,0 +1,288 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+use std::net::SocketAddr;
+
+use wheelchair_assistant_interfaces::digital_twin_get_provider::v1::digital_twin_get_provider_server::DigitalTwinGetProviderServer;
+use wheelchair_assistant_interfaces::digital_twin_invoke_provider::v1::digital_twin_invoke_provider_server::DigitalTwinInvokeProviderServer;
+use wheelchair_assistant_interfaces::digital_twin_set_provider::v1::digital_twin_set_provider_server::DigitalTwinSetProviderServer;
+use wheelchair_digital_twin_model::car_v1;
+use wheelchair_digital_twin_providers_common::constants::chariott::{
+    INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_KIND,
+    INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_REFERENCE, INVEHICLE_DIGITAL_TWIN_SERVICE_NAME,
+    INVEHICLE_DIGITAL_TWIN_SERVICE_NAMESPACE, INVEHICLE_DIGITAL_TWIN_SERVICE_VERSION,
+};
+use wheelchair_digital_twin_providers_common::constants::{
+    digital_twin_operation, digital_twin_protocol,
+};
+use wheelchair_digital_twin_providers_common::health::{check, start_health_monitor};
+use wheelchair_digital_twin_providers_common::retry::RetryPolicy;
+use wheelchair_digital_twin_providers_common::settings::{
+    load_settings, ProviderSettings, RetrySettings, SettingsError, ValidateSettings,
+};
+use wheelchair_digital_twin_providers_common::shutdown::{deregister_entities, Shutdown};
+use wheelchair_digital_twin_providers_common::utils::{
+    discover_service_using_chariott, ProtocolMatching,
+};
+
+use env_logger::{Builder, Target};
+use interfaces::invehicle_digital_twin::v1::invehicle_digital_twin_client::InvehicleDigitalTwinClient;
+use interfaces::invehicle_digital_twin::v1::{EndpointInfo, EntityAccessInfo, RegisterRequest};
+use log::{debug, info, warn, LevelFilter};
+use serde_derive::{Deserialize, Serialize};
+use tonic::transport::Server;
+use tonic::Status;
+
+use crate::vehicle_body_provider_impl::{VehicleBodyProviderImpl, VehicleBodyState};
+
+mod vehicle_body_provider_impl;
+
+const DEFAULT_CHARIOTT_SERVICE_DISCOVERY_URI: &str = "http://0.0.0.0:50000";
+const DEFAULT_PROVIDER_AUTHORITY: &str = "0.0.0.0:4020";
+
+/// Settings of the vehicle body provider.
+#[derive(Debug, Serialize, Deserialize)]
+struct Settings {
+    #[serde(flatten)]
+    provider: ProviderSettings,
+    /// Whether the car is unlocked when the provider starts.
+    initially_unlocked: bool,
+    /// Whether the engine is running when the provider starts, requires the car to be unlocked.
+    initially_running: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            provider: ProviderSettings {
+                chariott_uri: DEFAULT_CHARIOTT_SERVICE_DISCOVERY_URI.to_string(),
+                provider_authority: DEFAULT_PROVIDER_AUTHORITY.to_string(),
+                retry: RetrySettings::default(),
+            },
+            initially_unlocked: false,
+            initially_running: false,
+        }
+    }
+}
+
+impl ValidateSettings for Settings {
+    fn validate(&self) -> Result<(), SettingsError> {
+        self.provider.validate()?;
+
+        if self.initially_running && !self.initially_unlocked {
+            return Err(SettingsError::Invalid {
+                field: "initially_running".to_string(),
+                message: "the engine cannot be running while the car is locked".to_string(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Create the access information of an entity that is served by this provider.
+///
+/// # Arguments
+/// * `provider_uri` - The provider's URI.
+/// * `id` - The entity's id.
+/// * `name` - The entity's name.
+/// * `description` - The entity's description.
+/// * `operations` - The operations that the provider supports for the entity.
+fn create_entity_access_info(
+    provider_uri: &str,
+    id: &str,
+    name: &str,
+    description: &str,
+    operations: &[&str],
+) -> EntityAccessInfo {
+    let endpoint_info = EndpointInfo {
+        protocol: digital_twin_protocol::GRPC.to_string(),
+        operations: operations
+            .iter()
+            .map(|operation| operation.to_string())
+            .collect(),
+        uri: provider_uri.to_string(),
+        context: id.to_string(),
+    };
+
+    EntityAccessInfo {
+        name: name.to_string(),
+        id: id.to_string(),
+        description: description.to_string(),
+        endpoint_info_list: vec![endpoint_info],
+    }
+}
+
+/// Create the access information of the "is_car_unlocked" and "is_car_running" properties and the
+/// "lock", "unlock", "start_engine" and "stop_engine" commands.
+///
+/// # Arguments
+/// * `provider_uri` - The provider's URI.
+fn create_entity_access_info_list(provider_uri: &str) -> Vec<EntityAccessInfo> {
+    let properties = [
+        (
+            car_v1::car::is_car_unlocked::ID,
+            car_v1::car::is_car_unlocked::NAME,
+            car_v1::car::is_car_unlocked::DESCRIPTION,
+        ),
+        (
+            car_v1::car::is_car_running::ID,
+            car_v1::car::is_car_running::NAME,
+            car_v1::car::is_car_running::DESCRIPTION,
+        ),
+    ];
+    let commands = [
+        (
+            car_v1::car::lock::ID,
+            car_v1::car::lock::NAME,
+            car_v1::car::lock::DESCRIPTION,
+        ),
+        (
+            car_v1::car::unlock::ID,
+            car_v1::car::unlock::NAME,
+            car_v1::car::unlock::DESCRIPTION,
+        ),
+        (
+            car_v1::car::start_engine::ID,
+            car_v1::car::start_engine::NAME,
+            car_v1::car::start_engine::DESCRIPTION,
+        ),
+        (
+            car_v1::car::stop_engine::ID,
+            car_v1::car::stop_engine::NAME,
+            car_v1::car::stop_engine::DESCRIPTION,
+        ),
+    ];
+
+    let property_operations = [digital_twin_operation::GET, digital_twin_operation::SET];
+    let command_operations = [digital_twin_operation::INVOKE];
+    properties
+        .iter()
+        .map(|(id, name, description)| {
+            create_entity_access_info(provider_uri, id, name, description, &property_operations)
+        })
+        .chain(commands.iter().map(|(id, name, description)| {
+            create_entity_access_info(provider_uri, id, name, description, &command_operations)
+        }))
+        .collect()
+}
+
+/// Register the entities' endpoints.
+///
+/// # Arguments
+/// * `invehicle_digital_twin_uri` - The In-Vehicle Digital Twin URI.
+/// * `entity_access_info_list` - The entities' access information.
+async fn register_entities(
+    invehicle_digital_twin_uri: &str,
+    entity_access_info_list: Vec<EntityAccessInfo>,
+) -> Result<(), Status> {
+    let mut client = InvehicleDigitalTwinClient::connect(invehicle_digital_twin_uri.to_string())
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+    let request = tonic::Request::new(RegisterRequest {
+        entity_access_info_list,
+    });
+    let _response = client.register(request).await?;
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Set up logging.
+    Builder::new()
+        .filter(None, LevelFilter::Debug)
+        .target(Target::Stdout)
+        .init();
+
+    info!("The Provider VehicleBody has started.");
+
+    let settings = load_settings("vehicle_body_provider", &Settings::default())?;
+    let provider_settings = &settings.provider;
+
+    let provider_uri = format!("http://{}", provider_settings.provider_authority);
+    debug!("The Provider URI is {}", &provider_uri);
+
+    let shutdown = Shutdown::on_signals();
+    let (health, health_service) = start_health_monitor(
+        "vehicle_body_provider",
+        &[check::DISCOVERY, check::REGISTRATION],
+        &shutdown,
+    )
+    .await?;
+
+    // Setup the HTTP server, it is served until the shutdown.
+    let addr: SocketAddr = provider_settings.provider_authority.parse()?;
+    let provider_impl = VehicleBodyProviderImpl::new(VehicleBodyState {
+        is_car_unlocked: settings.initially_unlocked,
+        is_car_running: settings.initially_running,
+    });
+    let server_handle = tokio::spawn(
+        Server::builder()
+            .add_service(health_service)
+            .add_service(DigitalTwinGetProviderServer::new(
+                provider_impl.get_provider(),
+            ))
+            .add_service(DigitalTwinSetProviderServer::new(provider_impl.clone()))
+            .add_service(DigitalTwinInvokeProviderServer::new(provider_impl))
+            .serve_with_shutdown(addr, shutdown.signal()),
+    );
+    info!(
+        "The HTTP server is listening on address '{}'",
+        provider_settings.provider_authority
+    );
+
+    let retry_policy = RetryPolicy::from(&provider_settings.retry);
+
+    // Get the In-vehicle Digital Twin Uri from the service discovery system
+    let discovery = retry_policy.retry("discover the In-Vehicle Digital Twin Service", || {
+        discover_service_using_chariott(
+            &provider_settings.chariott_uri,
+            INVEHICLE_DIGITAL_TWIN_SERVICE_NAMESPACE,
+            INVEHICLE_DIGITAL_TWIN_SERVICE_NAME,
+            INVEHICLE_DIGITAL_TWIN_SERVICE_VERSION,
+            INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_KIND,
+            INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_REFERENCE,
+            ProtocolMatching::Strict,
+        )
+    });
+    let Some(invehicle_digital_twin_uri) = shutdown.run_until(discovery).await.transpose()? else {
+        server_handle.await??;
+        info!("The Provider has stopped before discovering the In-Vehicle Digital Twin Service.");
+        return Ok(());
+    };
+    health.set(check::DISCOVERY, true).await;
+
+    debug!(
+        "Sending a register request to the In-Vehicle Digital Twin Service URI {}",
+        invehicle_digital_twin_uri
+    );
+
+    let entity_access_info_list = create_entity_access_info_list(&provider_uri);
+    let registration = retry_policy
+        .retry("register with the In-Vehicle Digital Twin Service", || {
+            register_entities(&invehicle_digital_twin_uri, entity_access_info_list.clone())
+        });
+    let is_registered = shutdown
+        .run_until(registration)
+        .await
+        .transpose()?
+        .is_some();
+    if is_registered {
+        health.set(check::REGISTRATION, true).await;
+    }
+
+    server_handle.await??;
+
+    if is_registered {
+        match deregister_entities(&invehicle_digital_twin_uri, &entity_access_info_list).await {
+            Ok(()) => debug!("The Provider has deregistered from Ibeji."),
+            Err(err) => warn!("Failed to deregister from Ibeji due to '{err}'"),
+        }
+    }
+
+    info!("The Provider has completed.");
+
+    Ok(())
+}
@@ -0,0 +1,386 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! Module containing gRPC service implementations based on
+//! [`interfaces::digital_twin_set_provider.proto`] and
+//! [`interfaces::digital_twin_invoke_provider.proto`], the properties are read through a
+//! [`GetProvider`].
+//!
+//! The vehicle body owns the IsCarUnlocked and IsCarRunning properties. They are changed through
+//! the "Set" operation or the lock, unlock, start engine and stop engine commands.
+use std::sync::Arc;
+
+use log::info;
+use parking_lot::Mutex;
+use tonic::{Request, Response, Status};
+use wheelchair_assistant_interfaces::digital_twin_invoke_provider::v1::digital_twin_invoke_provider_server::DigitalTwinInvokeProvider;
+use wheelchair_assistant_interfaces::digital_twin_invoke_provider::v1::{
+    InvokeRequest, InvokeResponse,
+};
+use wheelchair_assistant_interfaces::digital_twin_set_provider::v1::digital_twin_set_provider_server::DigitalTwinSetProvider;
+use wheelchair_assistant_interfaces::digital_twin_set_provider::v1::{SetRequest, SetResponse};
+use wheelchair_digital_twin_model::car_v1;
+use wheelchair_digital_twin_providers_common::get_provider::GetProvider;
+use wheelchair_digital_twin_providers_common::managed_subscribe_provider::create_property_json;
+
+/// The state of the vehicle body.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct VehicleBodyState {
+    pub is_car_unlocked: car_v1::car::is_car_unlocked::TYPE,
+    pub is_car_running: car_v1::car::is_car_running::TYPE,
+}
+
+impl VehicleBodyState {
+    /// Lock or unlock the car. The car cannot be locked while its engine is running.
+    ///
+    /// # Arguments
+    /// * `is_car_unlocked` - Whether the car is to be unlocked.
+    fn set_unlocked(&mut self, is_car_unlocked: bool) -> Result<(), Status> {
+        if !is_car_unlocked && self.is_car_running {
+            return Err(Status::failed_precondition(
+                "The car cannot be locked while its engine is running",
+            ));
+        }
+
+        self.is_car_unlocked = is_car_unlocked;
+        Ok(())
+    }
+
+    /// Start or stop the engine. The engine cannot be started while the car is locked.
+    ///
+    /// # Arguments
+    /// * `is_car_running` - Whether the engine is to be running.
+    fn set_running(&mut self, is_car_running: bool) -> Result<(), Status> {
+        if is_car_running && !self.is_car_unlocked {
+            return Err(Status::failed_precondition(
+                "The engine cannot be started while the car is locked",
+            ));
+        }
+
+        self.is_car_running = is_car_running;
+        Ok(())
+    }
+}
+
+/// Base structure for the Vehicle Body Provider gRPC service.
+#[derive(Clone, Debug, Default)]
+pub struct VehicleBodyProviderImpl {
+    state: Arc<Mutex<VehicleBodyState>>,
+}
+
+impl VehicleBodyProviderImpl {
+    /// Create the provider with an initial state.
+    ///
+    /// # Arguments
+    /// * `state` - The initial state.
+    pub fn new(state: VehicleBodyState) -> Self {
+        VehicleBodyProviderImpl {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Create the "Get" provider that serves the "is_car_unlocked" and "is_car_running"
+    /// properties.
+    pub fn get_provider(&self) -> GetProvider {
+        let unlocked_state = self.state.clone();
+        let running_state = self.state.clone();
+
+        GetProvider::default()
+            .with_property(car_v1::car::is_car_unlocked::ID, move || {
+                unlocked_state.lock().is_car_unlocked
+            })
+            .with_property(car_v1::car::is_car_running::ID, move || {
+                running_state.lock().is_car_running
+            })
+    }
+
+    /// Change the state and log the change.
+    ///
+    /// # Arguments
+    /// * `change` - Changes the state, or fails if the change is not allowed.
+    fn update<F>(&self, change: F) -> Result<VehicleBodyState, Status>
+    where
+        F: FnOnce(&mut VehicleBodyState) -> Result<(), Status>,
+    {
+        let mut state = self.state.lock();
+        let previous = *state;
+        change(&mut state)?;
+
+        if *state != previous {
+            info!("The vehicle body changed from {previous:?} to {:?}", *state);
+        }
+
+        Ok(*state)
+    }
+}
+
+#[tonic::async_trait]
+impl DigitalTwinSetProvider for VehicleBodyProviderImpl {
+    /// This function sets the value of "is_car_unlocked" or "is_car_running" property
+    async fn set(&self, request: Request<SetRequest>) -> Result<Response<SetResponse>, Status> {
+        let request = request.into_inner();
+        let value = request
+            .property::<bool>()
+            .map_err(|err| Status::invalid_argument(err.to_string()))?;
+
+        match request.entity_id.as_str() {
+            car_v1::car::is_car_unlocked::ID => self.update(|state| state.set_unlocked(value))?,
+            car_v1::car::is_car_running::ID => self.update(|state| state.set_running(value))?,
+            _ => {
+                return Err(Status::not_found(format!(
+                    "The property {} is not provided",
+                    request.entity_id
+                )))
+            }
+        };
+
+        Ok(Response::new(SetResponse {}))
+    }
+}
+
+#[tonic::async_trait]
+impl DigitalTwinInvokeProvider for VehicleBodyProviderImpl {
+    /// This function invokes the lock, unlock, start engine and stop engine commands and returns
+    /// the new value of the property that the command changes
+    async fn invoke(
+        &self,
+        request: Request<InvokeRequest>,
+    ) -> Result<Response<InvokeResponse>, Status> {
+        let command_id = request.into_inner().entity_id;
+
+        let payload = match command_id.as_str() {
+            car_v1::car::lock::ID | car_v1::car::unlock::ID => {
+                let is_car_unlocked = command_id == car_v1::car::unlock::ID;
+                let state = self.update(|state| state.set_unlocked(is_car_unlocked))?;
+
+                create_property_json(
+                    car_v1::car::is_car_unlocked::ID,
+                    car_v1::car::is_car_unlocked::NAME,
+                    &state.is_car_unlocked,
+                )
+            }
+            car_v1::car::start_engine::ID | car_v1::car::stop_engine::ID => {
+                let is_car_running = command_id == car_v1::car::start_engine::ID;
+                let state = self.update(|state| state.set_running(is_car_running))?;
+
+                create_property_json(
+                    car_v1::car::is_car_running::ID,
+                    car_v1::car::is_car_running::NAME,
+                    &state.is_car_running,
+                )
+            }
+            _ => {
+                return Err(Status::not_found(format!(
+                    "The command {command_id} is not provided"
+                )))
+            }
+        }
+        .map_err(|err| Status::internal(err.to_string()))?;
+
+        info!("Invoked {command_id}");
+
+        Ok(Response::new(InvokeResponse { payload }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::Value;
+    use tonic::Code;
+
+    const UNLOCKED: VehicleBodyState = VehicleBodyState {
+        is_car_unlocked: true,
+        is_car_running: false,
+    };
+    const RUNNING: VehicleBodyState = VehicleBodyState {
+        is_car_unlocked: true,
+        is_car_running: true,
+    };
+
+    /// Set a property of the provider.
+    ///
+    /// # Arguments
+    /// * `provider` - The provider.
+    /// * `entity_id` - The property's entity id.
+    /// * `value` - The property's new value.
+    async fn set(
+        provider: &VehicleBodyProviderImpl,
+        entity_id: &str,
+        value: bool,
+    ) -> Result<(), Status> {
+        provider
+            .set(Request::new(SetRequest::from_property(entity_id, value)))
+            .await
+            .map(|_| ())
+    }
+
+    /// Invoke a command of the provider and return its payload.
+    ///
+    /// # Arguments
+    /// * `provider` - The provider.
+    /// * `command_id` - The command's entity id.
+    async fn invoke(provider: &VehicleBodyProviderImpl, command_id: &str) -> Result<Value, Status> {
+        let payload = provider
+            .invoke(Request::new(InvokeRequest {
+                entity_id: command_id.to_string(),
+                ..Default::default()
+            }))
+            .await?
+            .into_inner()
+            .payload;
+
+        Ok(serde_json::from_str(&payload).expect("The payload should be JSON"))
+    }
+
+    /// The provider's current state.
+    ///
+    /// # Arguments
+    /// * `provider` - The provider.
+    fn state(provider: &VehicleBodyProviderImpl) -> VehicleBodyState {
+        *provider.state.lock()
+    }
+
+    #[test]
+    fn the_car_cannot_be_locked_while_running() {
+        let mut state = RUNNING;
+
+        let status = state.set_unlocked(false).unwrap_err();
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert_eq!(state, RUNNING);
+
+        assert!(state.set_running(false).is_ok());
+        assert!(state.set_unlocked(false).is_ok());
+        assert_eq!(state, VehicleBodyState::default());
+    }
+
+    #[test]
+    fn the_engine_cannot_be_started_while_locked() {
+        let mut state = VehicleBodyState::default();
+
+        let status = state.set_running(true).unwrap_err();
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert_eq!(state, VehicleBodyState::default());
+
+        assert!(state.set_unlocked(true).is_ok());
+        assert!(state.set_running(true).is_ok());
+        assert_eq!(state, RUNNING);
+    }
+
+    #[test]
+    fn stopping_and_unlocking_are_always_allowed() {
+        let mut state = VehicleBodyState::default();
+        assert!(state.set_running(false).is_ok());
+        assert!(state.set_unlocked(false).is_ok());
+
+        let mut state = RUNNING;
+        assert!(state.set_unlocked(true).is_ok());
+        assert!(state.set_running(true).is_ok());
+        assert_eq!(state, RUNNING);
+    }
+
+    #[tokio::test]
+    async fn set_changes_the_properties() {
+        let provider = VehicleBodyProviderImpl::default();
+
+        assert!(set(&provider, car_v1::car::is_car_unlocked::ID, true)
+            .await
+            .is_ok());
+        assert!(set(&provider, car_v1::car::is_car_running::ID, true)
+            .await
+            .is_ok());
+        assert_eq!(state(&provider), RUNNING);
+    }
+
+    #[tokio::test]
+    async fn set_rejects_a_change_that_is_not_allowed() {
+        let provider = VehicleBodyProviderImpl::new(RUNNING);
+
+        let status = set(&provider, car_v1::car::is_car_unlocked::ID, false)
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert_eq!(state(&provider), RUNNING);
+
+        let provider = VehicleBodyProviderImpl::default();
+        let status = set(&provider, car_v1::car::is_car_running::ID, true)
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert_eq!(state(&provider), VehicleBodyState::default());
+    }
+
+    #[tokio::test]
+    async fn set_rejects_unknown_properties() {
+        let provider = VehicleBodyProviderImpl::new(UNLOCKED);
+
+        for entity_id in [car_v1::car::lock::ID, "dtmi:sdv:Test:Unknown;1", ""] {
+            let status = set(&provider, entity_id, false).await.unwrap_err();
+            assert_eq!(status.code(), Code::NotFound, "{entity_id}");
+        }
+        assert_eq!(state(&provider), UNLOCKED);
+    }
+
+    #[tokio::test]
+    async fn set_rejects_a_value_that_is_not_a_bool() {
+        let provider = VehicleBodyProviderImpl::default();
+        let request = SetRequest::from_property(car_v1::car::is_car_unlocked::ID, 1);
+
+        let status = provider.set(Request::new(request)).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn commands_return_the_property_that_they_change() {
+        let provider = VehicleBodyProviderImpl::default();
+
+        let payload = invoke(&provider, car_v1::car::unlock::ID).await.unwrap();
+        assert_eq!(payload[car_v1::car::is_car_unlocked::NAME], true);
+
+        let payload = invoke(&provider, car_v1::car::start_engine::ID)
+            .await
+            .unwrap();
+        assert_eq!(payload[car_v1::car::is_car_running::NAME], true);
+        assert_eq!(state(&provider), RUNNING);
+
+        let payload = invoke(&provider, car_v1::car::stop_engine::ID)
+            .await
+            .unwrap();
+        assert_eq!(payload[car_v1::car::is_car_running::NAME], false);
+
+        let payload = invoke(&provider, car_v1::car::lock::ID).await.unwrap();
+        assert_eq!(payload[car_v1::car::is_car_unlocked::NAME], false);
+        assert_eq!(state(&provider), VehicleBodyState::default());
+    }
+
+    #[tokio::test]
+    async fn commands_that_are_not_allowed_fail() {
+        let provider = VehicleBodyProviderImpl::default();
+        let status = invoke(&provider, car_v1::car::start_engine::ID)
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::FailedPrecondition);
+
+        let provider = VehicleBodyProviderImpl::new(RUNNING);
+        let status = invoke(&provider, car_v1::car::lock::ID).await.unwrap_err();
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert_eq!(state(&provider), RUNNING);
+    }
+
+    #[tokio::test]
+    async fn invoke_rejects_unknown_commands() {
+        let provider = VehicleBodyProviderImpl::new(UNLOCKED);
+
+        for command_id in [
+            car_v1::car::is_car_unlocked::ID,
+            "dtmi:sdv:Test:Unknown;1",
+            "",
+        ] {
+            let status = invoke(&provider, command_id).await.unwrap_err();
+            assert_eq!(status.code(), Code::NotFound, "{command_id}");
+        }
+        assert_eq!(state(&provider), UNLOCKED);
+    }
+}
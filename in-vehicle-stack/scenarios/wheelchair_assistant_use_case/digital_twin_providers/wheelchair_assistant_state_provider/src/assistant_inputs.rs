@@ -0,0 +1,425 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! Collects the digital twin properties that drive the wheelchair assistant state machine.
+//!
+//! IsCarUnlocked and IsCarRunning are read through their "Get" providers. WheelchairDistanceState
+//! is received through a managed subscription.
+
+use std::sync::Arc;
+
+use interfaces::module::managed_subscribe::v1::managed_subscribe_client::ManagedSubscribeClient;
+use interfaces::module::managed_subscribe::v1::{Constraint, SubscriptionInfoRequest};
+use log::{debug, info, warn};
+use paho_mqtt as mqtt;
+use serde_derive::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration};
+use tonic::{Request, Status};
+use uuid::Uuid;
+use wheelchair_assistant_interfaces::digital_twin_get_provider::v1::Quality;
+use wheelchair_assistant_interfaces::property_value::{get_property, PropertyValue};
+use wheelchair_digital_twin_model::{car_v1, Metadata};
+use wheelchair_digital_twin_providers_common::constants::{
+    constraint_type, digital_twin_operation, digital_twin_protocol,
+};
+use wheelchair_digital_twin_providers_common::shutdown::Shutdown;
+use wheelchair_digital_twin_providers_common::utils::{
+    discover_digital_twin_provider_using_ibeji, get_uri,
+};
+
+const MQTT_CLIENT_ID: &str = "wheelchair-assistant-state-consumer";
+
+/// The inputs of the wheelchair assistant state machine.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AssistantInputs {
+    pub is_car_unlocked: car_v1::car::is_car_unlocked::TYPE,
+    pub is_wheelchair_near: car_v1::car::wheelchair_distance_state::TYPE,
+    pub is_car_running: car_v1::car::is_car_running::TYPE,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WheelchairDistanceStateProperty {
+    #[serde(rename = "WheelchairDistanceState")]
+    wheelchair_distance_state: car_v1::car::wheelchair_distance_state::TYPE,
+    #[serde(rename = "$metadata")]
+    metadata: Metadata,
+}
+
+/// Get the value of a property from its provider.
+///
+/// # Arguments
+/// * `provider_uri` - The provider's URI.
+/// * `entity_id` - The property's entity id.
+async fn get_property_value<T: PropertyValue>(
+    provider_uri: &str,
+    entity_id: &str,
+) -> Result<T, Status> {
+    let reading = get_property::<T>(provider_uri.to_string(), entity_id).await?;
+    if reading.quality == Quality::Bad {
+        return Err(Status::unavailable(format!(
+            "The provider reports that {entity_id} is unreliable"
+        )));
+    }
+
+    Ok(reading.value)
+}
+
+/// A property that is read from the provider that Ibeji has registered for it.
+struct PolledProperty {
+    entity_id: &'static str,
+    name: &'static str,
+    /// The provider's URI, it is looked up again after a failed read.
+    provider_uri: Option<String>,
+}
+
+impl PolledProperty {
+    /// Create a polled property.
+    ///
+    /// # Arguments
+    /// * `entity_id` - The property's entity id.
+    /// * `name` - The property's name.
+    fn new(entity_id: &'static str, name: &'static str) -> Self {
+        Self {
+            entity_id,
+            name,
+            provider_uri: None,
+        }
+    }
+
+    /// Read the property's value, none if it could not be read.
+    ///
+    /// # Arguments
+    /// * `invehicle_digital_twin_uri` - The In-Vehicle Digital Twin URI.
+    async fn read<T: PropertyValue>(&mut self, invehicle_digital_twin_uri: &str) -> Option<T> {
+        let provider_uri = match self.provider_uri.take() {
+            Some(provider_uri) => provider_uri,
+            None => match discover_digital_twin_provider_using_ibeji(
+                invehicle_digital_twin_uri,
+                self.entity_id,
+                digital_twin_protocol::GRPC,
+                &[digital_twin_operation::GET.to_string()],
+            )
+            .await
+            {
+                Ok(endpoint_info) => endpoint_info.uri,
+                Err(err) => {
+                    debug!(
+                        "Unable to find the provider of {} due to '{err}'",
+                        self.name
+                    );
+                    return None;
+                }
+            },
+        };
+
+        match get_property_value::<T>(&provider_uri, self.entity_id).await {
+            Ok(value) => {
+                self.provider_uri = Some(provider_uri);
+                Some(value)
+            }
+            Err(err) => {
+                debug!("Unable to get {} due to '{err}'", self.name);
+                None
+            }
+        }
+    }
+}
+
+/// Start polling the IsCarUnlocked and IsCarRunning properties.
+///
+/// A property that cannot be read keeps its last known value, so that a transient error does not
+/// look like the car being locked or turned off. Until its first read, a property is `false`, i.e.
+/// the car is locked or its ignition is off.
+///
+/// # Arguments
+/// * `invehicle_digital_twin_uri` - The In-Vehicle Digital Twin URI.
+/// * `inputs` - The sender for the state machine inputs.
+/// * `poll_interval_ms` - The interval between two reads.
+pub fn start_car_state_polling(
+    invehicle_digital_twin_uri: String,
+    inputs: Arc<watch::Sender<AssistantInputs>>,
+    poll_interval_ms: u64,
+) {
+    debug!("Starting to poll the car lock and ignition state.");
+
+    tokio::spawn(async move {
+        let mut is_car_unlocked_property = PolledProperty::new(
+            car_v1::car::is_car_unlocked::ID,
+            car_v1::car::is_car_unlocked::NAME,
+        );
+        let mut is_car_running_property = PolledProperty::new(
+            car_v1::car::is_car_running::ID,
+            car_v1::car::is_car_running::NAME,
+        );
+
+        loop {
+            let is_car_unlocked = is_car_unlocked_property
+                .read::<car_v1::car::is_car_unlocked::TYPE>(&invehicle_digital_twin_uri)
+                .await;
+            let is_car_running = is_car_running_property
+                .read::<car_v1::car::is_car_running::TYPE>(&invehicle_digital_twin_uri)
+                .await;
+
+            inputs.send_if_modified(|current| {
+                let previous = *current;
+                if let Some(is_car_unlocked) = is_car_unlocked {
+                    current.is_car_unlocked = is_car_unlocked;
+                }
+                if let Some(is_car_running) = is_car_running {
+                    current.is_car_running = is_car_running;
+                }
+                *current != previous
+            });
+
+            if inputs.is_closed() {
+                warn!("Stopped polling the car state, nobody is listening.");
+                break;
+            }
+
+            sleep(Duration::from_millis(poll_interval_ms)).await;
+        }
+    });
+}
+
+/// Get the wheelchair distance state subscription information from its managed subscribe endpoint.
+///
+/// # Arguments
+/// * `invehicle_digital_twin_uri` - The In-Vehicle Digital Twin URI.
+/// * `frequency_ms` - The longest time between two publishes, the state is also published whenever
+///   it changes.
+async fn get_wheelchair_distance_state_subscription(
+    invehicle_digital_twin_uri: &str,
+    frequency_ms: u64,
+) -> Result<(String, String), Status> {
+    let managed_subscribe_uri = discover_digital_twin_provider_using_ibeji(
+        invehicle_digital_twin_uri,
+        car_v1::car::wheelchair_distance_state::ID,
+        digital_twin_protocol::GRPC,
+        &[digital_twin_operation::MANAGEDSUBSCRIBE.to_string()],
+    )
+    .await?
+    .uri;
+
+    let mut client = ManagedSubscribeClient::connect(managed_subscribe_uri)
+        .await
+        .map_err(|err| Status::from_error(err.into()))?;
+
+    let request = Request::new(SubscriptionInfoRequest {
+        entity_id: car_v1::car::wheelchair_distance_state::ID.to_string(),
+        constraints: vec![
+            Constraint {
+                r#type: constraint_type::ON_CHANGE.to_string(),
+                value: true.to_string(),
+            },
+            Constraint {
+                r#type: constraint_type::MAX_INTERVAL_MS.to_string(),
+                value: frequency_ms.to_string(),
+            },
+        ],
+    });
+
+    let subscription_info = client.get_subscription_info(request).await?.into_inner();
+
+    Ok((get_uri(&subscription_info.uri)?, subscription_info.context))
+}
+
+/// Receive wheelchair distance state updates until the connection to the broker is lost or the
+/// client stops consuming.
+///
+/// # Arguments
+/// * `client` - The client, stopping its consumption ends the updates.
+/// * `broker_uri` - The broker URI.
+/// * `topic` - The topic.
+/// * `inputs` - The sender for the state machine inputs.
+/// * `shutdown` - The provider's shutdown.
+fn receive_wheelchair_distance_state_updates(
+    client: &mqtt::Client,
+    broker_uri: &str,
+    topic: &str,
+    inputs: &watch::Sender<AssistantInputs>,
+    shutdown: &Shutdown,
+) -> Result<(), String> {
+    let receiver = client.start_consuming();
+
+    // The consumption may have been stopped before it was started.
+    if shutdown.is_triggered() {
+        return Ok(());
+    }
+
+    let conn_opts = mqtt::ConnectOptionsBuilder::new()
+        .keep_alive_interval(Duration::from_secs(30))
+        .clean_session(true)
+        .finalize();
+
+    client
+        .connect(conn_opts)
+        .map_err(|err| format!("Failed to connect due to '{err:?}'"))?;
+
+    client
+        .subscribe(topic, mqtt::types::QOS_1)
+        .map_err(|err| format!("Failed to subscribe to topic {topic} due to '{err:?}'"))?;
+
+    for msg in receiver.iter() {
+        if let Some(msg) = msg {
+            let property: WheelchairDistanceStateProperty =
+                match serde_json::from_str(&msg.payload_str()) {
+                    Ok(property) => property,
+                    Err(err) => {
+                        warn!("Ignoring malformed message on {topic} due to '{err}'");
+                        continue;
+                    }
+                };
+
+            let is_wheelchair_near = property.wheelchair_distance_state;
+            inputs.send_if_modified(|current| {
+                let modified = current.is_wheelchair_near != is_wheelchair_near;
+                current.is_wheelchair_near = is_wheelchair_near;
+                modified
+            });
+        } else if shutdown.is_triggered() || (!client.is_connected() && client.reconnect().is_err())
+        {
+            break;
+        }
+    }
+
+    if client.is_connected() {
+        if let Err(err) = client.unsubscribe(topic) {
+            warn!("Failed to unsubscribe from topic {topic} due to '{err:?}'");
+        }
+        if let Err(err) = client.disconnect(None) {
+            warn!("Failed to disconnect from broker {broker_uri} due to '{err:?}'");
+        }
+    }
+
+    if shutdown.is_triggered() {
+        Ok(())
+    } else {
+        Err(format!("Lost the connection to broker {broker_uri}"))
+    }
+}
+
+/// Receive wheelchair distance state updates on a blocking thread until the connection to the
+/// broker is lost or the shutdown is triggered.
+///
+/// # Arguments
+/// * `broker_uri` - The broker URI.
+/// * `topic` - The topic.
+/// * `inputs` - The sender for the state machine inputs.
+/// * `shutdown` - The provider's shutdown.
+async fn receive_wheelchair_distance_state_until_shutdown(
+    broker_uri: String,
+    topic: String,
+    inputs: Arc<watch::Sender<AssistantInputs>>,
+    shutdown: &Shutdown,
+) -> Result<(), String> {
+    // Create a unique id for the client.
+    let client_id = format!("{MQTT_CLIENT_ID}-{}", Uuid::new_v4());
+
+    let create_opts = mqtt::CreateOptionsBuilder::new()
+        .server_uri(&broker_uri)
+        .client_id(client_id)
+        .finalize();
+
+    let client = mqtt::Client::new(create_opts)
+        .map_err(|err| format!("Failed to create the client due to '{err:?}'"))?;
+
+    let consumer = client.clone();
+    let consumer_shutdown = shutdown.clone();
+    let mut receiving = tokio::task::spawn_blocking(move || {
+        receive_wheelchair_distance_state_updates(
+            &consumer,
+            &broker_uri,
+            &topic,
+            &inputs,
+            &consumer_shutdown,
+        )
+    });
+
+    let result = tokio::select! {
+        result = &mut receiving => result,
+        _ = shutdown.wait() => {
+            // Ends the receiver's iteration, the blocking thread then disconnects and completes.
+            client.stop_consuming();
+            receiving.await
+        }
+    };
+
+    result.map_err(|err| format!("The receiving thread failed due to '{err}'"))?
+}
+
+/// Start receiving the WheelchairDistanceState property.
+///
+/// Until a provider for the property is registered with Ibeji, the wheelchair is treated as far.
+/// The updates are received until the shutdown, the returned handle completes once the MQTT client
+/// has stopped.
+///
+/// # Arguments
+/// * `invehicle_digital_twin_uri` - The In-Vehicle Digital Twin URI.
+/// * `inputs` - The sender for the state machine inputs.
+/// * `frequency_ms` - The longest time between two publishes of the state.
+/// * `retry_interval_ms` - The interval between two attempts to subscribe.
+/// * `shutdown` - The provider's shutdown.
+pub fn start_wheelchair_distance_state_subscription(
+    invehicle_digital_twin_uri: String,
+    inputs: Arc<watch::Sender<AssistantInputs>>,
+    frequency_ms: u64,
+    retry_interval_ms: u64,
+    shutdown: Shutdown,
+) -> JoinHandle<()> {
+    debug!("Starting to receive the wheelchair distance state.");
+
+    tokio::spawn(async move {
+        loop {
+            let subscription = shutdown
+                .run_until(get_wheelchair_distance_state_subscription(
+                    &invehicle_digital_twin_uri,
+                    frequency_ms,
+                ))
+                .await;
+
+            match subscription {
+                None => break,
+                Some(Ok((broker_uri, topic))) => {
+                    info!(
+                        "Subscribing to {topic} on {broker_uri} for the wheelchair distance state."
+                    );
+
+                    let result = receive_wheelchair_distance_state_until_shutdown(
+                        broker_uri,
+                        topic,
+                        Arc::clone(&inputs),
+                        &shutdown,
+                    )
+                    .await;
+
+                    if let Err(err) = result {
+                        warn!("Stopped receiving the wheelchair distance state due to '{err}'");
+                    }
+                }
+                Some(Err(status)) => {
+                    debug!(
+                        "Unable to subscribe to {} due to '{status:?}'",
+                        car_v1::car::wheelchair_distance_state::NAME
+                    );
+                }
+            }
+
+            if inputs.is_closed() || shutdown.is_triggered() {
+                break;
+            }
+
+            if shutdown
+                .run_until(sleep(Duration::from_millis(retry_interval_ms)))
+                .await
+                .is_none()
+            {
+                break;
+            }
+        }
+
+        debug!("Stopped receiving the wheelchair distance state.");
+    })
+}
@@ -0,0 +1,219 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+mod assistant_inputs;
+
+use std::sync::Arc;
+
+use wheelchair_digital_twin_model::assistant_state::{AssistantEvent, AssistantState};
+use wheelchair_digital_twin_model::car_v1;
+use wheelchair_digital_twin_providers_common::managed_subscribe_provider::{
+    run_managed_subscribe_provider, ManagedSubscribeEntity,
+};
+use wheelchair_digital_twin_providers_common::retry::RetryPolicy;
+use wheelchair_digital_twin_providers_common::settings::{
+    load_settings, validate_non_zero, ManagedSubscribeProviderSettings, ProviderSettings,
+    RetrySettings, SettingsError, ValidateSettings,
+};
+use wheelchair_digital_twin_providers_common::shutdown::DRAIN_TIMEOUT;
+
+use env_logger::{Builder, Target};
+use log::{debug, info, warn, LevelFilter};
+use serde_derive::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tokio::time::timeout;
+
+use crate::assistant_inputs::{
+    start_car_state_polling, start_wheelchair_distance_state_subscription, AssistantInputs,
+};
+
+const DEFAULT_CHARIOTT_SERVICE_DISCOVERY_URI: &str = "http://0.0.0.0:50000";
+const DEFAULT_PROVIDER_AUTHORITY: &str = "0.0.0.0:4100";
+
+const DEFAULT_MIN_INTERVAL_MS: u64 = 100;
+const DEFAULT_CAR_STATE_POLL_INTERVAL_MS: u64 = 500;
+const DEFAULT_SUBSCRIPTION_RETRY_INTERVAL_MS: u64 = 5000;
+
+/// Settings of the wheelchair assistant state provider.
+#[derive(Debug, Serialize, Deserialize)]
+struct Settings {
+    #[serde(flatten)]
+    managed_subscribe: ManagedSubscribeProviderSettings,
+    /// The interval for polling the car's lock and ignition state.
+    car_state_poll_interval_ms: u64,
+    /// The delay before subscribing again when the wheelchair distance state subscription fails.
+    subscription_retry_interval_ms: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            managed_subscribe: ManagedSubscribeProviderSettings {
+                provider: ProviderSettings {
+                    chariott_uri: DEFAULT_CHARIOTT_SERVICE_DISCOVERY_URI.to_string(),
+                    provider_authority: DEFAULT_PROVIDER_AUTHORITY.to_string(),
+                    retry: RetrySettings::default(),
+                },
+                min_interval_ms: DEFAULT_MIN_INTERVAL_MS,
+            },
+            car_state_poll_interval_ms: DEFAULT_CAR_STATE_POLL_INTERVAL_MS,
+            subscription_retry_interval_ms: DEFAULT_SUBSCRIPTION_RETRY_INTERVAL_MS,
+        }
+    }
+}
+
+impl ValidateSettings for Settings {
+    fn validate(&self) -> Result<(), SettingsError> {
+        self.managed_subscribe.validate()?;
+        validate_non_zero(
+            "car_state_poll_interval_ms",
+            self.car_state_poll_interval_ms,
+        )?;
+        validate_non_zero(
+            "subscription_retry_interval_ms",
+            self.subscription_retry_interval_ms,
+        )
+    }
+}
+
+/// Get the event that the inputs trigger in the provided state, if any.
+///
+/// # Arguments
+/// * `state` - The current state.
+/// * `inputs` - The current inputs.
+fn next_event(state: AssistantState, inputs: &AssistantInputs) -> Option<AssistantEvent> {
+    match state {
+        AssistantState::Init if inputs.is_car_unlocked => Some(AssistantEvent::CarUnlock),
+        AssistantState::Open if !inputs.is_car_unlocked => Some(AssistantEvent::CarLock),
+        AssistantState::Open if inputs.is_wheelchair_near => Some(AssistantEvent::WheelchairNear),
+        AssistantState::Hold if inputs.is_car_running => Some(AssistantEvent::CarOn),
+        AssistantState::Hold if !inputs.is_car_unlocked => Some(AssistantEvent::CarLock),
+        AssistantState::Hold if !inputs.is_wheelchair_near => Some(AssistantEvent::WheelchairFar),
+        AssistantState::Drive if !inputs.is_car_running => Some(AssistantEvent::CarOff),
+        _ => None,
+    }
+}
+
+/// Compute the state that the inputs lead to from the provided state.
+///
+/// Events are applied until the state is stable, e.g. unlocking the car while the wheelchair is
+/// already near moves from INIT through OPEN to HOLD.
+///
+/// # Arguments
+/// * `state` - The current state.
+/// * `inputs` - The current inputs.
+fn compute_state(mut state: AssistantState, inputs: &AssistantInputs) -> AssistantState {
+    while let Some(event) = next_event(state, inputs) {
+        match state.on_event(event) {
+            Ok(next_state) => {
+                info!(
+                    "Wheelchair assistant state changed from {state} to {next_state} on {event:?}"
+                );
+                state = next_state;
+            }
+            Err(err) => {
+                warn!("{err}");
+                break;
+            }
+        }
+    }
+
+    state
+}
+
+/// Start the wheelchair assistant state data stream.
+///
+/// # Arguments
+/// `inputs` - Receiver for the state machine inputs.
+fn start_wheelchair_assistant_state_data_stream(
+    mut inputs: watch::Receiver<AssistantInputs>,
+) -> watch::Receiver<AssistantState> {
+    debug!("Starting the Provider's wheelchair assistant state data stream.");
+    let (sender, receiver) = watch::channel(AssistantState::default());
+    tokio::spawn(async move {
+        while inputs.changed().await.is_ok() {
+            let current_inputs = *inputs.borrow();
+            debug!("Received new inputs {current_inputs:?}");
+
+            let state = compute_state(*sender.borrow(), &current_inputs);
+            sender.send_if_modified(|current| {
+                let modified = *current != state;
+                *current = state;
+                modified
+            });
+
+            if sender.is_closed() {
+                warn!("Stopped the wheelchair assistant state data stream, nobody is listening.");
+                break;
+            }
+        }
+    });
+
+    receiver
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Setup logging.
+    Builder::new()
+        .filter(None, LevelFilter::Info)
+        .target(Target::Stdout)
+        .init();
+
+    info!("The Provider has started.");
+
+    let settings = load_settings("wheelchair_assistant_state_provider", &Settings::default())?;
+    let provider_settings = &settings.managed_subscribe.provider;
+    let min_interval_ms = settings.managed_subscribe.min_interval_ms;
+
+    // Start the state machine, its inputs are collected once the state is registered.
+    let (inputs_sender, inputs_receiver) = watch::channel(AssistantInputs::default());
+    let inputs_sender = Arc::new(inputs_sender);
+    let data_stream = start_wheelchair_assistant_state_data_stream(inputs_receiver);
+    debug!("The Provider has started the wheelchair assistant state data stream.");
+
+    let entities = vec![ManagedSubscribeEntity::new(
+        &provider_settings.provider_authority,
+        car_v1::car::wheelchair_assistant_state::ID,
+        car_v1::car::wheelchair_assistant_state::NAME,
+        car_v1::car::wheelchair_assistant_state::DESCRIPTION,
+        data_stream,
+        min_interval_ms,
+    )];
+
+    run_managed_subscribe_provider(
+        "wheelchair_assistant_state_provider",
+        &provider_settings.chariott_uri,
+        RetryPolicy::from(&provider_settings.retry),
+        entities,
+        |provider| async move {
+            start_car_state_polling(
+                provider.invehicle_digital_twin_uri.clone(),
+                Arc::clone(&inputs_sender),
+                settings.car_state_poll_interval_ms,
+            );
+            let subscription_handle = start_wheelchair_distance_state_subscription(
+                provider.invehicle_digital_twin_uri,
+                inputs_sender,
+                min_interval_ms,
+                settings.subscription_retry_interval_ms,
+                provider.shutdown.clone(),
+            );
+
+            // The subscription runs on a blocking thread, which the runtime waits for when it is
+            // dropped.
+            provider.shutdown.wait().await;
+            if timeout(DRAIN_TIMEOUT, subscription_handle).await.is_err() {
+                warn!("The wheelchair distance state subscription did not stop in time.");
+            }
+
+            Ok(())
+        },
+    )
+    .await?;
+
+    info!("The Provider has completed.");
+
+    Ok(())
+}
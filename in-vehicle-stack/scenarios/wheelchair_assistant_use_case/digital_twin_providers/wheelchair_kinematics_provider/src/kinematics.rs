@@ -0,0 +1,422 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! Simulates a wheelchair that drives along a route around a parked car.
+//!
+//! The car's center is the origin, its front points along +y and its right side along +x.
+//! Headings and bearings are in degrees, clockwise from the car's front. The wheelchair turns
+//! towards the next waypoint at a limited turn rate and accelerates and brakes within a limited
+//! acceleration, stopping at waypoints that it waits at and at the end of its route.
+
+use std::time::Duration;
+
+use serde_derive::{Deserialize, Serialize};
+use wheelchair_digital_twin_model::approach_side::ApproachSide;
+use wheelchair_digital_twin_model::car_v1;
+
+/// The distance to a waypoint at which the wheelchair has reached it.
+const ARRIVAL_RADIUS_CM: f64 = 10.0;
+
+/// The limits of the wheelchair's motion.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MotionLimits {
+    pub max_speed_cm_s: f64,
+    pub max_acceleration_cm_s2: f64,
+    pub max_turn_rate_deg_s: f64,
+}
+
+/// The footprint of the parked car.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CarGeometry {
+    pub length_cm: f64,
+    pub width_cm: f64,
+    /// Whether the driver's door is on the left side.
+    pub left_hand_drive: bool,
+}
+
+/// The wheelchair's position, heading and speed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct WheelchairPose {
+    pub x_cm: f64,
+    pub y_cm: f64,
+    pub heading_deg: f64,
+    #[serde(default)]
+    pub speed_cm_s: f64,
+}
+
+/// A point of the route.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Waypoint {
+    pub x_cm: f64,
+    pub y_cm: f64,
+    /// The time that the wheelchair waits at the waypoint. It only stops at a waypoint that it
+    /// waits at or that ends the route.
+    #[serde(default)]
+    pub pause_ms: u64,
+}
+
+/// What the car's sensors observe of the wheelchair.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Observation {
+    pub distance_cm: car_v1::car::wheelchair_distance::TYPE,
+    pub bearing_deg: car_v1::car::wheelchair_bearing::TYPE,
+    pub approach_side: car_v1::car::wheelchair_approach_side::TYPE,
+}
+
+/// Normalize an angle to [-180, 180).
+///
+/// # Arguments
+/// * `angle_deg` - The angle.
+fn normalize_signed_deg(angle_deg: f64) -> f64 {
+    (angle_deg + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// The heading from one point to another.
+///
+/// # Arguments
+/// * `dx_cm` - The offset along the car's right side.
+/// * `dy_cm` - The offset along the car's front.
+fn heading_deg(dx_cm: f64, dy_cm: f64) -> f64 {
+    dx_cm.atan2(dy_cm).to_degrees()
+}
+
+impl CarGeometry {
+    /// Observe the wheelchair from the car.
+    ///
+    /// # Arguments
+    /// * `pose` - The wheelchair's pose.
+    pub fn observe(&self, pose: &WheelchairPose) -> Observation {
+        // The distance to the car's footprint, zero when touching it.
+        let dx = (pose.x_cm.abs() - self.width_cm / 2.0).max(0.0);
+        let dy = (pose.y_cm.abs() - self.length_cm / 2.0).max(0.0);
+        let distance_cm = dx.hypot(dy).round().min(f64::from(i32::MAX)) as i32;
+
+        let bearing_deg = heading_deg(pose.x_cm, pose.y_cm).rem_euclid(360.0);
+
+        let is_left = pose.x_cm < 0.0;
+        let approach_side = if is_left == self.left_hand_drive {
+            ApproachSide::Driver
+        } else {
+            ApproachSide::Passenger
+        };
+
+        Observation {
+            distance_cm,
+            bearing_deg,
+            approach_side,
+        }
+    }
+}
+
+/// A wheelchair driving along its route.
+#[derive(Clone, Debug)]
+pub struct WheelchairSimulator {
+    limits: MotionLimits,
+    pose: WheelchairPose,
+    route: Vec<Waypoint>,
+    next_waypoint: usize,
+    pause_left: Duration,
+}
+
+impl WheelchairSimulator {
+    /// Place the wheelchair at its start.
+    ///
+    /// # Arguments
+    /// * `limits` - The limits of the wheelchair's motion.
+    /// * `start` - The wheelchair's pose at the start.
+    /// * `route` - The waypoints to drive to, in order.
+    pub fn new(limits: MotionLimits, start: WheelchairPose, route: Vec<Waypoint>) -> Self {
+        WheelchairSimulator {
+            limits,
+            pose: start,
+            route,
+            next_waypoint: 0,
+            pause_left: Duration::ZERO,
+        }
+    }
+
+    /// The wheelchair's current pose.
+    pub fn pose(&self) -> WheelchairPose {
+        self.pose
+    }
+
+    /// Whether the wheelchair has stopped at the end of its route.
+    pub fn is_finished(&self) -> bool {
+        self.next_waypoint >= self.route.len()
+            && self.pause_left.is_zero()
+            && self.pose.speed_cm_s == 0.0
+    }
+
+    /// Advance the simulation.
+    ///
+    /// # Arguments
+    /// * `step` - The simulated time.
+    pub fn step(&mut self, step: Duration) {
+        let dt = step.as_secs_f64();
+        let max_speed_change = self.limits.max_acceleration_cm_s2 * dt;
+
+        // Pick the waypoint to drive to, skipping the ones that have been reached.
+        let target = loop {
+            let Some(waypoint) = self.route.get(self.next_waypoint).copied() else {
+                break None;
+            };
+
+            let distance = (waypoint.x_cm - self.pose.x_cm).hypot(waypoint.y_cm - self.pose.y_cm);
+            if distance > ARRIVAL_RADIUS_CM {
+                break Some((waypoint, distance));
+            }
+
+            self.next_waypoint += 1;
+            self.pause_left = Duration::from_millis(waypoint.pause_ms);
+        };
+
+        let target_speed = match target {
+            _ if !self.pause_left.is_zero() => {
+                if self.pose.speed_cm_s == 0.0 {
+                    self.pause_left = self.pause_left.saturating_sub(step);
+                }
+                0.0
+            }
+            None => 0.0,
+            Some((waypoint, distance)) => {
+                let max_turn = self.limits.max_turn_rate_deg_s * dt;
+                let desired = heading_deg(
+                    waypoint.x_cm - self.pose.x_cm,
+                    waypoint.y_cm - self.pose.y_cm,
+                );
+                let error = normalize_signed_deg(desired - self.pose.heading_deg);
+                self.pose.heading_deg =
+                    (self.pose.heading_deg + error.clamp(-max_turn, max_turn)).rem_euclid(360.0);
+
+                let stops = waypoint.pause_ms > 0 || self.next_waypoint + 1 == self.route.len();
+                let mut speed = self.limits.max_speed_cm_s;
+                if stops {
+                    // Brake in time to stop at the waypoint.
+                    speed = speed.min((2.0 * self.limits.max_acceleration_cm_s2 * distance).sqrt());
+                }
+
+                // Slow down while turning, so that the wheelchair does not circle the waypoint.
+                let remaining_error = normalize_signed_deg(desired - self.pose.heading_deg);
+                speed * remaining_error.to_radians().cos().max(0.0)
+            }
+        };
+
+        let speed_change =
+            (target_speed - self.pose.speed_cm_s).clamp(-max_speed_change, max_speed_change);
+        self.pose.speed_cm_s = (self.pose.speed_cm_s + speed_change).max(0.0);
+
+        let heading = self.pose.heading_deg.to_radians();
+        self.pose.x_cm += self.pose.speed_cm_s * dt * heading.sin();
+        self.pose.y_cm += self.pose.speed_cm_s * dt * heading.cos();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_millis(100);
+    const MAX_STEPS: usize = 10_000;
+
+    fn limits() -> MotionLimits {
+        MotionLimits {
+            max_speed_cm_s: 100.0,
+            max_acceleration_cm_s2: 50.0,
+            max_turn_rate_deg_s: 90.0,
+        }
+    }
+
+    /// A car of 4.5 m by 1.8 m.
+    ///
+    /// # Arguments
+    /// * `left_hand_drive` - Whether the driver's door is on the left side.
+    fn car(left_hand_drive: bool) -> CarGeometry {
+        CarGeometry {
+            length_cm: 450.0,
+            width_cm: 180.0,
+            left_hand_drive,
+        }
+    }
+
+    /// A wheelchair standing at a position.
+    ///
+    /// # Arguments
+    /// * `x_cm` - The offset along the car's right side.
+    /// * `y_cm` - The offset along the car's front.
+    fn at(x_cm: f64, y_cm: f64) -> WheelchairPose {
+        WheelchairPose {
+            x_cm,
+            y_cm,
+            ..Default::default()
+        }
+    }
+
+    /// A waypoint.
+    ///
+    /// # Arguments
+    /// * `x_cm` - The offset along the car's right side.
+    /// * `y_cm` - The offset along the car's front.
+    /// * `pause_ms` - The time that the wheelchair waits at the waypoint.
+    fn waypoint(x_cm: f64, y_cm: f64, pause_ms: u64) -> Waypoint {
+        Waypoint {
+            x_cm,
+            y_cm,
+            pause_ms,
+        }
+    }
+
+    /// The distance between the wheelchair and a point.
+    ///
+    /// # Arguments
+    /// * `pose` - The wheelchair's pose.
+    /// * `x_cm` - The point's offset along the car's right side.
+    /// * `y_cm` - The point's offset along the car's front.
+    fn distance_to(pose: &WheelchairPose, x_cm: f64, y_cm: f64) -> f64 {
+        (pose.x_cm - x_cm).hypot(pose.y_cm - y_cm)
+    }
+
+    #[test]
+    fn the_distance_is_measured_to_the_cars_footprint() {
+        let car = car(true);
+
+        assert_eq!(car.observe(&at(190.0, 0.0)).distance_cm, 100);
+        assert_eq!(car.observe(&at(-190.0, 100.0)).distance_cm, 100);
+        assert_eq!(car.observe(&at(0.0, 325.0)).distance_cm, 100);
+        assert_eq!(car.observe(&at(50.0, -325.0)).distance_cm, 100);
+        assert_eq!(car.observe(&at(190.0, 325.0)).distance_cm, 141);
+    }
+
+    #[test]
+    fn the_distance_is_zero_when_touching_the_car() {
+        let car = car(true);
+
+        assert_eq!(car.observe(&at(90.0, 0.0)).distance_cm, 0);
+        assert_eq!(car.observe(&at(-90.0, 225.0)).distance_cm, 0);
+        assert_eq!(car.observe(&at(0.0, 0.0)).distance_cm, 0);
+    }
+
+    #[test]
+    fn the_bearing_is_clockwise_from_the_cars_front() {
+        let car = car(true);
+        let cases = [
+            (0.0, 300.0, 0.0),
+            (300.0, 300.0, 45.0),
+            (300.0, 0.0, 90.0),
+            (300.0, -300.0, 135.0),
+            (0.0, -300.0, 180.0),
+            (-300.0, -300.0, 225.0),
+            (-300.0, 0.0, 270.0),
+            (-300.0, 300.0, 315.0),
+        ];
+
+        for (x_cm, y_cm, expected_deg) in cases {
+            let bearing_deg = car.observe(&at(x_cm, y_cm)).bearing_deg;
+            assert!(
+                (bearing_deg - expected_deg).abs() < 1e-9,
+                "({x_cm}, {y_cm}): {bearing_deg}"
+            );
+        }
+    }
+
+    #[test]
+    fn the_driver_side_depends_on_the_drive_side() {
+        let left = at(-200.0, 0.0);
+        let right = at(200.0, 0.0);
+
+        let left_hand_drive = car(true);
+        assert_eq!(
+            left_hand_drive.observe(&left).approach_side,
+            ApproachSide::Driver
+        );
+        assert_eq!(
+            left_hand_drive.observe(&right).approach_side,
+            ApproachSide::Passenger
+        );
+
+        let right_hand_drive = car(false);
+        assert_eq!(
+            right_hand_drive.observe(&left).approach_side,
+            ApproachSide::Passenger
+        );
+        assert_eq!(
+            right_hand_drive.observe(&right).approach_side,
+            ApproachSide::Driver
+        );
+    }
+
+    #[test]
+    fn the_wheelchair_waits_at_a_paused_waypoint() {
+        let start = WheelchairPose {
+            x_cm: -300.0,
+            y_cm: -600.0,
+            heading_deg: 90.0,
+            speed_cm_s: 0.0,
+        };
+        let route = vec![waypoint(-300.0, -300.0, 2000), waypoint(-300.0, 0.0, 0)];
+        let mut simulator = WheelchairSimulator::new(limits(), start, route);
+
+        // Drive until the wheelchair has stopped at the first waypoint.
+        let mut steps = 0;
+        loop {
+            simulator.step(STEP);
+            steps += 1;
+            assert!(steps < MAX_STEPS, "The wheelchair should stop");
+
+            let pose = simulator.pose();
+            if pose.speed_cm_s == 0.0 && distance_to(&pose, -300.0, -300.0) < 50.0 {
+                break;
+            }
+        }
+        let stopped_at = simulator.pose();
+        assert!(!simulator.is_finished());
+
+        // It waits for the pause before driving on.
+        for _ in 0..19 {
+            simulator.step(STEP);
+            assert_eq!(simulator.pose(), stopped_at);
+        }
+        for _ in 0..2 {
+            simulator.step(STEP);
+        }
+        assert!(simulator.pose().speed_cm_s > 0.0);
+    }
+
+    #[test]
+    fn the_wheelchair_stops_at_the_end_of_its_route() {
+        let start = WheelchairPose {
+            x_cm: 300.0,
+            y_cm: -600.0,
+            heading_deg: 0.0,
+            speed_cm_s: 0.0,
+        };
+        let route = vec![waypoint(300.0, -200.0, 0), waypoint(150.0, 0.0, 0)];
+        let mut simulator = WheelchairSimulator::new(limits(), start, route);
+
+        let mut steps = 0;
+        while !simulator.is_finished() {
+            simulator.step(STEP);
+            steps += 1;
+            assert!(steps < MAX_STEPS, "The wheelchair should finish its route");
+
+            let speed_cm_s = simulator.pose().speed_cm_s;
+            assert!((0.0..=limits().max_speed_cm_s).contains(&speed_cm_s));
+        }
+
+        let pose = simulator.pose();
+        assert_eq!(pose.speed_cm_s, 0.0);
+        assert!(distance_to(&pose, 150.0, 0.0) < 50.0);
+
+        // A finished wheelchair stays where it is.
+        simulator.step(STEP);
+        assert_eq!(simulator.pose(), pose);
+        assert!(simulator.is_finished());
+    }
+
+    #[test]
+    fn a_wheelchair_without_a_route_is_finished() {
+        let simulator = WheelchairSimulator::new(limits(), at(0.0, -500.0), vec![]);
+
+        assert!(simulator.is_finished());
+    }
+}
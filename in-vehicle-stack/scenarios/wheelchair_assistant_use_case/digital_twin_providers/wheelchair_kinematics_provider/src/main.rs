@@ -0,0 +1,303 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! Simulates a wheelchair driving around the parked car and publishes its distance, bearing and
+//! approach side, each on its own managed subscribe endpoint.
+
+use env_logger::{Builder, Target};
+use log::{debug, info, LevelFilter};
+use serde_derive::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+use wheelchair_digital_twin_model::car_v1;
+use wheelchair_digital_twin_providers_common::managed_subscribe_provider::{
+    run_managed_subscribe_provider, ManagedSubscribeEntity,
+};
+use wheelchair_digital_twin_providers_common::retry::RetryPolicy;
+use wheelchair_digital_twin_providers_common::settings::{
+    load_settings, validate_authority, validate_non_zero, validate_uri, RetrySettings,
+    SettingsError, ValidateSettings,
+};
+
+use crate::kinematics::{
+    CarGeometry, MotionLimits, Observation, Waypoint, WheelchairPose, WheelchairSimulator,
+};
+
+mod kinematics;
+
+const DEFAULT_CHARIOTT_SERVICE_DISCOVERY_URI: &str = "http://0.0.0.0:50000";
+const DEFAULT_DISTANCE_PROVIDER_AUTHORITY: &str = "0.0.0.0:4120";
+const DEFAULT_BEARING_PROVIDER_AUTHORITY: &str = "0.0.0.0:4130";
+const DEFAULT_APPROACH_SIDE_PROVIDER_AUTHORITY: &str = "0.0.0.0:4140";
+const DEFAULT_MIN_INTERVAL_MS: u64 = 100;
+const DEFAULT_STEP_MS: u64 = 50;
+
+/// Settings of the wheelchair kinematics provider.
+#[derive(Debug, Serialize, Deserialize)]
+struct Settings {
+    /// Chariott's Service Discovery URI.
+    chariott_uri: String,
+    /// How discovery, registration and the requests to the In-Vehicle Digital Twin are retried.
+    #[serde(default)]
+    retry: RetrySettings,
+    /// The authority of the wheelchair distance endpoint.
+    distance_provider_authority: String,
+    /// The authority of the wheelchair bearing endpoint.
+    bearing_provider_authority: String,
+    /// The authority of the wheelchair approach side endpoint.
+    approach_side_provider_authority: String,
+    /// The default publish interval if the subscriber does not request one.
+    min_interval_ms: u64,
+    /// The simulated time between two updates of the wheelchair's pose.
+    step_ms: u64,
+    /// Whether the wheelchair starts its route again after it has stopped at its end.
+    repeat: bool,
+    limits: MotionLimits,
+    car: CarGeometry,
+    start: WheelchairPose,
+    route: Vec<Waypoint>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        // The wheelchair comes from behind on the driver's side, waits and turns away, then comes
+        // back and stops next to the driver's door.
+        let waypoint = |x_cm, y_cm, pause_ms| Waypoint {
+            x_cm,
+            y_cm,
+            pause_ms,
+        };
+
+        Settings {
+            chariott_uri: DEFAULT_CHARIOTT_SERVICE_DISCOVERY_URI.to_string(),
+            retry: RetrySettings::default(),
+            distance_provider_authority: DEFAULT_DISTANCE_PROVIDER_AUTHORITY.to_string(),
+            bearing_provider_authority: DEFAULT_BEARING_PROVIDER_AUTHORITY.to_string(),
+            approach_side_provider_authority: DEFAULT_APPROACH_SIDE_PROVIDER_AUTHORITY.to_string(),
+            min_interval_ms: DEFAULT_MIN_INTERVAL_MS,
+            step_ms: DEFAULT_STEP_MS,
+            repeat: false,
+            limits: MotionLimits {
+                max_speed_cm_s: 120.0,
+                max_acceleration_cm_s2: 60.0,
+                max_turn_rate_deg_s: 90.0,
+            },
+            car: CarGeometry {
+                length_cm: 480.0,
+                width_cm: 190.0,
+                left_hand_drive: true,
+            },
+            start: WheelchairPose {
+                x_cm: -600.0,
+                y_cm: -900.0,
+                heading_deg: 45.0,
+                speed_cm_s: 0.0,
+            },
+            route: vec![
+                waypoint(-400.0, -300.0, 2000),
+                waypoint(-700.0, -600.0, 0),
+                waypoint(-160.0, 40.0, 0),
+            ],
+        }
+    }
+}
+
+/// Check that a setting is greater than zero.
+///
+/// # Arguments
+/// * `field` - The setting's name.
+/// * `value` - The setting's value.
+fn validate_positive(field: &str, value: f64) -> Result<(), SettingsError> {
+    if !(value.is_finite() && value > 0.0) {
+        return Err(SettingsError::Invalid {
+            field: field.to_string(),
+            message: "must be a number greater than 0".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+impl ValidateSettings for Settings {
+    fn validate(&self) -> Result<(), SettingsError> {
+        validate_uri("chariott_uri", &self.chariott_uri)?;
+        self.retry.validate()?;
+        validate_authority(
+            "distance_provider_authority",
+            &self.distance_provider_authority,
+        )?;
+        validate_authority(
+            "bearing_provider_authority",
+            &self.bearing_provider_authority,
+        )?;
+        validate_authority(
+            "approach_side_provider_authority",
+            &self.approach_side_provider_authority,
+        )?;
+        validate_non_zero("min_interval_ms", self.min_interval_ms)?;
+        validate_non_zero("step_ms", self.step_ms)?;
+        validate_positive("limits.max_speed_cm_s", self.limits.max_speed_cm_s)?;
+        validate_positive(
+            "limits.max_acceleration_cm_s2",
+            self.limits.max_acceleration_cm_s2,
+        )?;
+        validate_positive(
+            "limits.max_turn_rate_deg_s",
+            self.limits.max_turn_rate_deg_s,
+        )?;
+        validate_positive("car.length_cm", self.car.length_cm)?;
+        validate_positive("car.width_cm", self.car.width_cm)?;
+
+        if self.route.is_empty() {
+            return Err(SettingsError::Invalid {
+                field: "route".to_string(),
+                message: "must have at least one waypoint".to_string(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Send a value if it differs from the current one, so that subscribers are only woken up by
+/// changes.
+///
+/// # Arguments
+/// * `sender` - The property's sender.
+/// * `value` - The new value.
+fn send_if_changed<T: PartialEq>(sender: &watch::Sender<T>, value: T) {
+    sender.send_if_modified(|current| {
+        let modified = *current != value;
+        *current = value;
+        modified
+    });
+}
+
+/// Start the simulation, which updates the observed properties after every step.
+///
+/// # Arguments
+/// * `settings` - The settings with the wheelchair's motion, route and the car.
+/// * `distance` - The sender for the wheelchair distance.
+/// * `bearing` - The sender for the wheelchair bearing.
+/// * `approach_side` - The sender for the wheelchair approach side.
+fn start_simulation(
+    settings: &Settings,
+    distance: watch::Sender<car_v1::car::wheelchair_distance::TYPE>,
+    bearing: watch::Sender<car_v1::car::wheelchair_bearing::TYPE>,
+    approach_side: watch::Sender<car_v1::car::wheelchair_approach_side::TYPE>,
+) {
+    let new_simulator = {
+        let limits = settings.limits.clone();
+        let start = settings.start;
+        let route = settings.route.clone();
+        move || WheelchairSimulator::new(limits.clone(), start, route.clone())
+    };
+    let car = settings.car.clone();
+    let step = Duration::from_millis(settings.step_ms);
+    let repeat = settings.repeat;
+
+    info!(
+        "Simulating a wheelchair driving along {} waypoints.",
+        settings.route.len()
+    );
+
+    tokio::spawn(async move {
+        let mut ticks = interval(step);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            let mut simulator = new_simulator();
+
+            while !simulator.is_finished() {
+                ticks.tick().await;
+                simulator.step(step);
+
+                let observation = car.observe(&simulator.pose());
+                debug!("{:?} observed as {observation:?}", simulator.pose());
+                send_if_changed(&distance, observation.distance_cm);
+                send_if_changed(&bearing, observation.bearing_deg);
+                send_if_changed(&approach_side, observation.approach_side);
+            }
+
+            if !repeat {
+                info!("The wheelchair has reached the end of its route.");
+                break;
+            }
+
+            info!("The wheelchair starts its route again.");
+        }
+
+        // The last observation stays published, e.g. again after the maximum interval, until
+        // nobody receives it anymore.
+        tokio::join!(distance.closed(), bearing.closed(), approach_side.closed());
+    });
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Setup logging.
+    Builder::new()
+        .filter(None, LevelFilter::Info)
+        .target(Target::Stdout)
+        .init();
+
+    info!("The Provider has started.");
+
+    let settings = load_settings("wheelchair_kinematics_provider", &Settings::default())?;
+
+    // The properties start with what the car observes at the wheelchair's start.
+    let Observation {
+        distance_cm,
+        bearing_deg,
+        approach_side,
+    } = settings.car.observe(&settings.start);
+    let (distance, distance_stream) = watch::channel(distance_cm);
+    let (bearing, bearing_stream) = watch::channel(bearing_deg);
+    let (side, side_stream) = watch::channel(approach_side);
+
+    let entities = vec![
+        ManagedSubscribeEntity::new(
+            &settings.distance_provider_authority,
+            car_v1::car::wheelchair_distance::ID,
+            car_v1::car::wheelchair_distance::NAME,
+            car_v1::car::wheelchair_distance::DESCRIPTION,
+            distance_stream,
+            settings.min_interval_ms,
+        ),
+        ManagedSubscribeEntity::new(
+            &settings.bearing_provider_authority,
+            car_v1::car::wheelchair_bearing::ID,
+            car_v1::car::wheelchair_bearing::NAME,
+            car_v1::car::wheelchair_bearing::DESCRIPTION,
+            bearing_stream,
+            settings.min_interval_ms,
+        ),
+        ManagedSubscribeEntity::new(
+            &settings.approach_side_provider_authority,
+            car_v1::car::wheelchair_approach_side::ID,
+            car_v1::car::wheelchair_approach_side::NAME,
+            car_v1::car::wheelchair_approach_side::DESCRIPTION,
+            side_stream,
+            settings.min_interval_ms,
+        ),
+    ];
+
+    run_managed_subscribe_provider(
+        "wheelchair_kinematics_provider",
+        &settings.chariott_uri,
+        RetryPolicy::from(&settings.retry),
+        entities,
+        |_| async {
+            // Start driving once the properties can be subscribed to.
+            start_simulation(&settings, distance, bearing, side);
+
+            Ok(())
+        },
+    )
+    .await?;
+
+    info!("The Provider has completed.");
+
+    Ok(())
+}
@@ -0,0 +1,259 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! Replays a scenario timeline: the wheelchair distance is published like the distance providers
+//! do, lock and ignition changes are sent to the vehicle body provider as commands.
+
+use std::path::Path;
+
+use env_logger::{Builder, Target};
+use log::{debug, info, warn, LevelFilter};
+use serde_derive::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tokio::time::{sleep, Duration, Instant};
+use tonic::{Request, Status};
+use wheelchair_assistant_interfaces::digital_twin_invoke_provider::v1::digital_twin_invoke_provider_client::DigitalTwinInvokeProviderClient;
+use wheelchair_assistant_interfaces::digital_twin_invoke_provider::v1::InvokeRequest;
+use wheelchair_digital_twin_model::car_v1;
+use wheelchair_digital_twin_providers_common::constants::{
+    digital_twin_operation, digital_twin_protocol,
+};
+use wheelchair_digital_twin_providers_common::managed_subscribe_provider::{
+    run_managed_subscribe_provider, ManagedSubscribeEntity,
+};
+use wheelchair_digital_twin_providers_common::retry::RetryPolicy;
+use wheelchair_digital_twin_providers_common::settings::{
+    load_settings, ManagedSubscribeProviderSettings, ProviderSettings, RetrySettings, SettingsError,
+    ValidateSettings,
+};
+use wheelchair_digital_twin_providers_common::utils::discover_digital_twin_provider_using_ibeji;
+
+use crate::timeline::{Timeline, TimelineEvent};
+
+mod timeline;
+
+const DEFAULT_CHARIOTT_SERVICE_DISCOVERY_URI: &str = "http://0.0.0.0:50000";
+const DEFAULT_PROVIDER_AUTHORITY: &str = "0.0.0.0:4110";
+const DEFAULT_MIN_INTERVAL_MS: u64 = 10;
+const DEFAULT_TIMELINE_PATH: &str = "timelines/approach.yaml";
+
+/// Settings of the wheelchair scenario provider.
+#[derive(Debug, Serialize, Deserialize)]
+struct Settings {
+    #[serde(flatten)]
+    managed_subscribe: ManagedSubscribeProviderSettings,
+    /// The YAML or CSV timeline to replay.
+    timeline_path: String,
+    /// Whether the timeline starts again after its last event.
+    repeat: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            managed_subscribe: ManagedSubscribeProviderSettings {
+                provider: ProviderSettings {
+                    chariott_uri: DEFAULT_CHARIOTT_SERVICE_DISCOVERY_URI.to_string(),
+                    provider_authority: DEFAULT_PROVIDER_AUTHORITY.to_string(),
+                    retry: RetrySettings::default(),
+                },
+                min_interval_ms: DEFAULT_MIN_INTERVAL_MS,
+            },
+            timeline_path: DEFAULT_TIMELINE_PATH.to_string(),
+            repeat: false,
+        }
+    }
+}
+
+impl ValidateSettings for Settings {
+    fn validate(&self) -> Result<(), SettingsError> {
+        self.managed_subscribe.validate()?;
+
+        if self.timeline_path.is_empty() {
+            return Err(SettingsError::Invalid {
+                field: "timeline_path".to_string(),
+                message: "must not be empty".to_string(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Invoke a command of the vehicle body provider that Ibeji has registered for it.
+///
+/// # Arguments
+/// * `invehicle_digital_twin_uri` - The In-Vehicle Digital Twin URI.
+/// * `command_id` - The command's entity id.
+async fn invoke_command(invehicle_digital_twin_uri: &str, command_id: &str) -> Result<(), Status> {
+    let endpoint_info = discover_digital_twin_provider_using_ibeji(
+        invehicle_digital_twin_uri,
+        command_id,
+        digital_twin_protocol::GRPC,
+        &[digital_twin_operation::INVOKE.to_string()],
+    )
+    .await?;
+
+    let mut client = DigitalTwinInvokeProviderClient::connect(endpoint_info.uri)
+        .await
+        .map_err(|err| Status::unavailable(err.to_string()))?;
+    let request = Request::new(InvokeRequest {
+        entity_id: command_id.to_string(),
+        payload: String::new(),
+    });
+    let response = client.invoke(request).await?;
+    debug!("Invoked {command_id}: {}", response.into_inner().payload);
+
+    Ok(())
+}
+
+/// Send the lock and ignition changes of an event. A change that fails is logged and skipped, so
+/// that the rest of the scenario is still played.
+///
+/// # Arguments
+/// * `invehicle_digital_twin_uri` - The In-Vehicle Digital Twin URI.
+/// * `event` - The event.
+async fn apply_event(invehicle_digital_twin_uri: &str, event: &TimelineEvent) {
+    let lock_command = event.unlocked.map(|unlocked| {
+        if unlocked {
+            car_v1::car::unlock::ID
+        } else {
+            car_v1::car::lock::ID
+        }
+    });
+    let ignition_command = event.running.map(|running| {
+        if running {
+            car_v1::car::start_engine::ID
+        } else {
+            car_v1::car::stop_engine::ID
+        }
+    });
+
+    // Unlock before starting and stop before locking, whatever the event sets first.
+    let commands = if event.running == Some(true) {
+        [lock_command, ignition_command]
+    } else {
+        [ignition_command, lock_command]
+    };
+
+    for command_id in commands.into_iter().flatten() {
+        info!("{} ms: invoking {command_id}", event.at_ms);
+        if let Err(err) = invoke_command(invehicle_digital_twin_uri, command_id).await {
+            warn!("Unable to invoke {command_id} due to '{err}'");
+        }
+    }
+}
+
+/// Start replaying the timeline.
+///
+/// # Arguments
+/// * `timeline` - The timeline.
+/// * `invehicle_digital_twin_uri` - The In-Vehicle Digital Twin URI.
+/// * `distance` - The sender for the wheelchair distance.
+/// * `tick_ms` - The interval for updating the distance.
+/// * `repeat` - Whether the timeline starts again after its last event.
+fn start_timeline(
+    timeline: Timeline,
+    invehicle_digital_twin_uri: String,
+    distance: watch::Sender<i32>,
+    tick_ms: u64,
+    repeat: bool,
+) {
+    info!(
+        "Playing a timeline of {} events over {} ms.",
+        timeline.events().len(),
+        timeline.duration_ms()
+    );
+
+    tokio::spawn(async move {
+        loop {
+            let start = Instant::now();
+            let mut pending_events = timeline.events().iter().peekable();
+
+            loop {
+                let elapsed_ms = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX);
+
+                while let Some(event) = pending_events.next_if(|event| event.at_ms <= elapsed_ms) {
+                    apply_event(&invehicle_digital_twin_uri, event).await;
+                }
+
+                let distance_cm = timeline.distance_at(elapsed_ms);
+                distance.send_if_modified(|current| {
+                    let modified = *current != distance_cm;
+                    *current = distance_cm;
+                    modified
+                });
+
+                if pending_events.peek().is_none() && elapsed_ms >= timeline.duration_ms() {
+                    break;
+                }
+
+                sleep(Duration::from_millis(tick_ms)).await;
+            }
+
+            if !repeat {
+                info!("The timeline has finished.");
+                break;
+            }
+
+            info!("The timeline starts again.");
+        }
+
+        // The last distance stays published, e.g. again after the maximum interval, until nobody
+        // receives it anymore.
+        distance.closed().await;
+    });
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Setup logging.
+    Builder::new()
+        .filter(None, LevelFilter::Info)
+        .target(Target::Stdout)
+        .init();
+
+    info!("The Provider has started.");
+
+    let settings = load_settings("wheelchair_scenario_provider", &Settings::default())?;
+    let provider_settings = &settings.managed_subscribe.provider;
+    let min_interval_ms = settings.managed_subscribe.min_interval_ms;
+
+    let timeline = Timeline::load(Path::new(&settings.timeline_path))?;
+    info!("Loaded the timeline {}", settings.timeline_path);
+
+    let (distance, data_stream) = watch::channel(timeline.distance_at(0));
+    let entities = vec![ManagedSubscribeEntity::new(
+        &provider_settings.provider_authority,
+        car_v1::car::wheelchair_distance::ID,
+        car_v1::car::wheelchair_distance::NAME,
+        car_v1::car::wheelchair_distance::DESCRIPTION,
+        data_stream,
+        min_interval_ms,
+    )];
+
+    run_managed_subscribe_provider(
+        "wheelchair_scenario_provider",
+        &provider_settings.chariott_uri,
+        RetryPolicy::from(&provider_settings.retry),
+        entities,
+        |provider| async move {
+            // Start the scenario once the distance can be subscribed to.
+            start_timeline(
+                timeline,
+                provider.invehicle_digital_twin_uri,
+                distance,
+                min_interval_ms,
+                settings.repeat,
+            );
+
+            Ok(())
+        },
+    )
+    .await?;
+
+    info!("The Provider has completed.");
+
+    Ok(())
+}
@@ -0,0 +1,502 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! A scenario timeline of wheelchair distances and car lock and ignition changes.
+//!
+//! Every event happens `at_ms` milliseconds after the start of the scenario and sets any of
+//! `distance_cm`, `unlocked` and `running`. Distances are interpolated linearly between two events,
+//! lock and ignition changes happen at their event. A timeline is read from YAML:
+//!
+//! ```yaml
+//! events:
+//!   - at_ms: 0
+//!     distance_cm: 700
+//!   - at_ms: 1000
+//!     unlocked: true
+//!   - at_ms: 7000
+//!     distance_cm: 0
+//! ```
+//!
+//! or from CSV with a header and empty cells for the values that an event does not set:
+//!
+//! ```csv
+//! at_ms,distance_cm,unlocked,running
+//! 0,700,,
+//! 1000,,true,
+//! 7000,0,,
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use config::{Config, File, FileFormat};
+use serde_derive::Deserialize;
+
+/// An event of the timeline.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+pub struct TimelineEvent {
+    /// The time of the event since the start of the scenario.
+    pub at_ms: u64,
+    /// The wheelchair's distance to the car.
+    pub distance_cm: Option<i32>,
+    /// Whether the car is unlocked from this event on.
+    pub unlocked: Option<bool>,
+    /// Whether the engine is running from this event on.
+    pub running: Option<bool>,
+}
+
+/// Errors for loading a timeline.
+#[derive(Debug)]
+pub enum TimelineError {
+    /// The file could not be read or parsed.
+    Load(String),
+    /// An event is not valid.
+    Invalid { event: usize, message: String },
+}
+
+impl fmt::Display for TimelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimelineError::Load(message) => write!(f, "Unable to load the timeline: {message}"),
+            TimelineError::Invalid { event, message } => {
+                write!(f, "Invalid timeline event {event}: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TimelineError {}
+
+#[derive(Debug, Deserialize)]
+struct TimelineFile {
+    events: Vec<TimelineEvent>,
+}
+
+/// A validated timeline, ordered by time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Timeline {
+    events: Vec<TimelineEvent>,
+}
+
+impl Timeline {
+    /// Load a timeline from a ".yaml", ".yml" or ".csv" file.
+    ///
+    /// # Arguments
+    /// * `path` - The file's path.
+    pub fn load(path: &Path) -> Result<Self, TimelineError> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+
+        let events = match extension.as_deref() {
+            Some("yaml") | Some("yml") => {
+                let file: TimelineFile = Config::builder()
+                    .add_source(File::from(path).format(FileFormat::Yaml))
+                    .build()
+                    .and_then(|config| config.try_deserialize())
+                    .map_err(|err| TimelineError::Load(err.to_string()))?;
+                file.events
+            }
+            Some("csv") => {
+                let content = fs::read_to_string(path)
+                    .map_err(|err| TimelineError::Load(format!("{path:?}: {err}")))?;
+                parse_csv(&content)?
+            }
+            _ => {
+                return Err(TimelineError::Load(format!(
+                    "{path:?} is neither a YAML nor a CSV file"
+                )))
+            }
+        };
+
+        Timeline::new(events)
+    }
+
+    /// Create a timeline. The events must be ordered by time and at least one of them must set a
+    /// distance.
+    ///
+    /// # Arguments
+    /// * `events` - The events.
+    pub fn new(events: Vec<TimelineEvent>) -> Result<Self, TimelineError> {
+        for (index, window) in events.windows(2).enumerate() {
+            if window[1].at_ms < window[0].at_ms {
+                return Err(TimelineError::Invalid {
+                    event: index + 1,
+                    message: format!(
+                        "at_ms {} is before the previous event's {}",
+                        window[1].at_ms, window[0].at_ms
+                    ),
+                });
+            }
+        }
+
+        for (index, event) in events.iter().enumerate() {
+            if event.distance_cm.is_some_and(|distance| distance < 0) {
+                return Err(TimelineError::Invalid {
+                    event: index,
+                    message: "distance_cm must not be negative".to_string(),
+                });
+            }
+        }
+
+        if !events.iter().any(|event| event.distance_cm.is_some()) {
+            return Err(TimelineError::Invalid {
+                event: 0,
+                message: "no event sets distance_cm".to_string(),
+            });
+        }
+
+        Ok(Timeline { events })
+    }
+
+    /// The events, ordered by time.
+    pub fn events(&self) -> &[TimelineEvent] {
+        &self.events
+    }
+
+    /// The time of the last event.
+    pub fn duration_ms(&self) -> u64 {
+        self.events.last().map_or(0, |event| event.at_ms)
+    }
+
+    /// The wheelchair's distance at a point in time, interpolated between the events that set it.
+    /// Before the first and after the last distance the distance does not change.
+    ///
+    /// # Arguments
+    /// * `at_ms` - The time since the start of the scenario.
+    pub fn distance_at(&self, at_ms: u64) -> i32 {
+        let mut distances = self
+            .events
+            .iter()
+            .filter_map(|event| event.distance_cm.map(|distance| (event.at_ms, distance)));
+
+        // There is at least one distance, the timeline has been validated.
+        let mut previous = distances.next().unwrap_or_default();
+        if at_ms <= previous.0 {
+            return previous.1;
+        }
+
+        for next in distances {
+            if at_ms < next.0 {
+                let progress = i64::try_from(at_ms - previous.0).unwrap_or(i64::MAX);
+                let span = i64::try_from(next.0 - previous.0).unwrap_or(i64::MAX);
+                let change = i64::from(next.1) - i64::from(previous.1);
+                let distance = i64::from(previous.1) + change * progress / span;
+
+                return i32::try_from(distance).unwrap_or(next.1);
+            }
+
+            previous = next;
+        }
+
+        previous.1
+    }
+}
+
+/// Parse an optional cell of a CSV row.
+///
+/// # Arguments
+/// * `row` - The row's number, used in errors.
+/// * `column` - The column's name, used in errors.
+/// * `cell` - The cell, empty if the event does not set the value.
+fn parse_cell<T: FromStr>(
+    row: usize,
+    column: &str,
+    cell: Option<&str>,
+) -> Result<Option<T>, TimelineError>
+where
+    T::Err: fmt::Display,
+{
+    match cell.map(str::trim).filter(|cell| !cell.is_empty()) {
+        Some(cell) => cell
+            .parse()
+            .map(Some)
+            .map_err(|err| TimelineError::Invalid {
+                event: row,
+                message: format!("{column} '{cell}': {err}"),
+            }),
+        None => Ok(None),
+    }
+}
+
+/// Parse the events of a CSV timeline. Empty lines and lines starting with '#' are skipped.
+///
+/// # Arguments
+/// * `content` - The CSV content, starting with a header.
+fn parse_csv(content: &str) -> Result<Vec<TimelineEvent>, TimelineError> {
+    let mut lines = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+    let header: Vec<&str> = lines
+        .next()
+        .ok_or_else(|| TimelineError::Load("The CSV file has no header".to_string()))?
+        .split(',')
+        .map(str::trim)
+        .collect();
+    let column = |name: &str| header.iter().position(|column| *column == name);
+
+    let at_ms_column = column("at_ms")
+        .ok_or_else(|| TimelineError::Load("The CSV header has no at_ms column".to_string()))?;
+    let distance_column = column("distance_cm");
+    let unlocked_column = column("unlocked");
+    let running_column = column("running");
+
+    lines
+        .enumerate()
+        .map(|(row, line)| {
+            let cells: Vec<&str> = line.split(',').collect();
+            let cell = |index: Option<usize>| index.and_then(|index| cells.get(index).copied());
+
+            Ok(TimelineEvent {
+                at_ms: parse_cell(row, "at_ms", cell(Some(at_ms_column)))?.ok_or_else(|| {
+                    TimelineError::Invalid {
+                        event: row,
+                        message: "at_ms is missing".to_string(),
+                    }
+                })?,
+                distance_cm: parse_cell(row, "distance_cm", cell(distance_column))?,
+                unlocked: parse_cell(row, "unlocked", cell(unlocked_column))?,
+                running: parse_cell(row, "running", cell(running_column))?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    /// The path of a timeline that ships with the provider.
+    ///
+    /// # Arguments
+    /// * `name` - The timeline's file name.
+    fn shipped_timeline(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("timelines")
+            .join(name)
+    }
+
+    /// Create an event that sets the distance.
+    ///
+    /// # Arguments
+    /// * `at_ms` - The time of the event.
+    /// * `distance_cm` - The distance.
+    fn distance(at_ms: u64, distance_cm: i32) -> TimelineEvent {
+        TimelineEvent {
+            at_ms,
+            distance_cm: Some(distance_cm),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn empty_csv_cells_leave_the_values_unset() {
+        let events = parse_csv(
+            "at_ms,distance_cm,unlocked,running\n\
+             # A comment.\n\
+             0,700,,\n\
+             \n\
+             1000,,true,\n\
+             2000, , ,false\n\
+             3000",
+        )
+        .unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                distance(0, 700),
+                TimelineEvent {
+                    at_ms: 1000,
+                    unlocked: Some(true),
+                    ..Default::default()
+                },
+                TimelineEvent {
+                    at_ms: 2000,
+                    running: Some(false),
+                    ..Default::default()
+                },
+                TimelineEvent {
+                    at_ms: 3000,
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn the_csv_columns_are_found_by_their_header() {
+        let events = parse_csv("running, at_ms\ntrue, 500").unwrap();
+
+        assert_eq!(
+            events,
+            vec![TimelineEvent {
+                at_ms: 500,
+                running: Some(true),
+                ..Default::default()
+            }]
+        );
+    }
+
+    #[test]
+    fn a_csv_event_without_at_ms_is_rejected() {
+        assert!(matches!(
+            parse_csv("at_ms,distance_cm\n0,700\n,600"),
+            Err(TimelineError::Invalid { event: 1, .. })
+        ));
+        assert!(matches!(
+            parse_csv("distance_cm\n700"),
+            Err(TimelineError::Load(_))
+        ));
+        assert!(matches!(
+            parse_csv("# Nothing"),
+            Err(TimelineError::Load(_))
+        ));
+    }
+
+    #[test]
+    fn an_invalid_csv_cell_is_rejected() {
+        assert!(matches!(
+            parse_csv("at_ms,unlocked\n0,yes"),
+            Err(TimelineError::Invalid { event: 0, .. })
+        ));
+        assert!(matches!(
+            parse_csv("at_ms,distance_cm\n-5,700"),
+            Err(TimelineError::Invalid { event: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn unordered_events_are_rejected() {
+        assert!(matches!(
+            Timeline::new(vec![
+                distance(0, 700),
+                distance(2000, 300),
+                distance(1000, 0)
+            ]),
+            Err(TimelineError::Invalid { event: 2, .. })
+        ));
+
+        // Events at the same time are ordered.
+        assert!(Timeline::new(vec![distance(0, 700), distance(0, 600)]).is_ok());
+    }
+
+    #[test]
+    fn negative_distances_are_rejected() {
+        assert!(matches!(
+            Timeline::new(vec![distance(0, 700), distance(1000, -1)]),
+            Err(TimelineError::Invalid { event: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn a_timeline_needs_a_distance() {
+        let unlock = TimelineEvent {
+            at_ms: 0,
+            unlocked: Some(true),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            Timeline::new(vec![unlock]),
+            Err(TimelineError::Invalid { .. })
+        ));
+        assert!(matches!(
+            Timeline::new(vec![]),
+            Err(TimelineError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn the_distance_is_interpolated_between_events() {
+        let timeline = Timeline::new(vec![
+            distance(0, 700),
+            distance(7000, 0),
+            distance(9000, 400),
+        ])
+        .unwrap();
+
+        assert_eq!(timeline.distance_at(0), 700);
+        assert_eq!(timeline.distance_at(1000), 600);
+        assert_eq!(timeline.distance_at(3500), 350);
+        assert_eq!(timeline.distance_at(7000), 0);
+        assert_eq!(timeline.distance_at(8000), 200);
+        assert_eq!(timeline.distance_at(9000), 400);
+    }
+
+    #[test]
+    fn the_distance_is_held_before_the_first_and_after_the_last_distance() {
+        let unlock = TimelineEvent {
+            at_ms: 0,
+            unlocked: Some(true),
+            ..Default::default()
+        };
+        let start = TimelineEvent {
+            at_ms: 5000,
+            running: Some(true),
+            ..Default::default()
+        };
+        let timeline = Timeline::new(vec![
+            unlock,
+            distance(1000, 500),
+            distance(2000, 100),
+            start,
+        ])
+        .unwrap();
+
+        assert_eq!(timeline.distance_at(0), 500);
+        assert_eq!(timeline.distance_at(1000), 500);
+        assert_eq!(timeline.distance_at(2000), 100);
+        assert_eq!(timeline.distance_at(5000), 100);
+        assert_eq!(timeline.distance_at(u64::MAX), 100);
+        assert_eq!(timeline.duration_ms(), 5000);
+    }
+
+    #[test]
+    fn the_approach_timeline_loads() {
+        let timeline = Timeline::load(&shipped_timeline("approach.yaml")).unwrap();
+
+        assert_eq!(timeline.events().len(), 4);
+        assert_eq!(timeline.duration_ms(), 10000);
+        assert_eq!(timeline.events()[1].unlocked, Some(true));
+        assert_eq!(timeline.events()[3].running, Some(true));
+        assert_eq!(timeline.distance_at(0), 700);
+        assert_eq!(timeline.distance_at(4000), 300);
+        assert_eq!(timeline.distance_at(10000), 0);
+    }
+
+    #[test]
+    fn the_hesitate_turn_away_return_timeline_loads() {
+        let timeline = Timeline::load(&shipped_timeline("hesitate_turn_away_return.csv")).unwrap();
+
+        assert_eq!(timeline.events().len(), 8);
+        assert_eq!(timeline.duration_ms(), 17000);
+        assert_eq!(timeline.events()[1].unlocked, Some(true));
+        assert_eq!(timeline.events()[7].running, Some(true));
+        assert_eq!(timeline.distance_at(5000), 300);
+        assert_eq!(timeline.distance_at(7000), 450);
+        assert_eq!(timeline.distance_at(8500), 600);
+        assert_eq!(timeline.distance_at(11500), 300);
+        assert_eq!(timeline.distance_at(17000), 0);
+    }
+
+    #[test]
+    fn other_file_types_are_rejected() {
+        assert!(matches!(
+            Timeline::load(Path::new("timeline.json")),
+            Err(TimelineError::Load(_))
+        ));
+        assert!(matches!(
+            Timeline::load(&shipped_timeline("missing.csv")),
+            Err(TimelineError::Load(_))
+        ));
+    }
+}
@@ -4,5 +4,7 @@
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::compile_protos("../interfaces/digital_twin_get_provider.proto")?;
+    tonic_build::compile_protos("../interfaces/digital_twin_set_provider.proto")?;
+    tonic_build::compile_protos("../interfaces/digital_twin_invoke_provider.proto")?;
     Ok(())
 }
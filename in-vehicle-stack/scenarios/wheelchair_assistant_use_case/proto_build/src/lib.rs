@@ -2,9 +2,25 @@
 // Licensed under the MIT license.
 // SPDX-License-Identifier: MIT
 
+pub mod property_value;
+
 pub mod digital_twin_get_provider {
     pub mod v1 {
         #![allow(clippy::derive_partial_eq_without_eq)]
         tonic::include_proto!("digital_twin_get_provider");
     }
 }
+
+pub mod digital_twin_set_provider {
+    pub mod v1 {
+        #![allow(clippy::derive_partial_eq_without_eq)]
+        tonic::include_proto!("digital_twin_set_provider");
+    }
+}
+
+pub mod digital_twin_invoke_provider {
+    pub mod v1 {
+        #![allow(clippy::derive_partial_eq_without_eq)]
+        tonic::include_proto!("digital_twin_invoke_provider");
+    }
+}
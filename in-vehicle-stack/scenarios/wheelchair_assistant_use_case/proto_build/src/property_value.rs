@@ -0,0 +1,294 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! Maps the value of a [`GetResponse`] or a [`SetRequest`] to the types of the vehicle model in
+//! `car_v1`, for the providers that serve or change a property and for the consumers that read or
+//! write it.
+
+use std::fmt;
+use std::time::SystemTime;
+
+use tonic::{Request, Status};
+use wheelchair_digital_twin_model::approach_side::ApproachSide;
+use wheelchair_digital_twin_model::assistant_state::AssistantState;
+
+use crate::digital_twin_get_provider::v1::digital_twin_get_provider_client::DigitalTwinGetProviderClient;
+use crate::digital_twin_get_provider::v1::get_response::Value;
+use crate::digital_twin_get_provider::v1::{GetRequest, GetResponse, Quality};
+use crate::digital_twin_set_provider::v1::{set_request, SetRequest};
+
+/// Errors for reading a property value from a [`GetResponse`] or a [`SetRequest`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PropertyValueError {
+    /// The response has no value.
+    Missing,
+    /// The value has a different type than the property.
+    TypeMismatch {
+        expected: &'static str,
+        actual: &'static str,
+    },
+    /// The value has the property's type but is not valid for it.
+    Invalid(String),
+}
+
+impl fmt::Display for PropertyValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PropertyValueError::Missing => write!(f, "The response has no value"),
+            PropertyValueError::TypeMismatch { expected, actual } => {
+                write!(f, "Expected a value of type {expected}, got {actual}")
+            }
+            PropertyValueError::Invalid(message) => write!(f, "Invalid value: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for PropertyValueError {}
+
+impl From<PropertyValueError> for Status {
+    fn from(err: PropertyValueError) -> Self {
+        Status::internal(err.to_string())
+    }
+}
+
+/// The name of a value's type, used in errors.
+///
+/// # Arguments
+/// * `value` - The value.
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::BoolValue(_) => "bool",
+        Value::Int32Value(_) => "int32",
+        Value::DoubleValue(_) => "double",
+        Value::StringValue(_) => "string",
+        Value::JsonValue(_) => "json",
+    }
+}
+
+/// A type of the vehicle model that can be carried in a [`GetResponse`] or a [`SetRequest`].
+pub trait PropertyValue: Sized {
+    /// Convert into a response value.
+    fn into_value(self) -> Value;
+
+    /// Convert from a response value.
+    ///
+    /// # Arguments
+    /// * `value` - The response value.
+    fn try_from_value(value: Value) -> Result<Self, PropertyValueError>;
+}
+
+impl PropertyValue for bool {
+    fn into_value(self) -> Value {
+        Value::BoolValue(self)
+    }
+
+    fn try_from_value(value: Value) -> Result<Self, PropertyValueError> {
+        match value {
+            Value::BoolValue(value) => Ok(value),
+            other => Err(PropertyValueError::TypeMismatch {
+                expected: "bool",
+                actual: type_name(&other),
+            }),
+        }
+    }
+}
+
+impl PropertyValue for i32 {
+    fn into_value(self) -> Value {
+        Value::Int32Value(self)
+    }
+
+    fn try_from_value(value: Value) -> Result<Self, PropertyValueError> {
+        match value {
+            Value::Int32Value(value) => Ok(value),
+            other => Err(PropertyValueError::TypeMismatch {
+                expected: "int32",
+                actual: type_name(&other),
+            }),
+        }
+    }
+}
+
+impl PropertyValue for f64 {
+    fn into_value(self) -> Value {
+        Value::DoubleValue(self)
+    }
+
+    fn try_from_value(value: Value) -> Result<Self, PropertyValueError> {
+        match value {
+            Value::DoubleValue(value) => Ok(value),
+            // Integers are widened, as DTDL allows an integer wherever a double is expected.
+            Value::Int32Value(value) => Ok(f64::from(value)),
+            other => Err(PropertyValueError::TypeMismatch {
+                expected: "double",
+                actual: type_name(&other),
+            }),
+        }
+    }
+}
+
+impl PropertyValue for String {
+    fn into_value(self) -> Value {
+        Value::StringValue(self)
+    }
+
+    fn try_from_value(value: Value) -> Result<Self, PropertyValueError> {
+        match value {
+            Value::StringValue(value) => Ok(value),
+            other => Err(PropertyValueError::TypeMismatch {
+                expected: "string",
+                actual: type_name(&other),
+            }),
+        }
+    }
+}
+
+impl PropertyValue for serde_json::Value {
+    fn into_value(self) -> Value {
+        Value::JsonValue(self.to_string())
+    }
+
+    fn try_from_value(value: Value) -> Result<Self, PropertyValueError> {
+        match value {
+            Value::JsonValue(json) => serde_json::from_str(&json)
+                .map_err(|err| PropertyValueError::Invalid(err.to_string())),
+            other => Err(PropertyValueError::TypeMismatch {
+                expected: "json",
+                actual: type_name(&other),
+            }),
+        }
+    }
+}
+
+/// Enums with an integer value schema are carried as their value.
+impl PropertyValue for AssistantState {
+    fn into_value(self) -> Value {
+        Value::Int32Value(self.into())
+    }
+
+    fn try_from_value(value: Value) -> Result<Self, PropertyValueError> {
+        let value = i32::try_from_value(value)?;
+
+        AssistantState::try_from(value).map_err(|err| PropertyValueError::Invalid(err.to_string()))
+    }
+}
+
+impl PropertyValue for ApproachSide {
+    fn into_value(self) -> Value {
+        Value::Int32Value(self.into())
+    }
+
+    fn try_from_value(value: Value) -> Result<Self, PropertyValueError> {
+        let value = i32::try_from_value(value)?;
+
+        ApproachSide::try_from(value).map_err(|err| PropertyValueError::Invalid(err.to_string()))
+    }
+}
+
+impl GetResponse {
+    /// Create a response with a value that has been sampled now.
+    ///
+    /// # Arguments
+    /// * `value` - The property's value.
+    /// * `quality` - How reliable the value is.
+    pub fn from_property<T: PropertyValue>(value: T, quality: Quality) -> Self {
+        GetResponse {
+            value: Some(value.into_value()),
+            timestamp: Some(SystemTime::now().into()),
+            quality: quality.into(),
+        }
+    }
+
+    /// Get the value as a type of the vehicle model.
+    pub fn property<T: PropertyValue>(&self) -> Result<T, PropertyValueError> {
+        let value = self.value.clone().ok_or(PropertyValueError::Missing)?;
+
+        T::try_from_value(value)
+    }
+
+    /// The time at which the provider has sampled the value, `None` if it has not set it.
+    pub fn sampled_at(&self) -> Option<SystemTime> {
+        self.timestamp
+            .clone()
+            .and_then(|timestamp| SystemTime::try_from(timestamp).ok())
+    }
+}
+
+/// A request carries its value with the same types as a response.
+impl From<set_request::Value> for Value {
+    fn from(value: set_request::Value) -> Self {
+        match value {
+            set_request::Value::BoolValue(value) => Value::BoolValue(value),
+            set_request::Value::Int32Value(value) => Value::Int32Value(value),
+            set_request::Value::DoubleValue(value) => Value::DoubleValue(value),
+            set_request::Value::StringValue(value) => Value::StringValue(value),
+            set_request::Value::JsonValue(value) => Value::JsonValue(value),
+        }
+    }
+}
+
+impl From<Value> for set_request::Value {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::BoolValue(value) => set_request::Value::BoolValue(value),
+            Value::Int32Value(value) => set_request::Value::Int32Value(value),
+            Value::DoubleValue(value) => set_request::Value::DoubleValue(value),
+            Value::StringValue(value) => set_request::Value::StringValue(value),
+            Value::JsonValue(value) => set_request::Value::JsonValue(value),
+        }
+    }
+}
+
+impl SetRequest {
+    /// Create a request that sets a property to a value.
+    ///
+    /// # Arguments
+    /// * `entity_id` - The property's entity id.
+    /// * `value` - The property's new value.
+    pub fn from_property<T: PropertyValue>(entity_id: &str, value: T) -> Self {
+        SetRequest {
+            entity_id: entity_id.to_string(),
+            value: Some(value.into_value().into()),
+        }
+    }
+
+    /// Get the new value as a type of the vehicle model.
+    pub fn property<T: PropertyValue>(&self) -> Result<T, PropertyValueError> {
+        let value = self.value.clone().ok_or(PropertyValueError::Missing)?;
+
+        T::try_from_value(value.into())
+    }
+}
+
+/// A property's value as read from its provider.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PropertyReading<T> {
+    pub value: T,
+    pub sampled_at: Option<SystemTime>,
+    pub quality: Quality,
+}
+
+/// Get a property's value from its provider.
+///
+/// # Arguments
+/// * `provider_uri` - The provider's URI.
+/// * `entity_id` - The property's entity id.
+pub async fn get_property<T: PropertyValue>(
+    provider_uri: String,
+    entity_id: &str,
+) -> Result<PropertyReading<T>, Status> {
+    let mut client = DigitalTwinGetProviderClient::connect(provider_uri)
+        .await
+        .map_err(|err| Status::unavailable(err.to_string()))?;
+    let request = Request::new(GetRequest {
+        entity_id: entity_id.to_string(),
+    });
+    let response = client.get(request).await?.into_inner();
+
+    Ok(PropertyReading {
+        value: response.property()?,
+        sampled_at: response.sampled_at(),
+        quality: response.quality(),
+    })
+}
@@ -0,0 +1,106 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! A fake of Ibeji's In-Vehicle Digital Twin Service.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use interfaces::invehicle_digital_twin::v1::invehicle_digital_twin_server::InvehicleDigitalTwin;
+use interfaces::invehicle_digital_twin::v1::{
+    EntityAccessInfo, FindByIdRequest, FindByIdResponse, RegisterRequest, RegisterResponse,
+};
+use log::debug;
+use parking_lot::RwLock;
+use tonic::{Request, Response, Status};
+use wheelchair_digital_twin_providers_common::constants::digital_twin_operation;
+
+use crate::managed_subscribe::FakeManagedSubscribe;
+
+/// Keeps the registered entities in memory.
+#[derive(Clone, Debug, Default)]
+pub struct FakeInvehicleDigitalTwin {
+    entities: Arc<RwLock<HashMap<String, EntityAccessInfo>>>,
+    managed_subscribe: Option<(FakeManagedSubscribe, String)>,
+}
+
+impl FakeInvehicleDigitalTwin {
+    /// Route managed subscribe endpoints through the managed subscribe fake, like Ibeji does when
+    /// its Managed Subscribe module is enabled.
+    ///
+    /// # Arguments
+    /// * `managed_subscribe` - The managed subscribe fake.
+    /// * `managed_subscribe_uri` - The URI that the managed subscribe fake is served on.
+    pub fn with_managed_subscribe(
+        mut self,
+        managed_subscribe: FakeManagedSubscribe,
+        managed_subscribe_uri: &str,
+    ) -> Self {
+        self.managed_subscribe = Some((managed_subscribe, managed_subscribe_uri.to_string()));
+        self
+    }
+
+    /// Get a registered entity.
+    ///
+    /// # Arguments
+    /// * `entity_id` - The entity's id.
+    pub fn entity(&self, entity_id: &str) -> Option<EntityAccessInfo> {
+        self.entities.read().get(entity_id).cloned()
+    }
+}
+
+#[tonic::async_trait]
+impl InvehicleDigitalTwin for FakeInvehicleDigitalTwin {
+    /// Find the access information of an entity.
+    ///
+    /// # Arguments
+    /// * `request` - The request with the entity's id.
+    async fn find_by_id(
+        &self,
+        request: Request<FindByIdRequest>,
+    ) -> Result<Response<FindByIdResponse>, Status> {
+        let entity_id = request.into_inner().id;
+
+        match self.entity(&entity_id) {
+            Some(entity_access_info) => Ok(Response::new(FindByIdResponse {
+                entity_access_info: Some(entity_access_info),
+            })),
+            None => Err(Status::not_found(format!(
+                "Unable to find the entity with id {entity_id}"
+            ))),
+        }
+    }
+
+    /// Register entities, replacing entities with the same id.
+    ///
+    /// # Arguments
+    /// * `request` - The request with the entities' access information.
+    async fn register(
+        &self,
+        request: Request<RegisterRequest>,
+    ) -> Result<Response<RegisterResponse>, Status> {
+        for mut entity_access_info in request.into_inner().entity_access_info_list {
+            if let Some((managed_subscribe, managed_subscribe_uri)) = &self.managed_subscribe {
+                for endpoint_info in entity_access_info.endpoint_info_list.iter_mut() {
+                    if endpoint_info
+                        .operations
+                        .iter()
+                        .any(|operation| operation == digital_twin_operation::MANAGEDSUBSCRIBE)
+                    {
+                        managed_subscribe
+                            .register_callback(&entity_access_info.id, &endpoint_info.uri);
+                        endpoint_info.uri = managed_subscribe_uri.clone();
+                    }
+                }
+            }
+
+            debug!("Registered entity {}", entity_access_info.id);
+            self.entities
+                .write()
+                .insert(entity_access_info.id.clone(), entity_access_info);
+        }
+
+        Ok(Response::new(RegisterResponse {}))
+    }
+}
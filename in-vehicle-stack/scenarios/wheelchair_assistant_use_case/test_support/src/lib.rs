@@ -0,0 +1,117 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! In-process fakes of Chariott, Ibeji and Agemo plus an embedded MQTT broker, so that the
+//! providers and applications can be tested offline with `cargo test`.
+
+pub mod invehicle_digital_twin;
+pub mod managed_subscribe;
+pub mod mqtt_broker;
+pub mod server;
+pub mod service_registry;
+
+use std::io;
+
+use interfaces::chariott::service_discovery::core::v1::service_registry_server::ServiceRegistryServer;
+use interfaces::chariott::service_discovery::core::v1::ServiceMetadata;
+use interfaces::invehicle_digital_twin::v1::invehicle_digital_twin_server::InvehicleDigitalTwinServer;
+use interfaces::module::managed_subscribe::v1::managed_subscribe_server::ManagedSubscribeServer;
+use tonic::transport::Server;
+use wheelchair_digital_twin_providers_common::constants::chariott::{
+    INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_KIND,
+    INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_REFERENCE, INVEHICLE_DIGITAL_TWIN_SERVICE_NAME,
+    INVEHICLE_DIGITAL_TWIN_SERVICE_NAMESPACE, INVEHICLE_DIGITAL_TWIN_SERVICE_VERSION,
+};
+
+use crate::invehicle_digital_twin::FakeInvehicleDigitalTwin;
+use crate::managed_subscribe::FakeManagedSubscribe;
+use crate::mqtt_broker::EmbeddedMqttBroker;
+use crate::server::{serve, ServerHandle};
+use crate::service_registry::FakeServiceRegistry;
+
+/// The services that the providers and applications depend on, wired up like the in-vehicle
+/// stack: the In-Vehicle Digital Twin Service is registered with Chariott and routes managed
+/// subscribe endpoints through the Managed Subscribe fake, which hands out topics on the broker.
+#[derive(Debug)]
+pub struct TestEnvironment {
+    pub service_registry: FakeServiceRegistry,
+    pub invehicle_digital_twin: FakeInvehicleDigitalTwin,
+    pub managed_subscribe: FakeManagedSubscribe,
+    pub broker: EmbeddedMqttBroker,
+    service_registry_server: ServerHandle,
+    invehicle_digital_twin_server: ServerHandle,
+    managed_subscribe_server: ServerHandle,
+}
+
+impl TestEnvironment {
+    /// Start all services on ephemeral ports.
+    pub async fn start() -> io::Result<Self> {
+        let broker = EmbeddedMqttBroker::start().await?;
+
+        let managed_subscribe = FakeManagedSubscribe::new(&broker.uri());
+        let managed_subscribe_server = serve(
+            Server::builder().add_service(ManagedSubscribeServer::new(managed_subscribe.clone())),
+        )
+        .await?;
+
+        let invehicle_digital_twin = FakeInvehicleDigitalTwin::default()
+            .with_managed_subscribe(managed_subscribe.clone(), &managed_subscribe_server.uri());
+        let invehicle_digital_twin_server = serve(Server::builder().add_service(
+            InvehicleDigitalTwinServer::new(invehicle_digital_twin.clone()),
+        ))
+        .await?;
+
+        let service_registry = FakeServiceRegistry::default();
+        service_registry.insert(ServiceMetadata {
+            namespace: INVEHICLE_DIGITAL_TWIN_SERVICE_NAMESPACE.to_string(),
+            name: INVEHICLE_DIGITAL_TWIN_SERVICE_NAME.to_string(),
+            version: INVEHICLE_DIGITAL_TWIN_SERVICE_VERSION.to_string(),
+            uri: invehicle_digital_twin_server.uri(),
+            communication_kind: INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_KIND.to_string(),
+            communication_reference: INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_REFERENCE
+                .to_string(),
+        });
+        let service_registry_server = serve(
+            Server::builder().add_service(ServiceRegistryServer::new(service_registry.clone())),
+        )
+        .await?;
+
+        Ok(TestEnvironment {
+            service_registry,
+            invehicle_digital_twin,
+            managed_subscribe,
+            broker,
+            service_registry_server,
+            invehicle_digital_twin_server,
+            managed_subscribe_server,
+        })
+    }
+
+    /// Chariott's Service Discovery URI.
+    pub fn chariott_uri(&self) -> String {
+        self.service_registry_server.uri()
+    }
+
+    /// The In-Vehicle Digital Twin URI.
+    pub fn invehicle_digital_twin_uri(&self) -> String {
+        self.invehicle_digital_twin_server.uri()
+    }
+
+    /// The Managed Subscribe URI.
+    pub fn managed_subscribe_uri(&self) -> String {
+        self.managed_subscribe_server.uri()
+    }
+
+    /// The broker's URI.
+    pub fn broker_uri(&self) -> String {
+        self.broker.uri()
+    }
+
+    /// Stop all services.
+    pub async fn shutdown(self) {
+        self.service_registry_server.shutdown().await;
+        self.invehicle_digital_twin_server.shutdown().await;
+        self.managed_subscribe_server.shutdown().await;
+    }
+}
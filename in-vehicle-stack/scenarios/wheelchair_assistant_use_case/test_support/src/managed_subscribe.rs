@@ -0,0 +1,168 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! A fake of Ibeji's Managed Subscribe module backed by Agemo, which hands out topics on the
+//! embedded broker and asks the providers to publish to them.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use interfaces::module::managed_subscribe::v1::managed_subscribe_callback_client::ManagedSubscribeCallbackClient;
+use interfaces::module::managed_subscribe::v1::managed_subscribe_server::ManagedSubscribe;
+use interfaces::module::managed_subscribe::v1::{
+    CallbackPayload, Constraint, SubscriptionInfo, SubscriptionInfoRequest,
+    SubscriptionInfoResponse, TopicManagementRequest,
+};
+use log::{debug, info};
+use parking_lot::RwLock;
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+use wheelchair_digital_twin_providers_common::constants::digital_twin_protocol;
+use wheelchair_digital_twin_providers_common::managed_subscribe_provider::ProviderAction;
+
+/// A topic that a provider was asked to publish to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ManagedTopic {
+    pub entity_id: String,
+    pub topic: String,
+    pub constraints: Vec<Constraint>,
+}
+
+/// Keeps the providers' callback URIs and the handed out topics in memory.
+#[derive(Clone, Debug)]
+pub struct FakeManagedSubscribe {
+    broker_uri: String,
+    callbacks: Arc<RwLock<HashMap<String, String>>>,
+    topics: Arc<RwLock<Vec<ManagedTopic>>>,
+}
+
+impl FakeManagedSubscribe {
+    /// Create the fake.
+    ///
+    /// # Arguments
+    /// * `broker_uri` - The URI of the broker that the topics are published on.
+    pub fn new(broker_uri: &str) -> Self {
+        FakeManagedSubscribe {
+            broker_uri: broker_uri.to_string(),
+            callbacks: Arc::new(RwLock::new(HashMap::new())),
+            topics: Arc::new(RwLock::new(Vec::new())),
+        }
+    }
+
+    /// Register the callback URI of the provider for an entity.
+    ///
+    /// # Arguments
+    /// * `entity_id` - The entity's id.
+    /// * `callback_uri` - The provider's managed subscribe callback URI.
+    pub fn register_callback(&self, entity_id: &str, callback_uri: &str) {
+        debug!("Registered callback {callback_uri} for {entity_id}");
+        self.callbacks
+            .write()
+            .insert(entity_id.to_string(), callback_uri.to_string());
+    }
+
+    /// Get the topics that are currently published.
+    pub fn topics(&self) -> Vec<ManagedTopic> {
+        self.topics.read().clone()
+    }
+
+    /// Send an action for a topic to the provider of the entity.
+    ///
+    /// # Arguments
+    /// * `action` - The action.
+    /// * `managed_topic` - The topic.
+    async fn send_action(
+        &self,
+        action: ProviderAction,
+        managed_topic: &ManagedTopic,
+    ) -> Result<(), Status> {
+        let callback_uri = self
+            .callbacks
+            .read()
+            .get(&managed_topic.entity_id)
+            .cloned()
+            .ok_or_else(|| {
+                Status::not_found(format!(
+                    "No provider is registered for {}",
+                    managed_topic.entity_id
+                ))
+            })?;
+
+        let mut client = ManagedSubscribeCallbackClient::connect(callback_uri)
+            .await
+            .map_err(|err| Status::unavailable(err.to_string()))?;
+
+        let request = Request::new(TopicManagementRequest {
+            action: action.to_string(),
+            payload: Some(CallbackPayload {
+                entity_id: managed_topic.entity_id.clone(),
+                topic: managed_topic.topic.clone(),
+                constraints: managed_topic.constraints.clone(),
+                subscription_info: Some(SubscriptionInfo {
+                    protocol: digital_twin_protocol::MQTT.to_string(),
+                    uri: self.broker_uri.clone(),
+                }),
+            }),
+        });
+
+        client.topic_management_cb(request).await?;
+
+        Ok(())
+    }
+
+    /// Ask the provider to stop publishing to a topic, as Agemo does when the last subscriber
+    /// has left.
+    ///
+    /// # Arguments
+    /// * `topic` - The topic.
+    pub async fn stop_publish(&self, topic: &str) -> Result<(), Status> {
+        let managed_topic = {
+            let mut topics = self.topics.write();
+            let index = topics
+                .iter()
+                .position(|managed_topic| managed_topic.topic == topic)
+                .ok_or_else(|| Status::not_found(format!("Unknown topic {topic}")))?;
+            topics.swap_remove(index)
+        };
+
+        info!("Stop publishing to {topic}");
+        self.send_action(ProviderAction::StopPublish, &managed_topic)
+            .await
+    }
+}
+
+#[tonic::async_trait]
+impl ManagedSubscribe for FakeManagedSubscribe {
+    /// Create a topic for the entity and ask its provider to publish to it.
+    ///
+    /// # Arguments
+    /// * `request` - The request with the entity id and the constraints.
+    async fn get_subscription_info(
+        &self,
+        request: Request<SubscriptionInfoRequest>,
+    ) -> Result<Response<SubscriptionInfoResponse>, Status> {
+        let request = request.into_inner();
+        let managed_topic = ManagedTopic {
+            entity_id: request.entity_id,
+            topic: Uuid::new_v4().to_string(),
+            constraints: request.constraints,
+        };
+
+        self.send_action(ProviderAction::Publish, &managed_topic)
+            .await?;
+        info!(
+            "Created topic {} for {}",
+            managed_topic.topic, managed_topic.entity_id
+        );
+
+        let response = SubscriptionInfoResponse {
+            protocol: digital_twin_protocol::MQTT.to_string(),
+            uri: self.broker_uri.clone(),
+            context: managed_topic.topic.clone(),
+        };
+        self.topics.write().push(managed_topic);
+
+        Ok(Response::new(response))
+    }
+}
@@ -0,0 +1,1044 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! A minimal in-process MQTT broker.
+//!
+//! The providers publish with MQTT 3.1.1 while the applications subscribe with MQTT 5, so the
+//! broker accepts both on the same port. It supports QoS 0 and 1, QoS 2 publishes are delivered
+//! with QoS 1. Sessions are not persisted and retained messages and wills are ignored.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use log::{debug, warn};
+use parking_lot::Mutex;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc, watch};
+
+// The packet types, the upper four bits of a packet's first byte.
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const PUBACK: u8 = 0x40;
+const PUBREC: u8 = 0x50;
+const PUBREL: u8 = 0x60;
+const PUBCOMP: u8 = 0x70;
+const SUBSCRIBE: u8 = 0x80;
+const SUBACK: u8 = 0x90;
+const UNSUBSCRIBE: u8 = 0xA0;
+const UNSUBACK: u8 = 0xB0;
+const PINGREQ: u8 = 0xC0;
+const PINGRESP: u8 = 0xD0;
+const DISCONNECT: u8 = 0xE0;
+
+const MQTT_V5: u8 = 5;
+const MAX_DELIVERY_QOS: u8 = 1;
+const MESSAGE_CHANNEL_CAPACITY: usize = 1024;
+
+/// A message that was published to the broker.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PublishedMessage {
+    pub topic: String,
+    pub payload: Vec<u8>,
+}
+
+impl PublishedMessage {
+    /// The payload as a string.
+    pub fn payload_str(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.payload)
+    }
+}
+
+/// A connected client.
+struct Session {
+    protocol_level: u8,
+    sender: mpsc::UnboundedSender<Vec<u8>>,
+    subscriptions: HashMap<String, u8>,
+    next_packet_id: u16,
+}
+
+impl Session {
+    /// Get the next packet id, skipping 0 which is not a valid packet id.
+    fn next_packet_id(&mut self) -> u16 {
+        self.next_packet_id = self.next_packet_id.wrapping_add(1).max(1);
+        self.next_packet_id
+    }
+}
+
+#[derive(Default)]
+struct BrokerState {
+    sessions: HashMap<u64, Session>,
+    next_session_id: u64,
+}
+
+/// Shared by the broker handle and the connection tasks.
+struct BrokerInner {
+    state: Mutex<BrokerState>,
+    published: Mutex<Vec<PublishedMessage>>,
+    messages: broadcast::Sender<PublishedMessage>,
+}
+
+/// An MQTT broker on an ephemeral port of the loopback interface, stopped when dropped.
+pub struct EmbeddedMqttBroker {
+    addr: SocketAddr,
+    inner: Arc<BrokerInner>,
+    shutdown: watch::Sender<bool>,
+}
+
+impl EmbeddedMqttBroker {
+    /// Start the broker.
+    pub async fn start() -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let (messages, _) = broadcast::channel(MESSAGE_CHANNEL_CAPACITY);
+        let inner = Arc::new(BrokerInner {
+            state: Mutex::new(BrokerState::default()),
+            published: Mutex::new(Vec::new()),
+            messages,
+        });
+        let (shutdown, mut shutdown_receiver) = watch::channel(false);
+
+        let accept_inner = Arc::clone(&inner);
+        let accept_shutdown = shutdown.subscribe();
+        tokio::spawn(async move {
+            loop {
+                let stream = tokio::select! {
+                    _ = shutdown_receiver.changed() => break,
+                    result = listener.accept() => match result {
+                        Ok((stream, peer)) => {
+                            debug!("The broker accepted a connection from {peer}");
+                            stream
+                        }
+                        Err(err) => {
+                            warn!("The broker failed to accept a connection due to '{err}'");
+                            continue;
+                        }
+                    },
+                };
+
+                let inner = Arc::clone(&accept_inner);
+                let shutdown = accept_shutdown.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = handle_connection(inner, stream, shutdown).await {
+                        debug!("The broker closed a connection due to '{err}'");
+                    }
+                });
+            }
+        });
+
+        debug!("The broker is listening on {addr}");
+
+        Ok(EmbeddedMqttBroker {
+            addr,
+            inner,
+            shutdown,
+        })
+    }
+
+    /// The broker's URI, e.g. "tcp://127.0.0.1:41234".
+    pub fn uri(&self) -> String {
+        format!("tcp://{}", self.addr)
+    }
+
+    /// Get all messages that were published so far.
+    pub fn published(&self) -> Vec<PublishedMessage> {
+        self.inner.published.lock().clone()
+    }
+
+    /// Receive the messages that are published from now on.
+    pub fn messages(&self) -> broadcast::Receiver<PublishedMessage> {
+        self.inner.messages.subscribe()
+    }
+
+    /// Get the number of connected clients that are subscribed to a topic.
+    ///
+    /// # Arguments
+    /// * `topic` - The topic.
+    pub fn subscriber_count(&self, topic: &str) -> usize {
+        self.inner
+            .state
+            .lock()
+            .sessions
+            .values()
+            .filter(|session| {
+                session
+                    .subscriptions
+                    .keys()
+                    .any(|filter| topic_matches(filter, topic))
+            })
+            .count()
+    }
+}
+
+impl Drop for EmbeddedMqttBroker {
+    fn drop(&mut self) {
+        let _ = self.shutdown.send(true);
+    }
+}
+
+impl std::fmt::Debug for EmbeddedMqttBroker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EmbeddedMqttBroker")
+            .field("addr", &self.addr)
+            .finish()
+    }
+}
+
+/// Does the topic match the topic filter?
+///
+/// # Arguments
+/// * `filter` - The topic filter, which may contain the '+' and '#' wildcards.
+/// * `topic` - The topic.
+pub fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut filter_levels = filter.split('/');
+    let mut topic_levels = topic.split('/');
+
+    loop {
+        match (filter_levels.next(), topic_levels.next()) {
+            (Some("#"), _) => return true,
+            (Some("+"), Some(_)) => {}
+            (Some(filter_level), Some(topic_level)) if filter_level == topic_level => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// Reads the fields of a packet body.
+struct PacketReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PacketReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        PacketReader { bytes, position: 0 }
+    }
+
+    fn has_remaining(&self) -> bool {
+        self.position < self.bytes.len()
+    }
+
+    fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let end = self.position + len;
+        let bytes = self
+            .bytes
+            .get(self.position..end)
+            .ok_or_else(|| invalid_data("The packet is too short"))?;
+        self.position = end;
+
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn binary(&mut self) -> io::Result<&'a [u8]> {
+        let len = self.u16()? as usize;
+        self.bytes(len)
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        String::from_utf8(self.binary()?.to_vec())
+            .map_err(|_| invalid_data("The string is not valid UTF-8"))
+    }
+
+    fn variable_int(&mut self) -> io::Result<usize> {
+        let mut value = 0;
+        for shift in [0, 7, 14, 21] {
+            let byte = self.u8()?;
+            value += ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(invalid_data("The variable byte integer is malformed"))
+    }
+
+    /// Skip the properties of an MQTT 5 packet.
+    fn skip_properties(&mut self) -> io::Result<()> {
+        let len = self.variable_int()?;
+        self.bytes(len).map(|_| ())
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.bytes[self.position..];
+        self.position = self.bytes.len();
+        rest
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Read a packet, returns `None` when the connection was closed.
+///
+/// # Arguments
+/// * `reader` - The connection's read half.
+async fn read_packet<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Option<(u8, Vec<u8>)>> {
+    let header = match reader.read_u8().await {
+        Ok(header) => header,
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    };
+
+    let mut remaining_length = 0;
+    for shift in [0, 7, 14, 21] {
+        let byte = reader.read_u8().await?;
+        remaining_length += ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            let mut body = vec![0; remaining_length];
+            reader.read_exact(&mut body).await?;
+            return Ok(Some((header, body)));
+        }
+    }
+
+    Err(invalid_data("The remaining length is malformed"))
+}
+
+/// Encode a packet.
+///
+/// # Arguments
+/// * `header` - The fixed header's first byte.
+/// * `body` - The variable header and payload.
+fn encode_packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    let mut len = body.len();
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+
+    packet
+}
+
+/// Encode a packet that only carries a packet id.
+///
+/// # Arguments
+/// * `header` - The fixed header's first byte.
+/// * `packet_id` - The packet id.
+fn encode_ack(header: u8, packet_id: u16) -> Vec<u8> {
+    encode_packet(header, &packet_id.to_be_bytes())
+}
+
+/// Encode a publish packet for a session.
+///
+/// # Arguments
+/// * `session` - The receiving session.
+/// * `qos` - The QoS to deliver with.
+/// * `message` - The message.
+fn encode_publish(session: &mut Session, qos: u8, message: &PublishedMessage) -> Vec<u8> {
+    let mut body = Vec::with_capacity(message.topic.len() + message.payload.len() + 5);
+    body.extend_from_slice(&(message.topic.len() as u16).to_be_bytes());
+    body.extend_from_slice(message.topic.as_bytes());
+    if qos > 0 {
+        body.extend_from_slice(&session.next_packet_id().to_be_bytes());
+    }
+    if session.protocol_level == MQTT_V5 {
+        // No properties.
+        body.push(0);
+    }
+    body.extend_from_slice(&message.payload);
+
+    encode_packet(PUBLISH | (qos << 1), &body)
+}
+
+/// Deliver a message to all subscribed sessions.
+///
+/// # Arguments
+/// * `inner` - The broker.
+/// * `qos` - The QoS that the message was published with.
+/// * `message` - The message.
+fn route(inner: &BrokerInner, qos: u8, message: PublishedMessage) {
+    {
+        let mut state = inner.state.lock();
+        for session in state.sessions.values_mut() {
+            let granted_qos = session
+                .subscriptions
+                .iter()
+                .filter(|(filter, _)| topic_matches(filter, &message.topic))
+                .map(|(_, granted_qos)| *granted_qos)
+                .max();
+
+            if let Some(granted_qos) = granted_qos {
+                let packet = encode_publish(session, qos.min(granted_qos), &message);
+                let _ = session.sender.send(packet);
+            }
+        }
+    }
+
+    inner.published.lock().push(message.clone());
+    let _ = inner.messages.send(message);
+}
+
+/// Parse a connect packet and get the protocol level.
+///
+/// # Arguments
+/// * `body` - The packet body.
+fn parse_connect(body: &[u8]) -> io::Result<u8> {
+    let mut reader = PacketReader::new(body);
+    let _protocol_name = reader.string()?;
+    let protocol_level = reader.u8()?;
+    let flags = reader.u8()?;
+    let _keep_alive = reader.u16()?;
+    if protocol_level == MQTT_V5 {
+        reader.skip_properties()?;
+    }
+
+    let client_id = reader.string()?;
+    if flags & 0x04 != 0 {
+        if protocol_level == MQTT_V5 {
+            reader.skip_properties()?;
+        }
+        let _will_topic = reader.string()?;
+        let _will_payload = reader.binary()?;
+    }
+    if flags & 0x80 != 0 {
+        let _user_name = reader.binary()?;
+    }
+    if flags & 0x40 != 0 {
+        let _password = reader.binary()?;
+    }
+
+    debug!("Client '{client_id}' connected with protocol level {protocol_level}");
+
+    Ok(protocol_level)
+}
+
+/// Handle a packet of a connected session, returns false if the session ends.
+///
+/// # Arguments
+/// * `inner` - The broker.
+/// * `session_id` - The session's id.
+/// * `protocol_level` - The session's protocol level.
+/// * `sender` - The sender for packets to the session.
+/// * `header` - The fixed header's first byte.
+/// * `body` - The packet body.
+fn handle_packet(
+    inner: &BrokerInner,
+    session_id: u64,
+    protocol_level: u8,
+    sender: &mpsc::UnboundedSender<Vec<u8>>,
+    header: u8,
+    body: &[u8],
+) -> io::Result<bool> {
+    let mut reader = PacketReader::new(body);
+
+    match header & 0xF0 {
+        PUBLISH => {
+            let qos = (header >> 1) & 0x03;
+            let topic = reader.string()?;
+            let packet_id = if qos > 0 { Some(reader.u16()?) } else { None };
+            if protocol_level == MQTT_V5 {
+                reader.skip_properties()?;
+            }
+            let payload = reader.rest().to_vec();
+
+            route(
+                inner,
+                qos.min(MAX_DELIVERY_QOS),
+                PublishedMessage { topic, payload },
+            );
+
+            match (qos, packet_id) {
+                (1, Some(packet_id)) => {
+                    let _ = sender.send(encode_ack(PUBACK, packet_id));
+                }
+                (2, Some(packet_id)) => {
+                    let _ = sender.send(encode_ack(PUBREC, packet_id));
+                }
+                _ => {}
+            }
+        }
+        PUBREL => {
+            let packet_id = reader.u16()?;
+            let _ = sender.send(encode_ack(PUBCOMP, packet_id));
+        }
+        SUBSCRIBE => {
+            let packet_id = reader.u16()?;
+            if protocol_level == MQTT_V5 {
+                reader.skip_properties()?;
+            }
+
+            let mut body = packet_id.to_be_bytes().to_vec();
+            if protocol_level == MQTT_V5 {
+                body.push(0);
+            }
+
+            let mut state = inner.state.lock();
+            let session = state
+                .sessions
+                .get_mut(&session_id)
+                .ok_or_else(|| invalid_data("The session is gone"))?;
+            while reader.has_remaining() {
+                let filter = reader.string()?;
+                let granted_qos = (reader.u8()? & 0x03).min(MAX_DELIVERY_QOS);
+                debug!("Session {session_id} subscribed to {filter}");
+                session.subscriptions.insert(filter, granted_qos);
+                body.push(granted_qos);
+            }
+
+            let _ = sender.send(encode_packet(SUBACK, &body));
+        }
+        UNSUBSCRIBE => {
+            let packet_id = reader.u16()?;
+            if protocol_level == MQTT_V5 {
+                reader.skip_properties()?;
+            }
+
+            let mut body = packet_id.to_be_bytes().to_vec();
+            if protocol_level == MQTT_V5 {
+                body.push(0);
+            }
+
+            let mut state = inner.state.lock();
+            let session = state
+                .sessions
+                .get_mut(&session_id)
+                .ok_or_else(|| invalid_data("The session is gone"))?;
+            while reader.has_remaining() {
+                let filter = reader.string()?;
+                let existed = session.subscriptions.remove(&filter).is_some();
+                if protocol_level == MQTT_V5 {
+                    // Success or "no subscription existed".
+                    body.push(if existed { 0x00 } else { 0x11 });
+                }
+            }
+
+            let _ = sender.send(encode_packet(UNSUBACK, &body));
+        }
+        PINGREQ => {
+            let _ = sender.send(encode_packet(PINGRESP, &[]));
+        }
+        DISCONNECT => return Ok(false),
+        // Acknowledgements of the messages that the broker delivered need no handling.
+        _ => {}
+    }
+
+    Ok(true)
+}
+
+/// Serve a client until it disconnects or the broker is shut down.
+///
+/// # Arguments
+/// * `inner` - The broker.
+/// * `stream` - The client's connection.
+/// * `shutdown` - Receiver for the broker's shutdown.
+async fn handle_connection(
+    inner: Arc<BrokerInner>,
+    stream: TcpStream,
+    mut shutdown: watch::Receiver<bool>,
+) -> io::Result<()> {
+    let (mut read_half, mut write_half) = stream.into_split();
+
+    let protocol_level = match read_packet(&mut read_half).await? {
+        Some((CONNECT, body)) => parse_connect(&body)?,
+        Some(_) => return Err(invalid_data("The first packet must be CONNECT")),
+        None => return Ok(()),
+    };
+
+    let (sender, mut receiver) = mpsc::unbounded_channel::<Vec<u8>>();
+    let writer = tokio::spawn(async move {
+        while let Some(packet) = receiver.recv().await {
+            if write_half.write_all(&packet).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    // Session present is 0, the return code is "accepted".
+    let connack = if protocol_level == MQTT_V5 {
+        encode_packet(CONNACK, &[0, 0, 0])
+    } else {
+        encode_packet(CONNACK, &[0, 0])
+    };
+    let _ = sender.send(connack);
+
+    let session_id = {
+        let mut state = inner.state.lock();
+        let session_id = state.next_session_id;
+        state.next_session_id += 1;
+        state.sessions.insert(
+            session_id,
+            Session {
+                protocol_level,
+                sender: sender.clone(),
+                subscriptions: HashMap::new(),
+                next_packet_id: 0,
+            },
+        );
+        session_id
+    };
+
+    let result = loop {
+        let packet = tokio::select! {
+            _ = shutdown.changed() => break Ok(()),
+            packet = read_packet(&mut read_half) => packet,
+        };
+
+        match packet {
+            Ok(Some((header, body))) => {
+                match handle_packet(&inner, session_id, protocol_level, &sender, header, &body) {
+                    Ok(true) => {}
+                    Ok(false) => break Ok(()),
+                    Err(err) => break Err(err),
+                }
+            }
+            Ok(None) => break Ok(()),
+            Err(err) => break Err(err),
+        }
+    };
+
+    // The writer stops once the last sender for the session is gone.
+    inner.state.lock().sessions.remove(&session_id);
+    drop(sender);
+    let _ = writer.await;
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MQTT_V3_1_1: u8 = 4;
+
+    /// Encode a length-prefixed string.
+    ///
+    /// # Arguments
+    /// * `value` - The string.
+    fn string(value: &str) -> Vec<u8> {
+        let mut bytes = (value.len() as u16).to_be_bytes().to_vec();
+        bytes.extend_from_slice(value.as_bytes());
+        bytes
+    }
+
+    /// Create a broker state without connections.
+    fn broker() -> BrokerInner {
+        let (messages, _) = broadcast::channel(MESSAGE_CHANNEL_CAPACITY);
+        BrokerInner {
+            state: Mutex::new(BrokerState::default()),
+            published: Mutex::new(Vec::new()),
+            messages,
+        }
+    }
+
+    /// Add a session to the broker and get the receiver for the packets that are sent to it.
+    ///
+    /// # Arguments
+    /// * `inner` - The broker.
+    /// * `protocol_level` - The session's protocol level.
+    /// * `subscriptions` - The session's topic filters and their granted QoS.
+    fn connect(
+        inner: &BrokerInner,
+        protocol_level: u8,
+        subscriptions: &[(&str, u8)],
+    ) -> (u64, mpsc::UnboundedReceiver<Vec<u8>>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let mut state = inner.state.lock();
+        let session_id = state.next_session_id;
+        state.next_session_id += 1;
+        state.sessions.insert(
+            session_id,
+            Session {
+                protocol_level,
+                sender,
+                subscriptions: subscriptions
+                    .iter()
+                    .map(|(filter, qos)| (filter.to_string(), *qos))
+                    .collect(),
+                next_packet_id: 0,
+            },
+        );
+
+        (session_id, receiver)
+    }
+
+    /// Let a session handle a packet and get the packets that it answers with.
+    ///
+    /// # Arguments
+    /// * `inner` - The broker.
+    /// * `protocol_level` - The session's protocol level.
+    /// * `header` - The fixed header's first byte.
+    /// * `body` - The packet body.
+    fn answers(inner: &BrokerInner, protocol_level: u8, header: u8, body: &[u8]) -> Vec<Vec<u8>> {
+        let (session_id, mut receiver) = connect(inner, protocol_level, &[]);
+        let sender = inner.state.lock().sessions[&session_id].sender.clone();
+
+        let session_continues =
+            handle_packet(inner, session_id, protocol_level, &sender, header, body)
+                .expect("The packet should be handled");
+        assert!(session_continues);
+
+        let mut packets = Vec::new();
+        while let Ok(packet) = receiver.try_recv() {
+            packets.push(packet);
+        }
+        packets
+    }
+
+    /// Create a message.
+    ///
+    /// # Arguments
+    /// * `topic` - The message's topic.
+    /// * `payload` - The message's payload.
+    fn message(topic: &str, payload: &str) -> PublishedMessage {
+        PublishedMessage {
+            topic: topic.to_string(),
+            payload: payload.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn topic_filters_match_levels_and_wildcards() {
+        let cases = [
+            ("a/b", "a/b", true),
+            ("a/b", "a/c", false),
+            ("a/b", "a/b/c", false),
+            ("a/b/c", "a/b", false),
+            ("a/+", "a/b", true),
+            ("a/+", "a", false),
+            ("a/+/c", "a/b/c", true),
+            ("a/+/c", "a/b/d", false),
+            ("+", "a", true),
+            ("+", "a/b", false),
+            ("a/#", "a", true),
+            ("a/#", "a/b/c", true),
+            ("a/#", "b/c", false),
+            ("#", "a/b", true),
+            ("a", "A", false),
+        ];
+
+        for (filter, topic, expected) in cases {
+            assert_eq!(topic_matches(filter, topic), expected, "{filter} {topic}");
+        }
+    }
+
+    #[test]
+    fn the_remaining_length_is_encoded_as_variable_byte_integer() {
+        let cases: [(usize, &[u8]); 6] = [
+            (0, &[0x00]),
+            (127, &[0x7f]),
+            (128, &[0x80, 0x01]),
+            (16_383, &[0xff, 0x7f]),
+            (16_384, &[0x80, 0x80, 0x01]),
+            (2_097_152, &[0x80, 0x80, 0x80, 0x01]),
+        ];
+
+        for (len, expected) in cases {
+            let body = vec![0xAB; len];
+            let packet = encode_packet(PUBLISH, &body);
+
+            assert_eq!(packet[0], PUBLISH);
+            assert_eq!(&packet[1..=expected.len()], expected, "{len}");
+            assert_eq!(packet.len(), 1 + expected.len() + len);
+
+            let mut reader = PacketReader::new(&packet[1..]);
+            assert_eq!(reader.variable_int().unwrap(), len);
+            assert_eq!(reader.rest().len(), len);
+        }
+    }
+
+    #[test]
+    fn a_variable_byte_integer_has_at_most_four_bytes() {
+        let mut reader = PacketReader::new(&[0xff, 0xff, 0xff, 0x7f]);
+        assert_eq!(reader.variable_int().unwrap(), 268_435_455);
+
+        let mut reader = PacketReader::new(&[0x80, 0x80, 0x80, 0x80, 0x01]);
+        assert!(reader.variable_int().is_err());
+
+        let mut reader = PacketReader::new(&[0x80]);
+        assert!(reader.variable_int().is_err());
+    }
+
+    #[test]
+    fn the_packet_reader_rejects_truncated_fields() {
+        let mut body = string("topic");
+        body.extend_from_slice(&[0x12, 0x34, 0x56]);
+
+        let mut reader = PacketReader::new(&body);
+        assert_eq!(reader.string().unwrap(), "topic");
+        assert_eq!(reader.u16().unwrap(), 0x1234);
+        assert!(reader.has_remaining());
+        assert!(reader.u16().is_err());
+
+        // The length prefix claims more bytes than the packet has.
+        let mut reader = PacketReader::new(&[0x00, 0x05, b'a', b'b']);
+        assert!(reader.string().is_err());
+
+        let mut reader = PacketReader::new(&[0x00, 0x02, 0xff, 0xfe]);
+        assert!(reader.string().is_err());
+    }
+
+    #[tokio::test]
+    async fn packets_are_read_as_they_are_encoded() {
+        let mut bytes = encode_packet(PINGREQ, &[]);
+        bytes.extend(encode_ack(PUBACK, 0x0102));
+        let mut reader = bytes.as_slice();
+
+        assert_eq!(
+            read_packet(&mut reader).await.unwrap(),
+            Some((PINGREQ, vec![]))
+        );
+        assert_eq!(
+            read_packet(&mut reader).await.unwrap(),
+            Some((PUBACK, vec![0x01, 0x02]))
+        );
+        assert_eq!(read_packet(&mut reader).await.unwrap(), None);
+
+        // The connection is closed before the body is complete.
+        let truncated = encode_packet(PUBLISH, &[0; 10]);
+        let mut reader = &truncated[..5];
+        assert!(read_packet(&mut reader).await.is_err());
+
+        let mut reader: &[u8] = &[PUBLISH, 0x80, 0x80, 0x80, 0x80, 0x01];
+        assert!(read_packet(&mut reader).await.is_err());
+    }
+
+    #[test]
+    fn connect_packets_of_both_protocol_levels_are_parsed() {
+        // MQTT 3.1.1 with a will, a user name and a password.
+        let mut body = string("MQTT");
+        body.extend_from_slice(&[MQTT_V3_1_1, 0xC4, 0x00, 0x3c]);
+        body.extend(string("client"));
+        body.extend(string("will/topic"));
+        body.extend(string("gone"));
+        body.extend(string("user"));
+        body.extend(string("secret"));
+        assert_eq!(parse_connect(&body).unwrap(), MQTT_V3_1_1);
+
+        // MQTT 5 with connect and will properties.
+        let mut body = string("MQTT");
+        body.extend_from_slice(&[MQTT_V5, 0x04, 0x00, 0x3c]);
+        body.extend_from_slice(&[0x05, 0x11, 0x00, 0x00, 0x00, 0x0a]);
+        body.extend(string("client"));
+        body.extend_from_slice(&[0x00]);
+        body.extend(string("will/topic"));
+        body.extend(string("gone"));
+        assert_eq!(parse_connect(&body).unwrap(), MQTT_V5);
+
+        // The will is announced but missing.
+        let mut body = string("MQTT");
+        body.extend_from_slice(&[MQTT_V3_1_1, 0x04, 0x00, 0x3c]);
+        body.extend(string("client"));
+        assert!(parse_connect(&body).is_err());
+    }
+
+    #[test]
+    fn publish_packets_follow_the_protocol_level_of_the_session() {
+        let (sender, _receiver) = mpsc::unbounded_channel();
+        let mut session = Session {
+            protocol_level: MQTT_V3_1_1,
+            sender,
+            subscriptions: HashMap::new(),
+            next_packet_id: 0,
+        };
+        let hi = message("t", "hi");
+
+        assert_eq!(
+            encode_publish(&mut session, 0, &hi),
+            [PUBLISH, 5, 0x00, 0x01, b't', b'h', b'i']
+        );
+        assert_eq!(
+            encode_publish(&mut session, 1, &hi),
+            [PUBLISH | 0x02, 7, 0x00, 0x01, b't', 0x00, 0x01, b'h', b'i']
+        );
+
+        // MQTT 5 adds the length of the properties, which are empty.
+        session.protocol_level = MQTT_V5;
+        assert_eq!(
+            encode_publish(&mut session, 1, &hi),
+            [
+                PUBLISH | 0x02,
+                8,
+                0x00,
+                0x01,
+                b't',
+                0x00,
+                0x02,
+                0x00,
+                b'h',
+                b'i'
+            ]
+        );
+    }
+
+    #[test]
+    fn the_packet_id_skips_zero_when_it_wraps() {
+        let (sender, _receiver) = mpsc::unbounded_channel();
+        let mut session = Session {
+            protocol_level: MQTT_V5,
+            sender,
+            subscriptions: HashMap::new(),
+            next_packet_id: u16::MAX - 1,
+        };
+
+        assert_eq!(session.next_packet_id(), u16::MAX);
+        assert_eq!(session.next_packet_id(), 1);
+        assert_eq!(session.next_packet_id(), 2);
+    }
+
+    #[test]
+    fn messages_are_routed_to_matching_subscriptions_only() {
+        let inner = broker();
+        let mut messages = inner.messages.subscribe();
+        let (_, mut exact) = connect(&inner, MQTT_V3_1_1, &[("a/b", 1)]);
+        let (_, mut wildcard) = connect(&inner, MQTT_V3_1_1, &[("a/+", 0), ("a/#", 1)]);
+        let (_, mut other) = connect(&inner, MQTT_V3_1_1, &[("c", 1)]);
+
+        route(&inner, 1, message("a/b", "x"));
+
+        // Each session gets the message once, with the highest QoS of its matching filters.
+        let packet = exact.try_recv().unwrap();
+        assert_eq!(packet[0], PUBLISH | 0x02);
+        assert!(exact.try_recv().is_err());
+        let packet = wildcard.try_recv().unwrap();
+        assert_eq!(packet[0], PUBLISH | 0x02);
+        assert!(wildcard.try_recv().is_err());
+        assert!(other.try_recv().is_err());
+
+        assert_eq!(*inner.published.lock(), [message("a/b", "x")]);
+        assert_eq!(messages.try_recv().unwrap(), message("a/b", "x"));
+    }
+
+    #[test]
+    fn messages_are_delivered_with_the_lower_of_published_and_granted_qos() {
+        let inner = broker();
+        let (_, mut at_most_once) = connect(&inner, MQTT_V3_1_1, &[("t", 0)]);
+        let (_, mut at_least_once) = connect(&inner, MQTT_V3_1_1, &[("t", 1)]);
+
+        route(&inner, 1, message("t", "x"));
+        assert_eq!(at_most_once.try_recv().unwrap()[0], PUBLISH);
+        assert_eq!(at_least_once.try_recv().unwrap()[0], PUBLISH | 0x02);
+
+        route(&inner, 0, message("t", "y"));
+        assert_eq!(at_most_once.try_recv().unwrap()[0], PUBLISH);
+        assert_eq!(at_least_once.try_recv().unwrap()[0], PUBLISH);
+    }
+
+    #[test]
+    fn publishes_are_acknowledged_by_qos() {
+        let inner = broker();
+        let mut body = string("t");
+        body.extend_from_slice(&[0x00, 0x07]);
+        body.extend_from_slice(b"x");
+
+        assert_eq!(
+            answers(&inner, MQTT_V3_1_1, PUBLISH | 0x02, &body),
+            [encode_ack(PUBACK, 7)]
+        );
+        assert_eq!(
+            answers(&inner, MQTT_V3_1_1, PUBLISH | 0x04, &body),
+            [encode_ack(PUBREC, 7)]
+        );
+        assert_eq!(
+            answers(&inner, MQTT_V3_1_1, PUBREL, &[0x00, 0x07]),
+            [encode_ack(PUBCOMP, 7)]
+        );
+
+        // A QoS 0 publish has no packet id and is not acknowledged.
+        let mut body = string("t");
+        body.extend_from_slice(b"x");
+        assert!(answers(&inner, MQTT_V3_1_1, PUBLISH, &body).is_empty());
+
+        assert_eq!(
+            *inner.published.lock(),
+            [message("t", "x"), message("t", "x"), message("t", "x")]
+        );
+    }
+
+    #[test]
+    fn subscriptions_are_granted_at_most_qos_1() {
+        let inner = broker();
+        let mut body = vec![0x00, 0x09];
+        body.extend(string("a/#"));
+        body.push(0x02);
+        body.extend(string("b"));
+        body.push(0x00);
+
+        let (session_id, mut receiver) = connect(&inner, MQTT_V3_1_1, &[]);
+        let sender = inner.state.lock().sessions[&session_id].sender.clone();
+        handle_packet(
+            &inner,
+            session_id,
+            MQTT_V3_1_1,
+            &sender,
+            SUBSCRIBE | 0x02,
+            &body,
+        )
+        .unwrap();
+
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            encode_packet(SUBACK, &[0x00, 0x09, 0x01, 0x00])
+        );
+        let state = inner.state.lock();
+        let subscriptions = &state.sessions[&session_id].subscriptions;
+        assert_eq!(subscriptions.get("a/#"), Some(&1));
+        assert_eq!(subscriptions.get("b"), Some(&0));
+    }
+
+    #[test]
+    fn unsubscribing_reports_missing_subscriptions_with_mqtt_5() {
+        let inner = broker();
+        let mut body = vec![0x00, 0x03, 0x00];
+        body.extend(string("a"));
+        body.extend(string("b"));
+
+        let (session_id, mut receiver) = connect(&inner, MQTT_V5, &[("a", 1)]);
+        let sender = inner.state.lock().sessions[&session_id].sender.clone();
+        handle_packet(
+            &inner,
+            session_id,
+            MQTT_V5,
+            &sender,
+            UNSUBSCRIBE | 0x02,
+            &body,
+        )
+        .unwrap();
+
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            encode_packet(UNSUBACK, &[0x00, 0x03, 0x00, 0x00, 0x11])
+        );
+        assert!(inner.state.lock().sessions[&session_id]
+            .subscriptions
+            .is_empty());
+    }
+
+    #[test]
+    fn pings_are_answered_and_disconnect_ends_the_session() {
+        let inner = broker();
+        assert_eq!(
+            answers(&inner, MQTT_V5, PINGREQ, &[]),
+            [encode_packet(PINGRESP, &[])]
+        );
+
+        let (session_id, _receiver) = connect(&inner, MQTT_V5, &[]);
+        let sender = inner.state.lock().sessions[&session_id].sender.clone();
+        let session_continues =
+            handle_packet(&inner, session_id, MQTT_V5, &sender, DISCONNECT, &[]).unwrap();
+        assert!(!session_continues);
+    }
+}
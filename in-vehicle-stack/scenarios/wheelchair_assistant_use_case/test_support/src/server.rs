@@ -0,0 +1,90 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! Serving fake gRPC services on ephemeral ports.
+
+use std::io;
+use std::net::SocketAddr;
+
+use log::{debug, warn};
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::server::Router;
+
+/// A gRPC server running in the background, stopped when the handle is dropped.
+#[derive(Debug)]
+pub struct ServerHandle {
+    addr: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl ServerHandle {
+    /// The address that the server listens on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The server's authority, e.g. "127.0.0.1:41234".
+    pub fn authority(&self) -> String {
+        self.addr.to_string()
+    }
+
+    /// The server's URI, e.g. "http://127.0.0.1:41234".
+    pub fn uri(&self) -> String {
+        format!("http://{}", self.addr) // Devskim: ignore DS137138
+    }
+
+    /// Stop the server and wait until it has stopped.
+    pub async fn shutdown(mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+    }
+}
+
+impl Drop for ServerHandle {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+/// Serve a router on an ephemeral port of the loopback interface.
+///
+/// # Arguments
+/// * `router` - The router with the services to serve.
+pub async fn serve(router: Router) -> io::Result<ServerHandle> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let (shutdown_sender, shutdown_receiver) = oneshot::channel::<()>();
+
+    let task = tokio::spawn(async move {
+        let result = router
+            .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async {
+                let _ = shutdown_receiver.await;
+            })
+            .await;
+
+        match result {
+            Ok(()) => debug!("The server on {addr} has stopped."),
+            Err(err) => warn!("The server on {addr} failed due to '{err}'"),
+        }
+    });
+
+    debug!("Serving on {addr}");
+
+    Ok(ServerHandle {
+        addr,
+        shutdown: Some(shutdown_sender),
+        task: Some(task),
+    })
+}
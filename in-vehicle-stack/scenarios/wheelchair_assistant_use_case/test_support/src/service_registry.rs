@@ -0,0 +1,157 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! A fake of Chariott's Service Registry.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use interfaces::chariott::service_discovery::core::v1::service_registry_server::ServiceRegistry;
+use interfaces::chariott::service_discovery::core::v1::{
+    DiscoverByNamespaceRequest, DiscoverByNamespaceResponse, DiscoverRequest, DiscoverResponse,
+    ListRequest, ListResponse, RegisterRequest, RegisterResponse, ServiceMetadata,
+    UnregisterRequest, UnregisterResponse,
+};
+use log::debug;
+use parking_lot::RwLock;
+use tonic::{Request, Response, Status};
+
+/// The namespace, name and version that identify a service.
+type ServiceKey = (String, String, String);
+
+/// Keeps the registered services in memory.
+#[derive(Clone, Debug, Default)]
+pub struct FakeServiceRegistry {
+    services: Arc<RwLock<HashMap<ServiceKey, ServiceMetadata>>>,
+}
+
+impl FakeServiceRegistry {
+    /// Add a service, replacing a service with the same namespace, name and version.
+    ///
+    /// # Arguments
+    /// * `service` - The service to add.
+    pub fn insert(&self, service: ServiceMetadata) {
+        let key = (
+            service.namespace.clone(),
+            service.name.clone(),
+            service.version.clone(),
+        );
+        self.services.write().insert(key, service);
+    }
+
+    /// Get all registered services.
+    pub fn services(&self) -> Vec<ServiceMetadata> {
+        self.services.read().values().cloned().collect()
+    }
+}
+
+#[tonic::async_trait]
+impl ServiceRegistry for FakeServiceRegistry {
+    /// Register a service, fails if it is already registered.
+    ///
+    /// # Arguments
+    /// * `request` - The request with the service.
+    async fn register(
+        &self,
+        request: Request<RegisterRequest>,
+    ) -> Result<Response<RegisterResponse>, Status> {
+        let service = request
+            .into_inner()
+            .service
+            .ok_or_else(|| Status::invalid_argument("The service is required"))?;
+        let key = (
+            service.namespace.clone(),
+            service.name.clone(),
+            service.version.clone(),
+        );
+
+        let mut services = self.services.write();
+        if services.contains_key(&key) {
+            return Err(Status::already_exists(format!(
+                "The service {key:?} is already registered"
+            )));
+        }
+
+        debug!("Registered service {key:?}");
+        services.insert(key, service);
+
+        Ok(Response::new(RegisterResponse {}))
+    }
+
+    /// Unregister a service.
+    ///
+    /// # Arguments
+    /// * `request` - The request with the service's namespace, name and version.
+    async fn unregister(
+        &self,
+        request: Request<UnregisterRequest>,
+    ) -> Result<Response<UnregisterResponse>, Status> {
+        let request = request.into_inner();
+        let key = (request.namespace, request.name, request.version);
+
+        match self.services.write().remove(&key) {
+            Some(_) => Ok(Response::new(UnregisterResponse {})),
+            None => Err(Status::not_found(format!(
+                "The service {key:?} is not registered"
+            ))),
+        }
+    }
+
+    /// Discover a service by its namespace, name and exact version.
+    ///
+    /// # Arguments
+    /// * `request` - The request with the service's namespace, name and version.
+    async fn discover(
+        &self,
+        request: Request<DiscoverRequest>,
+    ) -> Result<Response<DiscoverResponse>, Status> {
+        let request = request.into_inner();
+        let key = (request.namespace, request.name, request.version);
+
+        match self.services.read().get(&key) {
+            Some(service) => Ok(Response::new(DiscoverResponse {
+                service: Some(service.clone()),
+            })),
+            None => Err(Status::not_found(format!(
+                "The service {key:?} is not registered"
+            ))),
+        }
+    }
+
+    /// Discover all services in a namespace.
+    ///
+    /// # Arguments
+    /// * `request` - The request with the namespace.
+    async fn discover_by_namespace(
+        &self,
+        request: Request<DiscoverByNamespaceRequest>,
+    ) -> Result<Response<DiscoverByNamespaceResponse>, Status> {
+        let namespace = request.into_inner().namespace;
+        let services: Vec<ServiceMetadata> = self
+            .services
+            .read()
+            .values()
+            .filter(|service| service.namespace == namespace)
+            .cloned()
+            .collect();
+
+        if services.is_empty() {
+            return Err(Status::not_found(format!(
+                "No service is registered in namespace '{namespace}'"
+            )));
+        }
+
+        Ok(Response::new(DiscoverByNamespaceResponse { services }))
+    }
+
+    /// List all services.
+    ///
+    /// # Arguments
+    /// * `_request` - The empty request.
+    async fn list(&self, _request: Request<ListRequest>) -> Result<Response<ListResponse>, Status> {
+        Ok(Response::new(ListResponse {
+            services: self.services(),
+        }))
+    }
+}
@@ -0,0 +1,230 @@
+// Copyright (c) IAV  GmbH.
+// Licensed under the MIT license.
+// SPDX-License-Identifier: MIT
+
+//! A round trip through a managed subscribe provider: the consumer discovers the In-Vehicle
+//! Digital Twin through Chariott, finds the provider's entity in Ibeji, subscribes through the
+//! Managed Subscribe module and receives the provider's values from the broker.
+
+use interfaces::module::managed_subscribe::v1::managed_subscribe_callback_server::ManagedSubscribeCallbackServer;
+use interfaces::module::managed_subscribe::v1::managed_subscribe_client::ManagedSubscribeClient;
+use interfaces::module::managed_subscribe::v1::{Constraint, SubscriptionInfoRequest};
+use paho_mqtt as mqtt;
+use serde_json::Value;
+use tokio::sync::{broadcast, watch};
+use tokio::time::{timeout, Duration};
+use tonic::transport::Server;
+use tonic::Request;
+use wheelchair_digital_twin_providers_common::constants::chariott::{
+    INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_KIND,
+    INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_REFERENCE, INVEHICLE_DIGITAL_TWIN_SERVICE_NAME,
+    INVEHICLE_DIGITAL_TWIN_SERVICE_NAMESPACE, INVEHICLE_DIGITAL_TWIN_SERVICE_VERSION,
+};
+use wheelchair_digital_twin_providers_common::constants::{
+    constraint_type, digital_twin_operation, digital_twin_protocol,
+};
+use wheelchair_digital_twin_providers_common::managed_subscribe_provider::{
+    register_managed_subscribe_entity, ManagedSubscribeProvider,
+};
+use wheelchair_digital_twin_providers_common::shutdown::DRAIN_TIMEOUT;
+use wheelchair_digital_twin_providers_common::utils::{
+    discover_digital_twin_provider_using_ibeji, discover_service_using_chariott, ProtocolMatching,
+};
+use wheelchair_test_support::mqtt_broker::PublishedMessage;
+use wheelchair_test_support::server::serve;
+use wheelchair_test_support::TestEnvironment;
+
+const ENTITY_ID: &str = "dtmi:sdv:Test:Counter;1";
+const ENTITY_NAME: &str = "Counter";
+const ENTITY_DESCRIPTION: &str = "A counter for testing.";
+const MIN_INTERVAL_MS: u64 = 50;
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Wait for the next message that is published to a topic.
+///
+/// # Arguments
+/// * `messages` - Receiver for the messages that are published to the broker.
+/// * `topic` - The topic.
+async fn next_message(
+    messages: &mut broadcast::Receiver<PublishedMessage>,
+    topic: &str,
+) -> PublishedMessage {
+    timeout(RECEIVE_TIMEOUT, async {
+        loop {
+            let message = messages.recv().await.expect("The broker should be running");
+            if message.topic == topic {
+                return message;
+            }
+        }
+    })
+    .await
+    .expect("A message should be published in time")
+}
+
+/// Get the counter from a published property.
+///
+/// # Arguments
+/// * `payload` - The published property.
+fn counter(payload: &str) -> i64 {
+    let property: Value = serde_json::from_str(payload).expect("The property should be JSON");
+    assert_eq!(property["$metadata"]["$model"], ENTITY_ID);
+
+    property[ENTITY_NAME]
+        .as_i64()
+        .expect("The property should have the counter")
+}
+
+/// Receive one message with an MQTT 5 client, as the applications do.
+///
+/// # Arguments
+/// * `broker_uri` - The broker's URI.
+/// * `topic` - The topic to subscribe to.
+fn receive_with_mqtt_5(broker_uri: &str, topic: &str) -> Option<String> {
+    let create_opts = mqtt::CreateOptionsBuilder::new()
+        .server_uri(broker_uri)
+        .client_id("round-trip-subscriber")
+        .persistence(mqtt::PersistenceType::None)
+        .finalize();
+    let client = mqtt::Client::new(create_opts).expect("The client should be created");
+    let receiver = client.start_consuming();
+
+    let conn_opts = mqtt::ConnectOptionsBuilder::new_v5()
+        .clean_start(true)
+        .finalize();
+    client
+        .connect(conn_opts)
+        .expect("The client should connect");
+    client
+        .subscribe(topic, mqtt::types::QOS_1)
+        .expect("The client should subscribe");
+
+    let payload = receiver
+        .recv_timeout(RECEIVE_TIMEOUT)
+        .ok()
+        .flatten()
+        .map(|message| message.payload_str().to_string());
+
+    client
+        .disconnect(None)
+        .expect("The client should disconnect");
+
+    payload
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_consumer_receives_the_values_of_a_managed_subscribe_provider() {
+    let env = TestEnvironment::start()
+        .await
+        .expect("The test environment should start");
+
+    // The consumer finds the In-Vehicle Digital Twin through Chariott.
+    let invehicle_digital_twin_uri = discover_service_using_chariott(
+        &env.chariott_uri(),
+        INVEHICLE_DIGITAL_TWIN_SERVICE_NAMESPACE,
+        INVEHICLE_DIGITAL_TWIN_SERVICE_NAME,
+        INVEHICLE_DIGITAL_TWIN_SERVICE_VERSION,
+        INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_KIND,
+        INVEHICLE_DIGITAL_TWIN_SERVICE_COMMUNICATION_REFERENCE,
+        ProtocolMatching::Strict,
+    )
+    .await
+    .expect("The In-Vehicle Digital Twin should be discovered");
+    assert_eq!(invehicle_digital_twin_uri, env.invehicle_digital_twin_uri());
+
+    // The provider serves its callback and registers its entity.
+    let (counter_sender, data_stream) = watch::channel(1);
+    let provider =
+        ManagedSubscribeProvider::new(ENTITY_ID, ENTITY_NAME, data_stream, MIN_INTERVAL_MS);
+    let provider_server =
+        serve(Server::builder().add_service(ManagedSubscribeCallbackServer::new(provider.clone())))
+            .await
+            .expect("The provider should be served");
+    register_managed_subscribe_entity(
+        &invehicle_digital_twin_uri,
+        &provider_server.uri(),
+        ENTITY_ID,
+        ENTITY_NAME,
+        ENTITY_DESCRIPTION,
+    )
+    .await
+    .expect("The entity should be registered");
+
+    // The consumer finds the entity's managed subscribe endpoint, which Ibeji has routed to the
+    // Managed Subscribe module.
+    let endpoint_info = discover_digital_twin_provider_using_ibeji(
+        &invehicle_digital_twin_uri,
+        ENTITY_ID,
+        digital_twin_protocol::GRPC,
+        &[digital_twin_operation::MANAGEDSUBSCRIBE.to_string()],
+    )
+    .await
+    .expect("The entity should be discovered");
+    assert_eq!(endpoint_info.uri, env.managed_subscribe_uri());
+
+    let mut messages = env.broker.messages();
+    let mut client = ManagedSubscribeClient::connect(endpoint_info.uri)
+        .await
+        .expect("The Managed Subscribe module should be reachable");
+    let subscription_info = client
+        .get_subscription_info(Request::new(SubscriptionInfoRequest {
+            entity_id: ENTITY_ID.to_string(),
+            constraints: vec![Constraint {
+                r#type: constraint_type::ON_CHANGE.to_string(),
+                value: true.to_string(),
+            }],
+        }))
+        .await
+        .expect("The subscription should be created")
+        .into_inner();
+    assert_eq!(subscription_info.uri, env.broker_uri());
+    let topic = subscription_info.context;
+
+    // The current value is published first, then every change.
+    let message = next_message(&mut messages, &topic).await;
+    assert_eq!(counter(&message.payload_str()), 1);
+
+    counter_sender.send_replace(2);
+    let message = next_message(&mut messages, &topic).await;
+    assert_eq!(counter(&message.payload_str()), 2);
+
+    // A subscriber on the broker receives the next change.
+    let broker_uri = env.broker_uri();
+    let subscriber_topic = topic.clone();
+    let subscriber =
+        tokio::task::spawn_blocking(move || receive_with_mqtt_5(&broker_uri, &subscriber_topic));
+    timeout(RECEIVE_TIMEOUT, async {
+        while env.broker.subscriber_count(&topic) == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("The subscriber should subscribe in time");
+
+    counter_sender.send_replace(3);
+    let payload = subscriber
+        .await
+        .unwrap()
+        .expect("The subscriber should receive the change");
+    assert_eq!(counter(&payload), 3);
+
+    // Once the topic is stopped, changes are no longer published.
+    env.managed_subscribe
+        .stop_publish(&topic)
+        .await
+        .expect("The topic should be stopped");
+    assert!(env.managed_subscribe.topics().is_empty());
+
+    // Give the provider's publishing thread time to see that the topic has stopped.
+    tokio::time::sleep(Duration::from_millis(2 * MIN_INTERVAL_MS)).await;
+    counter_sender.send_replace(4);
+    let after_stop = timeout(
+        Duration::from_millis(10 * MIN_INTERVAL_MS),
+        next_message(&mut messages, &topic),
+    )
+    .await;
+    assert!(after_stop.is_err());
+
+    provider.shutdown(DRAIN_TIMEOUT).await;
+    provider_server.shutdown().await;
+    env.shutdown().await;
+}
@@ -7,16 +7,13 @@ set -e
 
 # Deklariere das Array
 PROVIDER_CONTAINERS=(
-  "car_off_provider"
-  "car_on_provider"
-  "carkey_lock_provider"
-  "carkey_unlock_provider"
+  "vehicle_body_provider"
   "wheelchair_distance_decreasing_provider"
   "wheelchair_distance_increasing_provider"
 )
 
 PROVIDER_CONTAINERS=(
-  "car_off_provider"
+  "vehicle_body_provider"
 )
 
 # Get the directory of where the script is located